@@ -56,6 +56,7 @@ Description=${serviceName}
 [Service]
 Type=simple
 WorkingDirectory=${rootPath}
+Environment=MEMWEB_DATA_DIR=${rootPath}/data
 ExecStart=${execPath}
 Restart=always
 
@@ -1,2 +1,8 @@
-mod memory;
-mod session;
+pub mod memory;
+pub mod pointer;
+pub mod scan;
+pub mod search;
+pub mod session;
+pub mod simulated;
+pub mod snapshot;
+pub mod storage;
@@ -1,4 +1,5 @@
 use log::info;
+use memweb::{memory, session};
 use std::env;
 use std::net::TcpListener;
 use std::thread::spawn;
@@ -7,19 +8,24 @@ use tungstenite::{
     handshake::server::{Request, Response},
 };
 
-mod memory;
-mod session;
-
 fn main() {
-    let _ = env_logger::init();
+    env_logger::init();
     let addr = env::args()
         .nth(1)
         .unwrap_or_else(|| "127.0.0.1:8069".to_string());
-    info!("Listening on: {}", addr);
+    // The backend sessions start with, clients may select another.
+    let backend = env::var("MEMWEB_BACKEND").unwrap_or_else(|_| memory::BACKENDS[0].to_string());
+    if memory::backend(backend.as_bytes()).is_none() {
+        eprintln!("Unknown MEMWEB_BACKEND '{}', expected one of {:?}", backend, memory::BACKENDS);
+        std::process::exit(1);
+    }
+    info!("Listening on: {} with the {} backend", addr, backend);
     let server = TcpListener::bind(addr).unwrap();
 
     for stream in server.incoming() {
+        let backend = backend.clone();
         spawn(move || {
+            #[allow(clippy::result_large_err)] // the error type is dictated by tungstenite
             let callback = |req: &Request, mut response: Response| {
                 println!("Received a new ws handshake");
                 println!("The request's path is: {}", req.uri().path());
@@ -37,16 +43,9 @@ fn main() {
             };
 
             let websocket = accept_hdr(stream.unwrap(), callback).unwrap();
-            let mut session = session::ClientSession::new(websocket);
+            let mut session = session::ClientSession::new(websocket, memory::backend(backend.as_bytes()).unwrap());
 
-            loop {
-                let msg = session.websocket.read_message().unwrap();
-
-                // We do not want to send back ping/pong messages.
-                if msg.is_binary() || msg.is_text() {
-                    session.message_handler(msg);
-                }
-            }
+            session.run();
         });
     }
 }
@@ -1,139 +1,713 @@
 use errno::errno;
-use libc::{c_int, c_void, iovec, pid_t, syscall, SYS_process_vm_readv, SYS_process_vm_writev};
+use libc::{c_ulong, c_void, iovec, pid_t, syscall, SYS_process_vm_readv, SYS_process_vm_writev};
+use crate::simulated::SimulatedMemory;
+use shared::expression::MemoryReader;
+use shared::process::{get_regions, get_running_processes};
+use shared::protocol::{page_chunks, ProcessEntry, Region};
+use std::fs::File;
 use std::io;
+use std::ops::Range;
+use std::os::unix::fs::FileExt;
 
-fn process_vm_writev(
-    pid: pid_t,
-    local_iov: &iovec,
-    liovcnt: c_int,
-    remote_iov: &iovec,
-    riovcnt: c_int,
-) -> isize {
+/// Maximum number of iovecs per syscall (UIO_MAXIOV on Linux).
+const IOV_MAX: usize = 1024;
+
+fn process_vm_writev(pid: pid_t, local_iov: &[iovec], remote_iov: &[iovec]) -> isize {
     unsafe {
         syscall(
             SYS_process_vm_writev,
             pid,
-            local_iov as *const iovec,
-            liovcnt,
-            remote_iov as *const iovec,
-            riovcnt,
+            local_iov.as_ptr(),
+            local_iov.len() as c_ulong,
+            remote_iov.as_ptr(),
+            remote_iov.len() as c_ulong,
+            0 as c_ulong,
         ) as isize
     }
 }
 
-fn process_vm_readev(
-    pid: pid_t,
-    local_iov: &iovec,
-    liovcnt: c_int,
-    remote_iov: &iovec,
-    riovcnt: c_int,
-) -> isize {
+fn process_vm_readev(pid: pid_t, local_iov: &[iovec], remote_iov: &[iovec]) -> isize {
     unsafe {
         syscall(
             SYS_process_vm_readv,
             pid,
-            local_iov as *const iovec,
-            liovcnt,
-            remote_iov as *const iovec,
-            riovcnt,
+            local_iov.as_ptr(),
+            local_iov.len() as c_ulong,
+            remote_iov.as_ptr(),
+            remote_iov.len() as c_ulong,
+            0 as c_ulong,
         ) as isize
     }
 }
 
-pub struct Memory {
-    pub pid: pid_t,
+/// Errors that concern the whole process rather than a single address range.
+/// ENOSYS means the syscalls are missing or filtered by seccomp.
+fn is_process_error(error: &io::Error) -> bool {
+    matches!(error.raw_os_error(), Some(libc::ESRCH) | Some(libc::EPERM) | Some(libc::ENOSYS))
+        || error.kind() == io::ErrorKind::NotConnected
 }
 
-impl Memory {
-    pub fn new(pid: i32) -> Memory {
-        Memory { pid }
-    }
+/// Opens `/proc/<pid>/mem`, failing like `process_vm_readv` would for a
+/// missing process or one we may not trace.
+fn open_proc_mem(pid: pid_t, write: bool) -> io::Result<File> {
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(write)
+        .open(format!("/proc/{}/mem", pid))
+        .map_err(|error| match error.raw_os_error() {
+            Some(libc::ENOENT) => io::Error::from_raw_os_error(libc::ESRCH),
+            Some(libc::EACCES) => io::Error::from_raw_os_error(libc::EPERM),
+            _ => error,
+        })
+}
 
-    #[cfg(not(feature = "fake_read_write"))]
-    pub fn read(&self, address: u64, size: usize) -> io::Result<Vec<u8>> {
-        if self.pid == -1 {
-            return Err(io::Error::new(io::ErrorKind::Other, "PID not set!"));
+/// Transfers `size` bytes at `address` with `transfer`, a pread or pwrite
+/// that may stop short. Unmapped memory fails like it does for the
+/// `process_vm` syscalls: EFAULT if nothing was transferred, the `partial`
+/// error if only some of it was.
+fn transfer_proc_mem(
+    address: u64,
+    size: usize,
+    partial: (io::ErrorKind, &str),
+    mut transfer: impl FnMut(usize, u64) -> io::Result<usize>,
+) -> io::Result<()> {
+    let mut done = 0;
+    while done < size {
+        // Offsets past i64::MAX are rejected with EINVAL, they are not user memory anyway.
+        let result = match address.checked_add(done as u64).filter(|&offset| offset <= i64::MAX as u64) {
+            Some(offset) => transfer(done, offset),
+            None => Err(io::Error::from_raw_os_error(libc::EINVAL)),
+        };
+        match result {
+            Ok(0) => break,
+            Ok(count) => done += count,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) if done == 0 => {
+                return Err(match error.raw_os_error() {
+                    Some(libc::EIO) | Some(libc::EINVAL) => io::Error::from_raw_os_error(libc::EFAULT),
+                    _ => error,
+                });
+            }
+            Err(_) => break,
         }
+    }
 
-        let mut result = vec![0; size];
-        let local_iov = iovec {
-            iov_base: result.as_mut_ptr() as *mut c_void,
-            iov_len: size,
-        };
-        let remote_iov = iovec {
+    match done {
+        0 if size > 0 => Err(io::Error::from_raw_os_error(libc::EFAULT)),
+        done if done < size => Err(io::Error::new(partial.0, partial.1)),
+        _ => Ok(()),
+    }
+}
+
+fn proc_mem_read(file: &File, address: u64, size: usize) -> io::Result<Vec<u8>> {
+    let mut result = vec![0; size];
+    transfer_proc_mem(address, size, (io::ErrorKind::UnexpectedEof, "Partial read occurred!"), |done, offset| {
+        file.read_at(&mut result[done..], offset)
+    })?;
+    Ok(result)
+}
+
+fn proc_mem_write(file: &File, address: u64, bytes: &[u8]) -> io::Result<usize> {
+    transfer_proc_mem(address, bytes.len(), (io::ErrorKind::WriteZero, "Partial write occurred!"), |done, offset| {
+        file.write_at(&bytes[done..], offset)
+    })?;
+    Ok(bytes.len())
+}
+
+fn remote_iovecs(ranges: &[(u64, usize)]) -> Vec<iovec> {
+    ranges
+        .iter()
+        .map(|&(address, size)| iovec {
             iov_base: address as *mut c_void,
             iov_len: size,
-        };
+        })
+        .collect()
+}
+
+/// Drives a vectored syscall over ranges of the given sizes, at most
+/// `IOV_MAX` at a time. The syscall stops at the first range it cannot
+/// transfer completely, so that range is marked as failed and the next call
+/// resumes right after it. Returns the error of every failed range.
+fn transfer_many(
+    sizes: &[usize],
+    partial: io::ErrorKind,
+    mut transfer: impl FnMut(Range<usize>) -> isize,
+) -> io::Result<Vec<Option<io::Error>>> {
+    let mut errors: Vec<Option<io::Error>> = sizes.iter().map(|_| None).collect();
+
+    let mut next = 0;
+    loop {
+        // The kernel skips empty ranges, so a failure belongs to the first
+        // non-empty one.
+        while sizes.get(next) == Some(&0) {
+            next += 1;
+        }
+        if next == sizes.len() {
+            break;
+        }
+
+        let batch = next..sizes.len().min(next + IOV_MAX);
+        let transferred = transfer(batch.clone());
 
-        let bytes_read = process_vm_readev(self.pid, &local_iov, 1, &remote_iov, 1);
-
-        if bytes_read == -1 {
-            let e = errno();
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Error {}: {}", e.0, e),
-            ));
-        } else if bytes_read as usize != size {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Partial read occurred!",
-            ));
-        } else {
-            Ok(result)
+        if transferred == -1 {
+            let error = io::Error::from_raw_os_error(errno().0);
+            if is_process_error(&error) {
+                return Err(error);
+            }
+            errors[next] = Some(error);
+            next += 1;
+            continue;
         }
+
+        let start = next;
+        let mut remaining = transferred as usize;
+        for &size in &sizes[batch.clone()] {
+            if size > remaining {
+                break;
+            }
+            remaining -= size;
+            next += 1;
+        }
+
+        // Either part of a range went through or nothing did at all.
+        if next < batch.end && (remaining > 0 || next == start) {
+            errors[next] = Some(io::Error::new(partial, "Partial transfer occurred!"));
+            next += 1;
+        }
+    }
+
+    Ok(errors)
+}
+
+/// Names accepted by `backend`, the default first.
+pub const BACKENDS: [&str; 4] = [Auto::NAME, ProcessVm::NAME, ProcMem::NAME, SimulatedMemory::NAME];
+
+/// A new backend called `name`, not attached to any process yet.
+pub fn backend(name: &[u8]) -> Option<Box<dyn MemoryBackend>> {
+    match std::str::from_utf8(name).ok()? {
+        Auto::NAME => Some(Box::new(Auto::new(-1))),
+        ProcessVm::NAME => Some(Box::new(ProcessVm::new(-1))),
+        ProcMem::NAME => Some(Box::new(ProcMem::new(-1))),
+        SimulatedMemory::NAME => Some(Box::new(SimulatedMemory::demo())),
+        _ => None,
+    }
+}
+
+/// Page size of the host, the granularity of `MemoryBackend::read_tolerant`.
+pub fn page_size() -> u64 {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as u64 }
+}
+
+/// Fails like every backend does before a target is set.
+pub fn check_pid(pid: pid_t) -> io::Result<()> {
+    match pid {
+        -1 => Err(io::Error::new(io::ErrorKind::NotConnected, "PID not set!")),
+        _ => Ok(()),
+    }
+}
+
+/// How a session reaches its target: the target's memory and mappings, and
+/// the processes it may pick a target from.
+pub trait MemoryBackend: Send {
+    /// Name announced to clients and accepted by `C2SSelectBackendPacket`.
+    fn name(&self) -> &'static str;
+
+    /// The target process, -1 if none was set.
+    fn pid(&self) -> pid_t;
+
+    fn set_pid(&mut self, pid: pid_t);
+
+    fn read(&self, address: u64, size: usize) -> io::Result<Vec<u8>>;
+
+    fn write(&self, address: u64, bytes: &[u8]) -> io::Result<usize>;
+
+    fn regions(&self) -> io::Result<Vec<Region>>;
+
+    fn processes(&self) -> io::Result<Vec<ProcessEntry>>;
+
+    /// Reads many `(address, size)` ranges. A range that cannot be read
+    /// fails on its own; only process-wide errors such as ESRCH fail the
+    /// whole batch.
+    fn read_many(&self, ranges: &[(u64, usize)]) -> io::Result<Vec<io::Result<Vec<u8>>>> {
+        check_pid(self.pid())?;
+        ranges
+            .iter()
+            .map(|&(address, size)| match self.read(address, size) {
+                Err(error) if is_process_error(&error) => Err(error),
+                result => Ok(result),
+            })
+            .collect()
+    }
+
+    /// Writes many `(address, bytes)` entries. Failures are reported per
+    /// entry like in `read_many`.
+    fn write_many(&self, entries: &[(u64, &[u8])]) -> io::Result<Vec<io::Result<usize>>> {
+        check_pid(self.pid())?;
+        entries
+            .iter()
+            .map(|&(address, bytes)| match self.write(address, bytes) {
+                Err(error) if is_process_error(&error) => Err(error),
+                result => Ok(result),
+            })
+            .collect()
     }
 
-    #[cfg(feature = "fake_read_write")]
-    pub fn read(&self, address: u64, size: usize) -> io::Result<Vec<u8>> {
-        if self.pid == -1 {
-            return Err(io::Error::new(io::ErrorKind::Other, "PID not set!"));
+    /// Reads `[address, address + size)` page by page so that unmapped or guard
+    /// pages do not fail the whole range. Their bytes are left zeroed and they
+    /// are reported as `false` in the returned per-page list.
+    fn read_tolerant(&self, address: u64, size: usize) -> io::Result<(Vec<u8>, Vec<bool>)> {
+        let pages: Vec<(u64, usize)> = page_chunks(address, size as u64, page_size()).collect();
+
+        let mut data = Vec::with_capacity(size);
+        let mut readable = Vec::with_capacity(pages.len());
+        for (result, &(_, page_size)) in self.read_many(&pages)?.into_iter().zip(&pages) {
+            readable.push(result.is_ok());
+            match result {
+                Ok(bytes) => data.extend(bytes),
+                Err(_) => data.resize(data.len() + page_size, 0),
+            }
         }
+        Ok((data, readable))
+    }
+}
+
+impl MemoryReader for dyn MemoryBackend + '_ {
+    fn read(&self, address: u64, size: usize) -> io::Result<Vec<u8>> {
+        MemoryBackend::read(self, address, size)
+    }
+}
+
+fn process_vm_read(pid: pid_t, address: u64, size: usize) -> io::Result<Vec<u8>> {
+    let mut result = vec![0; size];
+    let local_iov = iovec {
+        iov_base: result.as_mut_ptr() as *mut c_void,
+        iov_len: size,
+    };
+    let remote_iov = iovec {
+        iov_base: address as *mut c_void,
+        iov_len: size,
+    };
+
+    let bytes_read = process_vm_readev(pid, &[local_iov], &[remote_iov]);
 
-        let mut result = vec![1; size];
-        println!("Fake read: address={}, size={:?}", address, size);
+    if bytes_read == -1 {
+        let e = errno();
+        Err(io::Error::from_raw_os_error(e.0))
+    } else if bytes_read as usize != size {
+        Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Partial read occurred!"))
+    } else {
         Ok(result)
     }
+}
+
+/// Reads many ranges with as few syscalls as possible.
+fn process_vm_read_many(pid: pid_t, ranges: &[(u64, usize)]) -> io::Result<Vec<io::Result<Vec<u8>>>> {
+    let mut buffers: Vec<Vec<u8>> = ranges.iter().map(|&(_, size)| vec![0; size]).collect();
+    let sizes: Vec<usize> = ranges.iter().map(|&(_, size)| size).collect();
+
+    let errors = transfer_many(&sizes, io::ErrorKind::UnexpectedEof, |batch| {
+        let local_iov: Vec<iovec> = buffers[batch.clone()]
+            .iter_mut()
+            .map(|buffer| iovec {
+                iov_base: buffer.as_mut_ptr() as *mut c_void,
+                iov_len: buffer.len(),
+            })
+            .collect();
+        process_vm_readev(pid, &local_iov, &remote_iovecs(&ranges[batch]))
+    })?;
+
+    Ok(buffers
+        .into_iter()
+        .zip(errors)
+        .map(|(buffer, error)| match error {
+            Some(error) => Err(error),
+            None => Ok(buffer),
+        })
+        .collect())
+}
+
+fn process_vm_write(pid: pid_t, address: u64, buffer: &[u8]) -> io::Result<usize> {
+    let size = buffer.len();
+    let local_iov = iovec {
+        iov_base: buffer.as_ptr() as *mut c_void,
+        iov_len: size,
+    };
+    let remote_iov = iovec {
+        iov_base: address as *mut c_void,
+        iov_len: size,
+    };
+
+    let bytes_written = process_vm_writev(pid, &[local_iov], &[remote_iov]);
+
+    if bytes_written == -1 {
+        let e = errno();
+        Err(io::Error::from_raw_os_error(e.0))
+    } else if bytes_written as usize != size {
+        Err(io::Error::new(io::ErrorKind::WriteZero, "Partial write occurred!"))
+    } else {
+        Ok(bytes_written as usize)
+    }
+}
+
+/// Writes many entries with as few syscalls as possible.
+fn process_vm_write_many(pid: pid_t, entries: &[(u64, &[u8])]) -> io::Result<Vec<io::Result<usize>>> {
+    let sizes: Vec<usize> = entries.iter().map(|(_, bytes)| bytes.len()).collect();
+    let ranges: Vec<(u64, usize)> = entries.iter().map(|&(address, bytes)| (address, bytes.len())).collect();
+
+    let errors = transfer_many(&sizes, io::ErrorKind::WriteZero, |batch| {
+        let local_iov: Vec<iovec> = entries[batch.clone()]
+            .iter()
+            .map(|(_, bytes)| iovec {
+                iov_base: bytes.as_ptr() as *mut c_void,
+                iov_len: bytes.len(),
+            })
+            .collect();
+        process_vm_writev(pid, &local_iov, &remote_iovecs(&ranges[batch]))
+    })?;
+
+    Ok(sizes
+        .into_iter()
+        .zip(errors)
+        .map(|(size, error)| match error {
+            Some(error) => Err(error),
+            None => Ok(size),
+        })
+        .collect())
+}
+
+fn proc_mem_read_many(pid: pid_t, ranges: &[(u64, usize)]) -> io::Result<Vec<io::Result<Vec<u8>>>> {
+    let file = open_proc_mem(pid, false)?;
+    Ok(ranges.iter().map(|&(address, size)| proc_mem_read(&file, address, size)).collect())
+}
+
+fn proc_mem_write_many(pid: pid_t, entries: &[(u64, &[u8])]) -> io::Result<Vec<io::Result<usize>>> {
+    let file = open_proc_mem(pid, true)?;
+    Ok(entries.iter().map(|&(address, bytes)| proc_mem_write(&file, address, bytes)).collect())
+}
+
+/// The `process_vm_readv` and `process_vm_writev` syscalls.
+pub struct ProcessVm {
+    pid: pid_t,
+}
+
+impl ProcessVm {
+    pub const NAME: &'static str = "process_vm";
+
+    pub fn new(pid: pid_t) -> Self {
+        Self { pid }
+    }
+}
+
+impl MemoryBackend for ProcessVm {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn pid(&self) -> pid_t {
+        self.pid
+    }
+
+    fn set_pid(&mut self, pid: pid_t) {
+        self.pid = pid;
+    }
+
+    fn read(&self, address: u64, size: usize) -> io::Result<Vec<u8>> {
+        check_pid(self.pid)?;
+        process_vm_read(self.pid, address, size)
+    }
 
-    #[cfg(not(feature = "fake_read_write"))]
-    pub fn write(&self, address: u64, buffer: &[u8]) -> io::Result<usize> {
-        if self.pid == -1 {
-            return Err(io::Error::new(io::ErrorKind::Other, "PID not set!"));
+    fn write(&self, address: u64, bytes: &[u8]) -> io::Result<usize> {
+        check_pid(self.pid)?;
+        process_vm_write(self.pid, address, bytes)
+    }
+
+    fn regions(&self) -> io::Result<Vec<Region>> {
+        get_regions(self.pid)
+    }
+
+    fn processes(&self) -> io::Result<Vec<ProcessEntry>> {
+        get_running_processes()
+    }
+
+    fn read_many(&self, ranges: &[(u64, usize)]) -> io::Result<Vec<io::Result<Vec<u8>>>> {
+        check_pid(self.pid)?;
+        process_vm_read_many(self.pid, ranges)
+    }
+
+    fn write_many(&self, entries: &[(u64, &[u8])]) -> io::Result<Vec<io::Result<usize>>> {
+        check_pid(self.pid)?;
+        process_vm_write_many(self.pid, entries)
+    }
+}
+
+/// pread and pwrite on `/proc/<pid>/mem`, which unlike the `process_vm`
+/// syscalls also writes to read-only mappings.
+pub struct ProcMem {
+    pid: pid_t,
+}
+
+impl ProcMem {
+    pub const NAME: &'static str = "procmem";
+
+    pub fn new(pid: pid_t) -> Self {
+        Self { pid }
+    }
+}
+
+impl MemoryBackend for ProcMem {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn pid(&self) -> pid_t {
+        self.pid
+    }
+
+    fn set_pid(&mut self, pid: pid_t) {
+        self.pid = pid;
+    }
+
+    fn read(&self, address: u64, size: usize) -> io::Result<Vec<u8>> {
+        check_pid(self.pid)?;
+        proc_mem_read(&open_proc_mem(self.pid, false)?, address, size)
+    }
+
+    fn write(&self, address: u64, bytes: &[u8]) -> io::Result<usize> {
+        check_pid(self.pid)?;
+        proc_mem_write(&open_proc_mem(self.pid, true)?, address, bytes)
+    }
+
+    fn regions(&self) -> io::Result<Vec<Region>> {
+        get_regions(self.pid)
+    }
+
+    fn processes(&self) -> io::Result<Vec<ProcessEntry>> {
+        get_running_processes()
+    }
+
+    fn read_many(&self, ranges: &[(u64, usize)]) -> io::Result<Vec<io::Result<Vec<u8>>>> {
+        check_pid(self.pid)?;
+        proc_mem_read_many(self.pid, ranges)
+    }
+
+    fn write_many(&self, entries: &[(u64, &[u8])]) -> io::Result<Vec<io::Result<usize>>> {
+        check_pid(self.pid)?;
+        proc_mem_write_many(self.pid, entries)
+    }
+}
+
+/// `ProcessVm`, falling back to `ProcMem` where the syscalls are not
+/// available and for writes to read-only mappings.
+pub struct Auto {
+    pid: pid_t,
+}
+
+impl Auto {
+    pub const NAME: &'static str = "auto";
+
+    pub fn new(pid: pid_t) -> Self {
+        Self { pid }
+    }
+}
+
+impl MemoryBackend for Auto {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn pid(&self) -> pid_t {
+        self.pid
+    }
+
+    fn set_pid(&mut self, pid: pid_t) {
+        self.pid = pid;
+    }
+
+    fn read(&self, address: u64, size: usize) -> io::Result<Vec<u8>> {
+        check_pid(self.pid)?;
+        match process_vm_read(self.pid, address, size) {
+            Err(error) if read_falls_back(&error) => proc_mem_read(&open_proc_mem(self.pid, false)?, address, size),
+            result => result,
+        }
+    }
+
+    fn write(&self, address: u64, bytes: &[u8]) -> io::Result<usize> {
+        check_pid(self.pid)?;
+        match process_vm_write(self.pid, address, bytes) {
+            Err(error) if write_falls_back(&error) => proc_mem_write(&open_proc_mem(self.pid, true)?, address, bytes),
+            result => result,
         }
+    }
 
-        let size = buffer.len();
-        let local_iov = iovec {
-            iov_base: buffer.as_ptr() as *mut c_void,
-            iov_len: size,
+    fn regions(&self) -> io::Result<Vec<Region>> {
+        get_regions(self.pid)
+    }
+
+    fn processes(&self) -> io::Result<Vec<ProcessEntry>> {
+        get_running_processes()
+    }
+
+    fn read_many(&self, ranges: &[(u64, usize)]) -> io::Result<Vec<io::Result<Vec<u8>>>> {
+        check_pid(self.pid)?;
+        match process_vm_read_many(self.pid, ranges) {
+            Err(error) if read_falls_back(&error) => proc_mem_read_many(self.pid, ranges),
+            result => result,
+        }
+    }
+
+    fn write_many(&self, entries: &[(u64, &[u8])]) -> io::Result<Vec<io::Result<usize>>> {
+        check_pid(self.pid)?;
+        let mut results = match process_vm_write_many(self.pid, entries) {
+            Err(error) if read_falls_back(&error) => return proc_mem_write_many(self.pid, entries),
+            results => results?,
         };
-        let remote_iov = iovec {
-            iov_base: address as *mut c_void,
-            iov_len: size,
+        // Only the entries that failed are retried.
+        let mut file = None;
+        for (result, &(address, bytes)) in results.iter_mut().zip(entries) {
+            if result.as_ref().is_err_and(write_falls_back) {
+                if file.is_none() {
+                    file = Some(open_proc_mem(self.pid, true)?);
+                }
+                *result = proc_mem_write(file.as_ref().unwrap(), address, bytes);
+            }
+        }
+        Ok(results)
+    }
+}
+
+/// Whether `Auto` retries a failed read with `/proc/<pid>/mem`.
+fn read_falls_back(error: &io::Error) -> bool {
+    matches!(error.raw_os_error(), Some(libc::ENOSYS) | Some(libc::EPERM))
+}
+
+/// Whether `Auto` retries a failed write with `/proc/<pid>/mem`, which also
+/// gets through to read-only mappings.
+fn write_falls_back(error: &io::Error) -> bool {
+    read_falls_back(error) || error.raw_os_error() == Some(libc::EFAULT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_many_isolates_failing_ranges() {
+        let memory = ProcessVm::new(std::process::id() as i32);
+        let first = [1u8, 2, 3, 4];
+        let second = [5u8; 4096];
+
+        let results = memory
+            .read_many(&[
+                (first.as_ptr() as u64, first.len()),
+                (0, 8),
+                (second.as_ptr() as u64, second.len()),
+            ])
+            .unwrap();
+
+        assert_eq!(first.to_vec(), *results[0].as_ref().unwrap());
+        assert_eq!(Some(libc::EFAULT), results[1].as_ref().unwrap_err().raw_os_error());
+        assert_eq!(second.to_vec(), *results[2].as_ref().unwrap());
+
+        // Empty reads succeed even right before a failing range.
+        let results = memory
+            .read_many(&[(first.as_ptr() as u64, 0), (0x10, 8), (0, 0), (first.as_ptr() as u64, 2)])
+            .unwrap();
+        assert!(results[0].as_ref().unwrap().is_empty());
+        assert_eq!(Some(libc::EFAULT), results[1].as_ref().unwrap_err().raw_os_error());
+        assert!(results[2].as_ref().unwrap().is_empty());
+        assert_eq!(vec![1, 2], *results[3].as_ref().unwrap());
+    }
+
+    #[test]
+    fn test_read_tolerant_skips_unmapped_pages() {
+        let memory = ProcessVm::new(std::process::id() as i32);
+        let page_size = page_size() as usize;
+        let mapping = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                page_size * 3,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
         };
+        assert_ne!(libc::MAP_FAILED, mapping);
+        unsafe {
+            std::ptr::write_bytes(mapping as *mut u8, 7, page_size * 3);
+            libc::munmap((mapping as usize + page_size) as *mut c_void, page_size);
+        }
 
-        let bytes_written = process_vm_writev(self.pid, &local_iov, 1, &remote_iov, 1);
-
-        if bytes_written == -1 {
-            let e = errno();
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Error {}: {}", e.0, e),
-            ));
-        } else if bytes_written as usize != size {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Partial write occurred!",
-            ));
-        } else {
-            Ok(bytes_written as usize)
+        let (data, readable) = memory.read_tolerant(mapping as u64 + 16, page_size * 3 - 32).unwrap();
+
+        assert_eq!(vec![true, false, true], readable);
+        assert_eq!(page_size * 3 - 32, data.len());
+        assert!(data[..page_size - 16].iter().all(|&byte| byte == 7));
+        assert!(data[page_size - 16..page_size * 2 - 16].iter().all(|&byte| byte == 0));
+        assert!(data[page_size * 2 - 16..].iter().all(|&byte| byte == 7));
+
+        unsafe {
+            libc::munmap(mapping, page_size);
+            libc::munmap((mapping as usize + page_size * 2) as *mut c_void, page_size);
         }
     }
 
-    #[cfg(feature = "fake_read_write")]
-    pub fn write(&self, address: u64, buffer: &[u8]) -> io::Result<usize> {
-        if self.pid == -1 {
-            return Err(io::Error::new(io::ErrorKind::Other, "PID not set!"));
-        }
-        println!("Fake write: address={}, bytes={:?}", address, buffer);
-        Ok(buffer.len())
+    #[test]
+    fn test_write_many_isolates_failing_entries() {
+        let memory = ProcessVm::new(std::process::id() as i32);
+        let mut first = [0u8; 4];
+        let mut second = [0u8; 2];
+
+        let results = memory
+            .write_many(&[
+                (first.as_mut_ptr() as u64, &[1, 2, 3, 4]),
+                (0, &[9, 9]),
+                (second.as_mut_ptr() as u64, &[5, 6]),
+            ])
+            .unwrap();
+
+        assert_eq!(4, *results[0].as_ref().unwrap());
+        assert_eq!(Some(libc::EFAULT), results[1].as_ref().unwrap_err().raw_os_error());
+        assert_eq!(2, *results[2].as_ref().unwrap());
+        assert_eq!([1, 2, 3, 4], unsafe { std::ptr::read_volatile(&first) });
+        assert_eq!([5, 6], unsafe { std::ptr::read_volatile(&second) });
+    }
+
+    #[test]
+    fn test_proc_mem_backend() {
+        let pid = std::process::id() as i32;
+        let memory = ProcMem::new(pid);
+        let page_size = page_size() as usize;
+        let mapping = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                page_size * 2,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(libc::MAP_FAILED, mapping);
+        unsafe { libc::munmap((mapping as usize + page_size) as *mut c_void, page_size) };
+        let address = mapping as u64;
+
+        assert_eq!(vec![0; 4], memory.read(address, 4).unwrap());
+        assert_eq!(Some(libc::EFAULT), memory.read(0, 4).unwrap_err().raw_os_error());
+        let error = memory.read(address + page_size as u64 - 2, 4).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, error.kind());
+        let error = memory.write(address + page_size as u64 - 2, &[1; 4]).unwrap_err();
+        assert_eq!(io::ErrorKind::WriteZero, error.kind());
+
+        // Read-only mappings can only be written through /proc/<pid>/mem.
+        assert_eq!(4, memory.write(address, &[1, 2, 3, 4]).unwrap());
+        assert_eq!(Some(libc::EFAULT), ProcessVm::new(pid).write(address, &[5]).unwrap_err().raw_os_error());
+        let results = Auto::new(pid).write_many(&[(address + 4, &[5, 6]), (0, &[9])]).unwrap();
+        assert_eq!(2, *results[0].as_ref().unwrap());
+        assert_eq!(Some(libc::EFAULT), results[1].as_ref().unwrap_err().raw_os_error());
+        assert_eq!(vec![1, 2, 3, 4, 5, 6], memory.read(address, 6).unwrap());
+
+        assert_eq!(io::ErrorKind::NotConnected, ProcMem::new(-1).read(address, 4).unwrap_err().kind());
+        unsafe { libc::munmap(mapping, page_size) };
     }
 }
@@ -0,0 +1,383 @@
+//! Pointer scans: chains of pointers from a module's static memory to an
+//! address, which survive the target being restarted with a different layout.
+
+use crate::memory::MemoryBackend;
+use crate::scan::for_each_chunk;
+use crate::storage;
+use shared::protocol::{PointerPath, Region};
+use std::io::{self, BufWriter, Error, ErrorKind, Read, Write};
+use std::path::Path;
+
+const POINTER_SIZE: usize = 8;
+/// Most pointers a scan collects, bounding its memory to 1 GiB.
+const MAX_POINTERS: usize = 1 << 26;
+/// Most paths a scan finds.
+pub const MAX_PATHS: usize = 1 << 20;
+/// Most pointers a scan follows, as the number of paths grows exponentially
+/// with the depth.
+const MAX_VISITS: usize = 1 << 24;
+/// Starts a file of saved paths, followed by its version.
+const FILE_MAGIC: &[u8; 8] = b"MWPTRS01";
+
+fn is_file_backed(region: &Region) -> bool {
+    region.pathname.as_bytes().starts_with(b"/")
+}
+
+/// The mapped files of the target and the memory that counts as theirs.
+pub struct Modules {
+    /// Pathname and base address, the start of the file's lowest mapping.
+    modules: Vec<(Vec<u8>, u64)>,
+    /// Sorted `(start, end, module)` ranges of static memory.
+    ranges: Vec<(u64, u64, usize)>,
+}
+
+impl Modules {
+    /// The mappings of a file are its static memory, and so is the anonymous
+    /// mapping right after them, which holds the part of `.bss` that does not
+    /// fit the file's last page.
+    pub fn new(regions: &[Region]) -> Self {
+        let mut modules: Vec<(Vec<u8>, u64)> = Vec::new();
+        let mut ranges = Vec::new();
+        let mut previous: Option<(&Region, usize)> = None;
+        for region in regions {
+            let module = if is_file_backed(region) {
+                let pathname = region.pathname.as_bytes();
+                match modules.iter().position(|(module, _)| module == pathname) {
+                    Some(index) => {
+                        modules[index].1 = modules[index].1.min(region.start);
+                        Some(index)
+                    }
+                    None => {
+                        modules.push((pathname.to_vec(), region.start));
+                        Some(modules.len() - 1)
+                    }
+                }
+            } else {
+                previous
+                    .filter(|(previous, _)| previous.end == region.start && region.pathname.as_bytes() == b"[Anonymous]")
+                    .map(|(_, module)| module)
+            };
+
+            previous = module.filter(|_| is_file_backed(region)).map(|module| (region, module));
+            if let Some(module) = module {
+                ranges.push((region.start, region.end, module));
+            }
+        }
+        ranges.sort_unstable();
+        Self { modules, ranges }
+    }
+
+    /// The module `address` is static memory of, and the offset from its base.
+    fn find(&self, address: u64) -> Option<(usize, u64)> {
+        let index = self.ranges.partition_point(|&(start, _, _)| start <= address).checked_sub(1)?;
+        let (_, end, module) = self.ranges[index];
+        (address < end).then(|| (module, address - self.modules[module].1))
+    }
+
+    pub fn base(&self, pathname: &[u8]) -> Option<u64> {
+        self.modules
+            .iter()
+            .find(|(module, _)| module == pathname)
+            .map(|&(_, base)| base)
+    }
+
+    /// Like `base`, but also accepts a module's file name, as in `libc.so.6`.
+    pub fn base_by_name(&self, name: &[u8]) -> Option<u64> {
+        self.base(name).or_else(|| {
+            self.modules
+                .iter()
+                .find(|(module, _)| module.rsplit(|&byte| byte == b'/').next() == Some(name))
+                .map(|&(_, base)| base)
+        })
+    }
+}
+
+/// Paths found by a scan, `truncated` when one of its limits was hit.
+pub struct PointerScan {
+    pub paths: Vec<PointerPath>,
+    pub truncated: bool,
+}
+
+/// Every aligned pointer in writable or static memory that points into a
+/// readable region, as `(value, address)` sorted by value.
+fn collect_pointers(memory: &dyn MemoryBackend, regions: &[Region], modules: &Modules) -> (Vec<(u64, u64)>, bool) {
+    let mut readable: Vec<(u64, u64)> = regions
+        .iter()
+        .filter(|region| region.permissions & 1 != 0)
+        .map(|region| (region.start, region.end))
+        .collect();
+    readable.sort_unstable();
+    let points_into_memory = |value: u64| {
+        let index = readable.partition_point(|&(start, _)| start <= value);
+        index > 0 && value < readable[index - 1].1
+    };
+
+    let sources: Vec<Region> = regions
+        .iter()
+        .filter(|region| region.permissions & 2 != 0 || modules.find(region.start).is_some())
+        .cloned()
+        .collect();
+    let mut pointers = Vec::new();
+    let mut truncated = false;
+    for_each_chunk(memory, &sources, 0, |address, data, owned| {
+        let skip = (POINTER_SIZE - address as usize % POINTER_SIZE) % POINTER_SIZE;
+        for (index, bytes) in data[skip.min(owned)..owned].chunks_exact(POINTER_SIZE).enumerate() {
+            let value = u64::from_le_bytes(bytes.try_into().unwrap());
+            if points_into_memory(value) {
+                if pointers.len() == MAX_POINTERS {
+                    truncated = true;
+                    return false;
+                }
+                pointers.push((value, address + (skip + index * POINTER_SIZE) as u64));
+            }
+        }
+        true
+    });
+    pointers.sort_unstable();
+    (pointers, truncated)
+}
+
+/// Walks the pointers backwards from `target`, recording a path whenever
+/// one is read from static memory.
+struct Walk<'a> {
+    pointers: &'a [(u64, u64)],
+    modules: &'a Modules,
+    max_depth: usize,
+    max_offset: u64,
+    /// Offsets from the target backwards.
+    offsets: Vec<u32>,
+    visits: usize,
+    scan: PointerScan,
+}
+
+impl Walk<'_> {
+    fn walk(&mut self, target: u64) {
+        let first = self.pointers.partition_point(|&(value, _)| value < target.saturating_sub(self.max_offset));
+        for &(value, address) in self.pointers[first..].iter().take_while(|&&(value, _)| value <= target) {
+            if self.scan.paths.len() == MAX_PATHS || self.visits == MAX_VISITS {
+                self.scan.truncated = true;
+                return;
+            }
+            self.visits += 1;
+
+            self.offsets.push((target - value) as u32);
+            match self.modules.find(address) {
+                Some((module, module_offset)) => self.scan.paths.push(PointerPath::new(
+                    self.modules.modules[module].0.clone(),
+                    module_offset,
+                    self.offsets.iter().rev().copied().collect(),
+                )),
+                None if self.offsets.len() < self.max_depth => self.walk(address),
+                None => {}
+            }
+            self.offsets.pop();
+        }
+    }
+}
+
+/// Finds the paths of at most `max_depth` pointers with offsets of at most
+/// `max_offset` that lead to `target`.
+pub fn scan(memory: &dyn MemoryBackend, regions: &[Region], target: u64, max_depth: usize, max_offset: u32) -> PointerScan {
+    let modules = Modules::new(regions);
+    let (pointers, truncated) = collect_pointers(memory, regions, &modules);
+    let mut walk = Walk {
+        pointers: &pointers,
+        modules: &modules,
+        max_depth,
+        max_offset: max_offset as u64,
+        offsets: Vec::new(),
+        visits: 0,
+        scan: PointerScan {
+            paths: Vec::new(),
+            truncated,
+        },
+    };
+    walk.walk(target);
+    walk.scan
+}
+
+/// Where `path` leads to, `None` if its module is not mapped or one of its
+/// pointers cannot be read.
+pub fn resolve(memory: &dyn MemoryBackend, modules: &Modules, path: &PointerPath) -> Option<u64> {
+    let base = modules.base(path.module.as_bytes())?;
+    let offsets: Vec<i64> = path.offsets.iter().map(|&offset| offset as i64).collect();
+    let (addresses, error) = follow(memory, base + path.module_offset, &offsets);
+    error.is_none().then(|| *addresses.last().unwrap())
+}
+
+/// The addresses a chain passes through, from `start` to the one reached after
+/// the last offset. If a pointer cannot be read the walk stops there, with the
+/// error and its address last.
+pub fn follow(memory: &dyn MemoryBackend, start: u64, offsets: &[i64]) -> (Vec<u64>, Option<io::Error>) {
+    let mut addresses = vec![start];
+    for &offset in offsets {
+        let address = *addresses.last().unwrap();
+        match memory.read(address, POINTER_SIZE) {
+            Ok(pointer) => {
+                let pointer = u64::from_le_bytes(pointer.try_into().unwrap());
+                addresses.push(pointer.wrapping_add(offset as u64));
+            }
+            Err(error) => return (addresses, Some(error)),
+        }
+    }
+    (addresses, None)
+}
+
+/// Keeps the `paths` that still lead to `target`.
+pub fn rescan(memory: &dyn MemoryBackend, regions: &[Region], paths: Vec<PointerPath>, target: u64) -> Vec<PointerPath> {
+    let modules = Modules::new(regions);
+    paths
+        .into_iter()
+        .filter(|path| resolve(memory, &modules, path) == Some(target))
+        .collect()
+}
+
+pub fn save(path: &Path, scan: &PointerScan) -> io::Result<()> {
+    let mut file = BufWriter::new(storage::create(path)?);
+    file.write_all(FILE_MAGIC)?;
+    file.write_all(&[scan.truncated as u8])?;
+    file.write_all(&(scan.paths.len() as u64).to_le_bytes())?;
+    for path in &scan.paths {
+        file.write_all(&(path.module.as_bytes().len() as u32).to_le_bytes())?;
+        file.write_all(path.module.as_bytes())?;
+        file.write_all(&path.module_offset.to_le_bytes())?;
+        file.write_all(&(path.offsets.len() as u32).to_le_bytes())?;
+        for offset in &path.offsets {
+            file.write_all(&offset.to_le_bytes())?;
+        }
+    }
+    file.flush()
+}
+
+/// Splits `size` bytes off the front of `data`.
+fn take<'a>(data: &mut &'a [u8], size: usize) -> io::Result<&'a [u8]> {
+    if data.len() < size {
+        return Err(Error::new(ErrorKind::InvalidData, "Truncated pointer scan file"));
+    }
+    let (taken, rest) = data.split_at(size);
+    *data = rest;
+    Ok(taken)
+}
+
+fn take_u32(data: &mut &[u8]) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(take(data, 4)?.try_into().unwrap()))
+}
+
+fn take_u64(data: &mut &[u8]) -> io::Result<u64> {
+    Ok(u64::from_le_bytes(take(data, 8)?.try_into().unwrap()))
+}
+
+pub fn load(path: &Path) -> io::Result<PointerScan> {
+    let mut file = Vec::new();
+    storage::open(path)?.read_to_end(&mut file)?;
+    let mut data = file
+        .strip_prefix(FILE_MAGIC)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Not a pointer scan file"))?;
+    let truncated = take(&mut data, 1)?[0] != 0;
+    let count = take_u64(&mut data)?;
+
+    let mut paths = Vec::new();
+    for _ in 0..count {
+        let length = take_u32(&mut data)? as usize;
+        let module = take(&mut data, length)?.to_vec();
+        let module_offset = take_u64(&mut data)?;
+        let offset_count = take_u32(&mut data)? as usize;
+        let offsets = (0..offset_count)
+            .map(|_| take_u32(&mut data))
+            .collect::<io::Result<_>>()?;
+        paths.push(PointerPath::new(module, module_offset, offsets));
+    }
+    Ok(PointerScan { paths, truncated })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Auto;
+    use shared::process::get_regions;
+    use std::sync::atomic::{AtomicPtr, Ordering};
+
+    /// The root of the chain, in the test binary's static memory.
+    static ROOT: AtomicPtr<Node> = AtomicPtr::new(std::ptr::null_mut());
+
+    #[repr(C)]
+    struct Node {
+        padding: [u64; 3],
+        next: *const Node,
+        value: u64,
+    }
+
+    fn node(next: *const Node) -> Box<Node> {
+        Box::new(Node {
+            padding: [0; 3],
+            next,
+            value: 0x5EED,
+        })
+    }
+
+    #[test]
+    fn test_scan_and_rescan_pointer_paths() {
+        let memory = Auto::new(std::process::id() as i32);
+        let leaf = node(std::ptr::null());
+        let mut middle = node(&*leaf);
+        ROOT.store(&mut *middle, Ordering::SeqCst);
+        let target = &leaf.value as *const u64 as u64;
+
+        let regions = get_regions(std::process::id() as i32).unwrap();
+        let modules = Modules::new(&regions);
+        let executable = std::fs::read_link("/proc/self/exe").unwrap();
+        let executable = executable.as_os_str().as_encoded_bytes();
+        let root_offset = &ROOT as *const _ as u64 - modules.base(executable).unwrap();
+        // `[ROOT] + 24` is `middle.next`, `[middle.next] + 32` is `leaf.value`.
+        let expected = PointerPath::new(executable.to_vec(), root_offset, vec![24, 32]);
+
+        let scan = scan(&memory, &regions, target, 2, 64);
+        assert!(scan.paths.contains(&expected));
+        assert_eq!(Some(target), resolve(&memory, &modules, &expected));
+
+        let file = std::env::temp_dir().join(format!("memweb-pointer-scan-{}", std::process::id()));
+        save(&file, &scan).unwrap();
+        let loaded = load(&file).unwrap();
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(scan.paths, loaded.paths);
+        assert_eq!(scan.truncated, loaded.truncated);
+
+        // The value moves, as it would after a restart.
+        let moved = node(std::ptr::null());
+        middle.next = &*moved;
+        let moved_target = &moved.value as *const u64 as u64;
+        let kept = rescan(&memory, &regions, loaded.paths, moved_target);
+        assert!(kept.contains(&expected));
+        assert!(kept.iter().all(|path| resolve(&memory, &modules, path) == Some(moved_target)));
+        assert!(rescan(&memory, &regions, kept, target).is_empty());
+        ROOT.store(std::ptr::null_mut(), Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_follow_pointer_chain() {
+        let memory = Auto::new(std::process::id() as i32);
+        let leaf = node(std::ptr::null());
+        let middle = node(&*leaf);
+        let holder = Box::new(&*middle as *const Node as u64);
+        let start = &*holder as *const u64 as u64;
+
+        let (addresses, error) = follow(&memory, start, &[24, 24, 8]);
+        assert!(error.is_none());
+        let next = |node: &Node| &node.next as *const _ as u64;
+        assert_eq!(vec![start, next(&middle), next(&leaf), 8], addresses);
+
+        // The walk stops at the null pointer plus 8, which cannot be read.
+        let (addresses, error) = follow(&memory, start, &[24, 24, 8, -8]);
+        assert_eq!(4, addresses.len());
+        assert!(error.is_some());
+
+        let regions = get_regions(std::process::id() as i32).unwrap();
+        let modules = Modules::new(&regions);
+        let executable = std::fs::read_link("/proc/self/exe").unwrap();
+        let file_name = executable.file_name().unwrap().as_encoded_bytes();
+        let executable = executable.as_os_str().as_encoded_bytes();
+        assert!(modules.base(executable).is_some());
+        assert_eq!(modules.base(executable), modules.base_by_name(file_name));
+        assert_eq!(None, modules.base_by_name(b"memweb-no-such-module.so"));
+    }
+}
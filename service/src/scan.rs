@@ -0,0 +1,643 @@
+//! Value scanning ("first scan / next scan") over the target's readable regions.
+//!
+//! Scans for a known value keep a list of candidate addresses. Scans for an
+//! unknown initial value start from a compressed snapshot of the writable
+//! regions instead, and turn into a list once few enough candidates remain.
+
+use crate::memory::MemoryBackend;
+use shared::protocol::{Endianness, Region, ScanComparison, ValueType};
+use std::io::{self, Error, ErrorKind};
+
+/// Regions are read in chunks of this size.
+pub(crate) const CHUNK_SIZE: usize = 1024 * 1024;
+/// Most candidates a scan keeps, bounding its memory to a few hundred MiB.
+pub const MAX_RESULTS: usize = 1 << 24;
+/// Candidates closer than this are re-read together by a next scan.
+const MAX_GAP: u64 = 4096;
+/// Largest read a next scan issues for a group of candidates.
+const MAX_SPAN: u64 = 64 * 1024;
+/// A snapshot turns into a candidate list once it holds this few candidates.
+const MAX_SNAPSHOT_LIST: usize = 1 << 20;
+/// Most compressed bytes a snapshot keeps.
+const MAX_SNAPSHOT_SIZE: usize = 1 << 30;
+
+/// Calls `visit` with the readable chunks of `regions` as `(address, data, owned)`.
+/// `data` extends `overlap` bytes past the `owned` ones, so a value starting
+/// in the owned part can be decoded in full. Unreadable chunks are skipped.
+/// Stops early once `visit` returns `false`.
+pub fn for_each_chunk(
+    memory: &dyn MemoryBackend,
+    regions: &[Region],
+    overlap: usize,
+    mut visit: impl FnMut(u64, &[u8], usize) -> bool,
+) {
+    for region in regions.iter().filter(|region| region.permissions & 1 != 0) {
+        let mut address = region.start;
+        while address < region.end {
+            let owned = (region.end - address).min(CHUNK_SIZE as u64) as usize;
+            let size = (region.end - address).min((CHUNK_SIZE + overlap) as u64) as usize;
+            if let Ok(data) = memory.read(address, size) {
+                if !visit(address, &data, owned) {
+                    return;
+                }
+            }
+            address += owned as u64;
+        }
+    }
+}
+
+/// A scanned value, decoded so that ordering comparisons work across types.
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Number {
+    Integer(i128),
+    Float(f64),
+}
+
+macro_rules! from_bytes {
+    ($type:ty, $bytes:expr, $endianness:expr) => {{
+        let bytes = $bytes.try_into().unwrap();
+        match $endianness {
+            Endianness::Little => <$type>::from_le_bytes(bytes),
+            Endianness::Big => <$type>::from_be_bytes(bytes),
+        }
+    }};
+}
+
+/// Type, byte order and alignment of the values a scan looks at.
+#[derive(Clone, Copy)]
+pub struct ValueLayout {
+    pub value_type: ValueType,
+    pub endianness: Endianness,
+    pub alignment: usize,
+}
+
+impl ValueLayout {
+    /// An `alignment` of 0 means the size of the value.
+    pub fn new(value_type: ValueType, endianness: Endianness, alignment: u32) -> Self {
+        Self {
+            value_type,
+            endianness,
+            alignment: match alignment {
+                0 => value_type.size(),
+                alignment => alignment as usize,
+            },
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.value_type.size()
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Number {
+        let endianness = self.endianness;
+        match self.value_type {
+            ValueType::I8 => Number::Integer(from_bytes!(i8, bytes, endianness) as i128),
+            ValueType::I16 => Number::Integer(from_bytes!(i16, bytes, endianness) as i128),
+            ValueType::I32 => Number::Integer(from_bytes!(i32, bytes, endianness) as i128),
+            ValueType::I64 => Number::Integer(from_bytes!(i64, bytes, endianness) as i128),
+            ValueType::U8 => Number::Integer(from_bytes!(u8, bytes, endianness) as i128),
+            ValueType::U16 => Number::Integer(from_bytes!(u16, bytes, endianness) as i128),
+            ValueType::U32 => Number::Integer(from_bytes!(u32, bytes, endianness) as i128),
+            ValueType::U64 => Number::Integer(from_bytes!(u64, bytes, endianness) as i128),
+            ValueType::F32 => Number::Float(from_bytes!(f32, bytes, endianness) as f64),
+            ValueType::F64 => Number::Float(from_bytes!(f64, bytes, endianness)),
+        }
+    }
+
+    /// Offsets into a chunk at `address` where an aligned value starts.
+    fn offsets(&self, address: u64, owned: usize) -> impl Iterator<Item = usize> {
+        let alignment = self.alignment as u64;
+        let first = ((alignment - address % alignment) % alignment) as usize;
+        (first..owned).step_by(self.alignment)
+    }
+
+    /// The offsets of `offsets` whose value fits into the `size` bytes read.
+    fn slots(&self, address: u64, owned: usize, size: usize) -> impl Iterator<Item = usize> {
+        let value_size = self.size();
+        self.offsets(address, owned).take_while(move |offset| offset + value_size <= size)
+    }
+}
+
+fn difference(minuend: Number, subtrahend: Number) -> Option<Number> {
+    match (minuend, subtrahend) {
+        (Number::Integer(minuend), Number::Integer(subtrahend)) => Some(Number::Integer(minuend - subtrahend)),
+        (Number::Float(minuend), Number::Float(subtrahend)) => Some(Number::Float(minuend - subtrahend)),
+        _ => None,
+    }
+}
+
+/// A comparison with its operands checked and decoded.
+struct Comparison {
+    kind: ScanComparison,
+    value: Option<Number>,
+    upper: Option<Number>,
+}
+
+impl Comparison {
+    fn new(layout: &ValueLayout, kind: ScanComparison, value: &[u8], upper: &[u8]) -> io::Result<Self> {
+        let operand = |bytes: &[u8], name: &str, needed: bool| match (needed, bytes.len()) {
+            (false, _) => Ok(None),
+            (true, size) if size == layout.size() => Ok(Some(layout.decode(bytes))),
+            (true, size) => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Expected {} bytes for the {}, got {}", layout.size(), name, size),
+            )),
+        };
+        Ok(Self {
+            kind,
+            value: operand(
+                value,
+                "value",
+                matches!(
+                    kind,
+                    ScanComparison::Equal
+                        | ScanComparison::Between
+                        | ScanComparison::IncreasedBy
+                        | ScanComparison::DecreasedBy
+                ),
+            )?,
+            upper: operand(upper, "upper bound", kind == ScanComparison::Between)?,
+        })
+    }
+
+    /// Whether `current` passes, `previous` being the value seen by the last scan.
+    fn matches(&self, current: Number, previous: Option<(Number, &[u8])>, current_bytes: &[u8]) -> bool {
+        match (self.kind, previous) {
+            (ScanComparison::Equal, _) => Some(current) == self.value,
+            (ScanComparison::Between, _) => {
+                self.value.is_some_and(|value| value <= current) && self.upper.is_some_and(|upper| current <= upper)
+            }
+            // Compare bytes, so that a NaN that stays NaN counts as unchanged.
+            (ScanComparison::Changed, Some((_, bytes))) => current_bytes != bytes,
+            (ScanComparison::Unchanged, Some((_, bytes))) => current_bytes == bytes,
+            (ScanComparison::Increased, Some((previous, _))) => current > previous,
+            (ScanComparison::Decreased, Some((previous, _))) => current < previous,
+            (ScanComparison::IncreasedBy, Some((previous, _))) => difference(current, previous) == self.value,
+            (ScanComparison::DecreasedBy, Some((previous, _))) => difference(previous, current) == self.value,
+            (ScanComparison::Unknown, _) => true,
+            (_, None) => false,
+        }
+    }
+}
+
+/// One chunk of a snapshot: its bytes as seen by the last scan and which of
+/// its slots are still candidates, both compressed.
+struct SnapshotChunk {
+    address: u64,
+    owned: usize,
+    size: usize,
+    data: Vec<u8>,
+    /// One bit per slot, `None` while every slot is a candidate.
+    alive: Option<Vec<u8>>,
+    slot_count: usize,
+    count: usize,
+}
+
+impl SnapshotChunk {
+    fn data(&self) -> Vec<u8> {
+        // We compressed it ourselves, it cannot be malformed.
+        lz4_flex::decompress(&self.data, self.size).unwrap()
+    }
+
+    fn alive(&self) -> Option<Vec<u8>> {
+        let alive = self.alive.as_ref()?;
+        Some(lz4_flex::decompress(alive, self.slot_count.div_ceil(8)).unwrap())
+    }
+}
+
+fn is_alive(alive: &Option<Vec<u8>>, slot: usize) -> bool {
+    alive.as_ref().is_none_or(|alive| alive[slot / 8] & (1 << (slot % 8)) != 0)
+}
+
+enum Candidates {
+    /// Addresses with their values, `layout.size()` bytes each.
+    List { addresses: Vec<u64>, values: Vec<u8> },
+    Snapshot(Vec<SnapshotChunk>),
+}
+
+/// The candidates of a scan with their values as seen by the last pass.
+pub struct Scan {
+    layout: ValueLayout,
+    candidates: Candidates,
+    /// The first scan found more than `MAX_RESULTS` candidates, or more
+    /// than `MAX_SNAPSHOT_SIZE` bytes to snapshot.
+    pub truncated: bool,
+}
+
+impl Scan {
+    /// Scans all readable `regions` for values passing `comparison`, which
+    /// must not refer to a previous scan. `Unknown` snapshots the writable
+    /// regions instead.
+    pub fn first(
+        memory: &dyn MemoryBackend,
+        regions: &[Region],
+        layout: ValueLayout,
+        comparison: ScanComparison,
+        value: &[u8],
+        upper: &[u8],
+    ) -> io::Result<Self> {
+        match comparison {
+            ScanComparison::Unknown => Ok(Self::snapshot(memory, regions, layout)),
+            ScanComparison::Equal | ScanComparison::Between => {
+                let comparison = Comparison::new(&layout, comparison, value, upper)?;
+                Ok(Self::list(memory, regions, layout, comparison))
+            }
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{:?} needs a previous scan", comparison),
+            )),
+        }
+    }
+
+    fn list(memory: &dyn MemoryBackend, regions: &[Region], layout: ValueLayout, comparison: Comparison) -> Self {
+        let size = layout.size();
+        let mut addresses = Vec::new();
+        let mut values = Vec::new();
+        let mut truncated = false;
+        for_each_chunk(memory, regions, size - 1, |address, data, owned| {
+            for offset in layout.slots(address, owned, data.len()) {
+                let bytes = &data[offset..offset + size];
+                if comparison.matches(layout.decode(bytes), None, bytes) {
+                    if addresses.len() == MAX_RESULTS {
+                        truncated = true;
+                        return false;
+                    }
+                    addresses.push(address + offset as u64);
+                    values.extend_from_slice(bytes);
+                }
+            }
+            true
+        });
+        Self {
+            layout,
+            candidates: Candidates::List { addresses, values },
+            truncated,
+        }
+    }
+
+    fn snapshot(memory: &dyn MemoryBackend, regions: &[Region], layout: ValueLayout) -> Self {
+        let writable: Vec<Region> = regions
+            .iter()
+            .filter(|region| region.permissions & 2 != 0)
+            .cloned()
+            .collect();
+        let mut chunks = Vec::new();
+        let mut stored = 0;
+        let mut truncated = false;
+        for_each_chunk(memory, &writable, layout.size() - 1, |address, data, owned| {
+            let slot_count = layout.slots(address, owned, data.len()).count();
+            if slot_count == 0 {
+                return true;
+            }
+            let compressed = lz4_flex::compress(data);
+            stored += compressed.len();
+            if stored > MAX_SNAPSHOT_SIZE {
+                truncated = true;
+                return false;
+            }
+            chunks.push(SnapshotChunk {
+                address,
+                owned,
+                size: data.len(),
+                data: compressed,
+                alive: None,
+                slot_count,
+                count: slot_count,
+            });
+            true
+        });
+        Self {
+            layout,
+            candidates: Candidates::Snapshot(chunks),
+            truncated,
+        }
+    }
+
+    /// Re-reads every candidate and keeps those passing `comparison`.
+    /// Candidates that cannot be read anymore are dropped.
+    pub fn next(&mut self, memory: &dyn MemoryBackend, comparison: ScanComparison, value: &[u8], upper: &[u8]) -> io::Result<()> {
+        if comparison == ScanComparison::Unknown {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Unknown only applies to a first scan".to_string(),
+            ));
+        }
+        let comparison = Comparison::new(&self.layout, comparison, value, upper)?;
+
+        self.candidates = match &self.candidates {
+            Candidates::List { addresses, values } => self.next_list(memory, &comparison, addresses, values)?,
+            Candidates::Snapshot(chunks) => self.next_snapshot(memory, &comparison, chunks),
+        };
+        Ok(())
+    }
+
+    fn next_list(
+        &self,
+        memory: &dyn MemoryBackend,
+        comparison: &Comparison,
+        addresses: &[u64],
+        values: &[u8],
+    ) -> io::Result<Candidates> {
+        let size = self.layout.size();
+        let current = read_current(memory, addresses, size)?;
+
+        let mut kept_addresses = Vec::new();
+        let mut kept_values = Vec::new();
+        for (index, current) in current.iter().enumerate() {
+            let Some(bytes) = current else {
+                continue;
+            };
+            let previous = &values[index * size..(index + 1) * size];
+            if comparison.matches(
+                self.layout.decode(bytes),
+                Some((self.layout.decode(previous), previous)),
+                bytes,
+            ) {
+                kept_addresses.push(addresses[index]);
+                kept_values.extend_from_slice(bytes);
+            }
+        }
+        Ok(Candidates::List {
+            addresses: kept_addresses,
+            values: kept_values,
+        })
+    }
+
+    /// Compares every chunk of the snapshot with the target's memory. Chunks
+    /// that cannot be read anymore or have no candidates left are dropped.
+    fn next_snapshot(&self, memory: &dyn MemoryBackend, comparison: &Comparison, chunks: &[SnapshotChunk]) -> Candidates {
+        let size = self.layout.size();
+        let mut kept = Vec::new();
+        let mut total = 0;
+        for chunk in chunks {
+            let Ok(current) = memory.read(chunk.address, chunk.size) else {
+                continue;
+            };
+            let previous = chunk.data();
+            let alive = chunk.alive();
+
+            let mut kept_alive = vec![0u8; chunk.slot_count.div_ceil(8)];
+            let mut count = 0;
+            for (slot, offset) in self.layout.slots(chunk.address, chunk.owned, chunk.size).enumerate() {
+                if !is_alive(&alive, slot) {
+                    continue;
+                }
+                let (previous, bytes) = (&previous[offset..offset + size], &current[offset..offset + size]);
+                if comparison.matches(
+                    self.layout.decode(bytes),
+                    Some((self.layout.decode(previous), previous)),
+                    bytes,
+                ) {
+                    kept_alive[slot / 8] |= 1 << (slot % 8);
+                    count += 1;
+                }
+            }
+
+            if count > 0 {
+                total += count;
+                kept.push(SnapshotChunk {
+                    data: lz4_flex::compress(&current),
+                    alive: Some(lz4_flex::compress(&kept_alive)),
+                    count,
+                    ..*chunk
+                });
+            }
+        }
+
+        if total > MAX_SNAPSHOT_LIST {
+            return Candidates::Snapshot(kept);
+        }
+        let (addresses, values) = self.snapshot_results(&kept, 0, total).into_iter().unzip::<_, _, Vec<_>, Vec<_>>();
+        Candidates::List {
+            addresses,
+            values: values.concat(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.candidates {
+            Candidates::List { addresses, .. } => addresses.len(),
+            Candidates::Snapshot(chunks) => chunks.iter().map(|chunk| chunk.count).sum(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Up to `limit` candidates starting at `offset`, with their last seen values.
+    pub fn results(&self, offset: usize, limit: usize) -> Vec<(u64, Vec<u8>)> {
+        match &self.candidates {
+            Candidates::List { addresses, values } => addresses
+                .iter()
+                .zip(values.chunks_exact(self.layout.size()))
+                .skip(offset)
+                .take(limit)
+                .map(|(&address, value)| (address, value.to_vec()))
+                .collect(),
+            Candidates::Snapshot(chunks) => self.snapshot_results(chunks, offset, limit),
+        }
+    }
+
+    /// Like `results`, only decompressing the chunks the page touches.
+    fn snapshot_results(&self, chunks: &[SnapshotChunk], mut offset: usize, limit: usize) -> Vec<(u64, Vec<u8>)> {
+        let size = self.layout.size();
+        let mut results = Vec::new();
+        for chunk in chunks {
+            if results.len() == limit {
+                break;
+            }
+            if offset >= chunk.count {
+                offset -= chunk.count;
+                continue;
+            }
+
+            let data = chunk.data();
+            let alive = chunk.alive();
+            let slots = self
+                .layout
+                .slots(chunk.address, chunk.owned, chunk.size)
+                .enumerate()
+                .filter(|&(slot, _)| is_alive(&alive, slot))
+                .skip(offset)
+                .take(limit - results.len());
+            for (_, slot_offset) in slots {
+                results.push((chunk.address + slot_offset as u64, data[slot_offset..slot_offset + size].to_vec()));
+            }
+            offset = 0;
+        }
+        results
+    }
+}
+
+/// Reads the current value of every candidate, grouping close ones into
+/// one read. Groups that fail are retried candidate by candidate.
+fn read_current(memory: &dyn MemoryBackend, addresses: &[u64], size: usize) -> io::Result<Vec<Option<Vec<u8>>>> {
+    let size = size as u64;
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for (index, &address) in addresses.iter().enumerate() {
+        match groups.last_mut() {
+            Some((start, end))
+                if address - addresses[*end - 1] <= MAX_GAP && address + size - addresses[*start] <= MAX_SPAN =>
+            {
+                *end = index + 1
+            }
+            _ => groups.push((index, index + 1)),
+        }
+    }
+
+    let ranges: Vec<(u64, usize)> = groups
+        .iter()
+        .map(|&(start, end)| {
+            let address = addresses[start];
+            (address, (addresses[end - 1] + size - address) as usize)
+        })
+        .collect();
+    let mut current = vec![None; addresses.len()];
+    for ((start, end), result) in groups.into_iter().zip(memory.read_many(&ranges)?) {
+        let candidates = start..end;
+        match result {
+            Ok(data) => {
+                let base = addresses[start];
+                for index in candidates {
+                    let offset = (addresses[index] - base) as usize;
+                    current[index] = Some(data[offset..offset + size as usize].to_vec());
+                }
+            }
+            Err(_) => {
+                let ranges: Vec<(u64, usize)> = candidates
+                    .clone()
+                    .map(|index| (addresses[index], size as usize))
+                    .collect();
+                for (index, result) in candidates.zip(memory.read_many(&ranges)?) {
+                    current[index] = result.ok();
+                }
+            }
+        }
+    }
+    Ok(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Auto;
+    use shared::protocol::EncodedString;
+
+    /// A region covering `values`, so tests do not scan the whole test binary.
+    fn region_of<T>(values: &[T]) -> Region {
+        let start = values.as_ptr() as u64;
+        let end = start + std::mem::size_of_val(values) as u64;
+        Region {
+            start,
+            end,
+            size: end - start,
+            permissions: 1 | 2,
+            offset: 0,
+            device: EncodedString::new("0:0".to_string()),
+            inode: 0,
+            pathname: EncodedString::new("[Heap]".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_first_and_next_scans() {
+        let memory = Auto::new(std::process::id() as i32);
+        let mut values = vec![0i32; 4096];
+        values[10] = 1234;
+        values[2000] = 1234;
+        values[3000] = 1234;
+        let regions = [region_of(&values)];
+        let layout = ValueLayout::new(ValueType::I32, Endianness::Little, 0);
+
+        let mut scan = Scan::first(
+            &memory,
+            &regions,
+            layout,
+            ScanComparison::Equal,
+            &1234i32.to_le_bytes(),
+            &[],
+        )
+        .unwrap();
+        assert_eq!(3, scan.len());
+        assert!(!scan.truncated);
+
+        values[10] = 1300;
+        values[3000] = -5;
+        std::hint::black_box(&values);
+        scan.next(&memory, ScanComparison::Increased, &[], &[]).unwrap();
+        assert_eq!(
+            vec![(&values[10] as *const i32 as u64, 1300i32.to_le_bytes().to_vec())],
+            scan.results(0, 10)
+        );
+
+        values[10] = 1250;
+        std::hint::black_box(&values);
+        scan.next(&memory, ScanComparison::Between, &1200i32.to_le_bytes(), &1260i32.to_le_bytes())
+            .unwrap();
+        assert_eq!(1, scan.len());
+        scan.next(&memory, ScanComparison::Changed, &[], &[]).unwrap();
+        assert!(scan.is_empty());
+    }
+
+    #[test]
+    fn test_scan_honours_endianness_and_alignment() {
+        let memory = Auto::new(std::process::id() as i32);
+        #[repr(align(8))]
+        struct Aligned([u8; 64]);
+        let mut bytes = Aligned([0; 64]);
+        bytes.0[8..12].copy_from_slice(&2.5f32.to_be_bytes());
+        bytes.0[21..25].copy_from_slice(&2.5f32.to_be_bytes());
+        let base = bytes.0.as_ptr() as u64;
+        let regions = [region_of(&bytes.0)];
+        let addresses = |scan: &Scan| scan.results(0, 10).into_iter().map(|(address, _)| address).collect::<Vec<_>>();
+
+        let aligned = ValueLayout::new(ValueType::F32, Endianness::Big, 0);
+        let scan = Scan::first(&memory, &regions, aligned, ScanComparison::Equal, &2.5f32.to_be_bytes(), &[]).unwrap();
+        assert_eq!(vec![base + 8], addresses(&scan));
+
+        let unaligned = ValueLayout::new(ValueType::F32, Endianness::Big, 1);
+        let scan = Scan::first(&memory, &regions, unaligned, ScanComparison::Equal, &2.5f32.to_be_bytes(), &[]).unwrap();
+        assert_eq!(vec![base + 8, base + 21], addresses(&scan));
+
+        let error = Scan::first(&memory, &regions, unaligned, ScanComparison::Equal, &[0; 3], &[]).err().unwrap();
+        assert_eq!(ErrorKind::InvalidInput, error.kind());
+    }
+
+    #[test]
+    fn test_unknown_value_scans() {
+        let memory = Auto::new(std::process::id() as i32);
+        // More slots than a snapshot turns into a list at.
+        let mut values = vec![0i32; MAX_SNAPSHOT_LIST + 4096];
+        let regions = [region_of(&values)];
+        let layout = ValueLayout::new(ValueType::I32, Endianness::Little, 0);
+        let base = values.as_ptr() as u64;
+        let address = |index: usize| base + 4 * index as u64;
+
+        let mut scan = Scan::first(&memory, &regions, layout, ScanComparison::Unknown, &[], &[]).unwrap();
+        assert_eq!(values.len(), scan.len());
+
+        values[5] += 7;
+        values[300_000] -= 3;
+        std::hint::black_box(&values);
+        scan.next(&memory, ScanComparison::Unchanged, &[], &[]).unwrap();
+        assert_eq!(values.len() - 2, scan.len());
+        assert!(matches!(scan.candidates, Candidates::Snapshot(_)));
+        let page = scan.results(4, 2);
+        assert_eq!(vec![(address(4), vec![0; 4]), (address(6), vec![0; 4])], page);
+        let page = scan.results(300_000, 1);
+        assert_eq!(vec![(address(300_002), vec![0; 4])], page);
+
+        values[700_000] += 7;
+        values[800_000] += 8;
+        std::hint::black_box(&values);
+        scan.next(&memory, ScanComparison::IncreasedBy, &7i32.to_le_bytes(), &[]).unwrap();
+        assert!(matches!(scan.candidates, Candidates::List { .. }));
+        assert_eq!(vec![(address(700_000), 7i32.to_le_bytes().to_vec())], scan.results(0, 10));
+
+        values[700_000] -= 2;
+        std::hint::black_box(&values);
+        scan.next(&memory, ScanComparison::DecreasedBy, &2i32.to_le_bytes(), &[]).unwrap();
+        assert_eq!(1, scan.len());
+    }
+}
@@ -0,0 +1,500 @@
+//! Searches of the target's memory for byte patterns, strings and regexes.
+
+use crate::memory::MemoryBackend;
+use crate::scan::for_each_chunk;
+use regex::bytes::{Regex, RegexBuilder};
+use shared::protocol::{PatternMatch, Region, RegexMatch, StringEncoding, StringHit};
+use std::io::{self, Error, ErrorKind};
+
+/// Bounds the memory a compiled regex may take.
+const REGEX_SIZE_LIMIT: usize = 16 * 1024 * 1024;
+
+/// An IDA-style byte pattern, see `C2SPatternScanPacket`.
+pub struct Pattern {
+    /// The pattern's bytes with the wildcard bits cleared.
+    bytes: Vec<u8>,
+    /// Bits of each byte that have to match.
+    masks: Vec<u8>,
+    /// A byte without wildcards to look for before comparing the rest.
+    anchor: Option<(usize, u8)>,
+}
+
+impl Pattern {
+    /// Parses space separated bytes like `48 8B ?? 4?`. `?` and `??` match
+    /// any byte, a `?` in place of a hex digit matches any nibble.
+    pub fn parse(pattern: &str) -> io::Result<Self> {
+        let invalid = |message: String| Error::new(ErrorKind::InvalidInput, message);
+        let nibble = |digit: char| match digit {
+            '?' => Ok((0, 0)),
+            digit => digit
+                .to_digit(16)
+                .map(|value| (value as u8, 0xF))
+                .ok_or_else(|| invalid(format!("Invalid pattern digit '{}'", digit))),
+        };
+
+        let mut bytes = Vec::new();
+        let mut masks = Vec::new();
+        for token in pattern.split_whitespace() {
+            let digits: Vec<char> = token.chars().collect();
+            let (high, low) = match digits[..] {
+                ['?'] => ((0, 0), (0, 0)),
+                [high, low] => (nibble(high)?, nibble(low)?),
+                _ => return Err(invalid(format!("Invalid pattern byte '{}'", token))),
+            };
+            bytes.push(high.0 << 4 | low.0);
+            masks.push(high.1 << 4 | low.1);
+        }
+        if masks.iter().all(|&mask| mask == 0) {
+            return Err(invalid(format!("Pattern '{}' matches anything", pattern)));
+        }
+
+        let anchor = masks.iter().position(|&mask| mask == 0xFF).map(|index| (index, bytes[index]));
+        Ok(Self { bytes, masks, anchor })
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn matches_at(&self, data: &[u8]) -> bool {
+        self.bytes
+            .iter()
+            .zip(&self.masks)
+            .zip(data)
+            .all(|((&byte, &mask), &value)| value & mask == byte)
+    }
+
+    /// Offsets below `owned` where the pattern matches `data` in full.
+    pub fn find<'a>(&'a self, data: &'a [u8], owned: usize) -> impl Iterator<Item = usize> + 'a {
+        let end = owned.min((data.len() + 1).saturating_sub(self.len()));
+        let mut start = 0;
+        std::iter::from_fn(move || {
+            while start < end {
+                let candidate = match self.anchor {
+                    Some((index, byte)) => {
+                        start + data[start + index..end + index].iter().position(|&value| value == byte)?
+                    }
+                    None => start,
+                };
+                start = candidate + 1;
+                if self.matches_at(&data[candidate..]) {
+                    return Some(candidate);
+                }
+            }
+            None
+        })
+    }
+}
+
+/// Where a RIP-relative displacement sits in a match, see `C2SPatternScanPacket`.
+#[derive(Clone, Copy)]
+pub struct RipRelative {
+    pub displacement_offset: usize,
+    pub instruction_length: usize,
+}
+
+impl RipRelative {
+    /// An `instruction_length` of 0 means the displacement ends the instruction.
+    pub fn new(displacement_offset: u32, instruction_length: u32) -> Self {
+        Self {
+            displacement_offset: displacement_offset as usize,
+            instruction_length: match instruction_length {
+                0 => displacement_offset as usize + 4,
+                length => length as usize,
+            },
+        }
+    }
+
+    /// The target for a match at `address`, `None` if `data`, starting at the
+    /// match, ends before the displacement does.
+    fn resolve(&self, address: u64, data: &[u8]) -> Option<u64> {
+        let displacement = data.get(self.displacement_offset..self.displacement_offset + 4)?;
+        let displacement = i32::from_le_bytes(displacement.try_into().unwrap());
+        Some((address + self.instruction_length as u64).wrapping_add_signed(displacement as i64))
+    }
+}
+
+/// Up to `max_results` matches of `pattern` in `regions`, and whether more
+/// were left.
+pub fn pattern_scan(
+    memory: &dyn MemoryBackend,
+    regions: &[Region],
+    pattern: &Pattern,
+    resolve: Option<RipRelative>,
+    max_results: usize,
+) -> (Vec<PatternMatch>, bool) {
+    let overlap = match resolve {
+        Some(resolve) => pattern.len().max(resolve.displacement_offset + 4),
+        None => pattern.len(),
+    } - 1;
+
+    let mut matches = Vec::new();
+    let mut truncated = false;
+    for_each_chunk(memory, regions, overlap, |address, data, owned| {
+        for offset in pattern.find(data, owned) {
+            if matches.len() == max_results {
+                truncated = true;
+                return false;
+            }
+            let address = address + offset as u64;
+            matches.push(PatternMatch {
+                address,
+                resolved: resolve
+                    .and_then(|resolve| resolve.resolve(address, &data[offset..]))
+                    .unwrap_or(0),
+            });
+        }
+        true
+    });
+    (matches, truncated)
+}
+
+/// A string to search for in one encoding. Each character is a unit of
+/// alternative encodings, more than one when case is ignored.
+pub struct Needle {
+    encoding: StringEncoding,
+    units: Vec<Vec<Vec<u8>>>,
+    max_len: usize,
+}
+
+impl Needle {
+    /// `text` must not be empty.
+    pub fn new(text: &str, encoding: StringEncoding, ignore_case: bool) -> Self {
+        let encode = |character: char| match encoding {
+            StringEncoding::Utf8 => character.to_string().into_bytes(),
+            StringEncoding::Utf16Le => character
+                .encode_utf16(&mut [0; 2])
+                .iter()
+                .flat_map(|unit| unit.to_le_bytes())
+                .collect(),
+        };
+        fn single(mut characters: impl ExactSizeIterator<Item = char>) -> Option<char> {
+            if characters.len() == 1 {
+                characters.next()
+            } else {
+                None
+            }
+        }
+
+        let units: Vec<Vec<Vec<u8>>> = text
+            .chars()
+            .map(|character| {
+                let mut alternatives = vec![character];
+                if ignore_case {
+                    // Only simple case mappings, "ß" does not match "SS".
+                    let cases = [
+                        single(character.to_lowercase()),
+                        single(character.to_uppercase()),
+                    ];
+                    for case in cases.into_iter().flatten() {
+                        if !alternatives.contains(&case) {
+                            alternatives.push(case);
+                        }
+                    }
+                }
+                alternatives.into_iter().map(encode).collect()
+            })
+            .collect();
+        let max_len = units
+            .iter()
+            .map(|unit| unit.iter().map(Vec::len).max().unwrap())
+            .sum();
+        Self { encoding, units, max_len }
+    }
+
+    /// The length of the match at the start of `data`, if any. Encodings are
+    /// prefix-free, so at most one alternative of each unit can match.
+    fn match_len(&self, data: &[u8]) -> Option<usize> {
+        self.units.iter().try_fold(0, |length, unit| {
+            let rest = &data[length..];
+            let alternative = unit.iter().find(|alternative| rest.starts_with(alternative))?;
+            Some(length + alternative.len())
+        })
+    }
+
+    /// Offsets below `owned` where the needle matches `data`, with the length
+    /// of each match.
+    fn find<'a>(&'a self, data: &'a [u8], owned: usize) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let first: Vec<u8> = self.units[0].iter().map(|alternative| alternative[0]).collect();
+        (0..owned.min(data.len()))
+            .filter(move |&offset| first.contains(&data[offset]))
+            .filter_map(|offset| Some((offset, self.match_len(&data[offset..])?)))
+    }
+}
+
+/// The needles for `text` in each requested encoding.
+pub fn string_needles(text: &[u8], utf8: bool, utf16: bool, ignore_case: bool) -> io::Result<Vec<Needle>> {
+    let text = std::str::from_utf8(text).map_err(|error| Error::new(ErrorKind::InvalidInput, error))?;
+    if text.is_empty() || !(utf8 || utf16) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Expected a search text and at least one encoding",
+        ));
+    }
+    Ok([(utf8, StringEncoding::Utf8), (utf16, StringEncoding::Utf16Le)]
+        .into_iter()
+        .filter(|&(enabled, _)| enabled)
+        .map(|(_, encoding)| Needle::new(text, encoding, ignore_case))
+        .collect())
+}
+
+/// Up to `max_results` hits of any of `needles` in `regions` with up to
+/// `context` bytes around each, and whether more were left.
+pub fn string_search(
+    memory: &dyn MemoryBackend,
+    regions: &[Region],
+    needles: &[Needle],
+    context: usize,
+    max_results: usize,
+) -> io::Result<(Vec<StringHit>, bool)> {
+    let overlap = needles.iter().map(|needle| needle.max_len).max().unwrap_or(1) - 1;
+
+    // Hits with the bounds of their region, which the context may not cross.
+    let mut hits: Vec<(u64, usize, StringEncoding, &Region)> = Vec::new();
+    let mut truncated = false;
+    for region in regions {
+        for_each_chunk(memory, std::slice::from_ref(region), overlap, |address, data, owned| {
+            let mut found: Vec<(usize, usize, StringEncoding)> = needles
+                .iter()
+                .flat_map(|needle| {
+                    needle
+                        .find(data, owned)
+                        .map(|(offset, length)| (offset, length, needle.encoding))
+                })
+                .collect();
+            found.sort_by_key(|&(offset, ..)| offset);
+
+            for (offset, length, encoding) in found {
+                if hits.len() == max_results {
+                    truncated = true;
+                    return false;
+                }
+                hits.push((address + offset as u64, length, encoding, region));
+            }
+            true
+        });
+        if truncated {
+            break;
+        }
+    }
+
+    let ranges: Vec<(u64, usize)> = hits
+        .iter()
+        .map(|&(address, length, _, region)| {
+            let start = address.saturating_sub(context as u64).max(region.start);
+            let end = (address + (length + context) as u64).min(region.end);
+            (start, (end - start) as usize)
+        })
+        .collect();
+    let contexts = memory.read_many(&ranges)?;
+    let hits = hits
+        .into_iter()
+        .zip(ranges)
+        .zip(contexts)
+        .map(|(((address, length, encoding, _), (context_address, _)), context)| {
+            StringHit::new(address, length as u32, encoding, context_address, context.unwrap_or_default())
+        })
+        .collect();
+    Ok((hits, truncated))
+}
+
+/// Compiles a `C2SRegexSearchPacket` pattern. Unicode is off so that `.`,
+/// classes and `\xNN` escapes match arbitrary bytes.
+pub fn compile_regex(pattern: &[u8]) -> io::Result<Regex> {
+    let pattern = std::str::from_utf8(pattern).map_err(|error| Error::new(ErrorKind::InvalidInput, error))?;
+    RegexBuilder::new(pattern)
+        .unicode(false)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|error| Error::new(ErrorKind::InvalidInput, error))
+}
+
+/// Up to `max_results` non-empty matches of `regex` in `regions`, each cut
+/// short after `max_length` bytes, and whether more were left. Chunks overlap
+/// by `max_length` bytes, so matches up to that long are found wherever they
+/// lie and see the byte after them; longer ones may be missed at chunk
+/// boundaries.
+pub fn regex_search(
+    memory: &dyn MemoryBackend,
+    regions: &[Region],
+    regex: &Regex,
+    max_length: usize,
+    max_results: usize,
+) -> (Vec<RegexMatch>, bool) {
+    let mut matches = Vec::new();
+    let mut truncated = false;
+    // Where the last match ended, so the next chunk does not report its tail.
+    let mut matched_until = 0;
+    for_each_chunk(memory, regions, max_length, |address, data, owned| {
+        for found in regex.find_iter(data) {
+            if found.start() >= owned {
+                break;
+            }
+            let start = address + found.start() as u64;
+            if found.is_empty() || start < matched_until {
+                continue;
+            }
+            if matches.len() == max_results {
+                truncated = true;
+                return false;
+            }
+            let bytes = &found.as_bytes()[..found.len().min(max_length)];
+            matched_until = address + found.end() as u64;
+            matches.push(RegexMatch::new(start, bytes.to_vec()));
+        }
+        true
+    });
+    (matches, truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Auto;
+    use crate::scan::CHUNK_SIZE;
+    use shared::protocol::EncodedString;
+
+    #[test]
+    fn test_parse_and_find_patterns() {
+        let pattern = Pattern::parse("48 8B ?? 4? ?0").unwrap();
+        let data = [0x48, 0x8B, 0x05, 0x41, 0x10, 0x48, 0x8B, 0x00, 0x4F, 0xF0, 0x48, 0x8B];
+        assert_eq!(vec![0, 5], pattern.find(&data, data.len()).collect::<Vec<_>>());
+        // A match starting past `owned` belongs to the next chunk.
+        assert_eq!(vec![0], pattern.find(&data, 5).collect::<Vec<_>>());
+
+        let pattern = Pattern::parse("?0 ? 8?").unwrap();
+        assert_eq!(vec![2], pattern.find(&[0x01, 0x02, 0x30, 0xFF, 0x81], 5).collect::<Vec<_>>());
+
+        for invalid in ["", "?? ?", "48 8G", "488B", "4"] {
+            let error = Pattern::parse(invalid).err().unwrap();
+            assert_eq!(ErrorKind::InvalidInput, error.kind(), "{}", invalid);
+        }
+    }
+
+    /// A region covering `data`, so tests do not search the whole test binary.
+    fn region_of(data: &[u8]) -> Region {
+        let start = data.as_ptr() as u64;
+        Region {
+            start,
+            end: start + data.len() as u64,
+            size: data.len() as u64,
+            permissions: 1 | 2,
+            offset: 0,
+            device: EncodedString::new("0:0".to_string()),
+            inode: 0,
+            pathname: EncodedString::new("[Heap]".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_pattern_scan_resolves_rip_relative_targets() {
+        let memory = Auto::new(std::process::id() as i32);
+        let mut code = vec![0xCCu8; 4096];
+        // mov rax, [rip + 0x100]; test rax, rax
+        code[100..110].copy_from_slice(&[0x48, 0x8B, 0x05, 0x00, 0x01, 0x00, 0x00, 0x48, 0x85, 0xC0]);
+        // The same instruction with a negative displacement, cut off by the region's end.
+        code[4090..4096].copy_from_slice(&[0x48, 0x8B, 0x05, 0xF0, 0xFF, 0xFF]);
+        let start = code.as_ptr() as u64;
+        let regions = [region_of(&code)];
+
+        let pattern = Pattern::parse("48 8B 05").unwrap();
+        let (matches, truncated) = pattern_scan(&memory, &regions, &pattern, Some(RipRelative::new(3, 0)), 10);
+        assert!(!truncated);
+        assert_eq!(
+            vec![
+                PatternMatch { address: start + 100, resolved: start + 107 + 0x100 },
+                PatternMatch { address: start + 4090, resolved: 0 },
+            ],
+            matches
+        );
+
+        let (matches, truncated) = pattern_scan(&memory, &regions, &pattern, None, 1);
+        assert!(truncated);
+        assert_eq!(vec![PatternMatch { address: start + 100, resolved: 0 }], matches);
+    }
+
+    #[test]
+    fn test_string_search_finds_all_encodings_and_cases() {
+        let memory = Auto::new(std::process::id() as i32);
+        let mut data = vec![b'.'; 256];
+        data[10..21].copy_from_slice("name=Ärger".as_bytes());
+        let utf16: Vec<u8> = "ÄRGER".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        data[100..110].copy_from_slice(&utf16);
+        data[250..255].copy_from_slice("ärge".as_bytes());
+        let start = data.as_ptr() as u64;
+        let regions = [region_of(&data)];
+
+        let needles = string_needles("ärger".as_bytes(), true, true, true).unwrap();
+        let (hits, truncated) = string_search(&memory, &regions, &needles, 4, 10).unwrap();
+        assert!(!truncated);
+        assert_eq!(
+            vec![
+                StringHit::new(start + 15, 6, StringEncoding::Utf8, start + 11, data[11..25].to_vec()),
+                StringHit::new(start + 100, 10, StringEncoding::Utf16Le, start + 96, data[96..114].to_vec()),
+            ],
+            hits
+        );
+
+        let needles = string_needles("ärger".as_bytes(), true, false, false).unwrap();
+        assert!(string_search(&memory, &regions, &needles, 0, 10).unwrap().0.is_empty());
+
+        // The context is cut short at the region's start.
+        let needles = string_needles(b"NAME", true, false, true).unwrap();
+        assert!(string_needles(b"NAME", false, false, true).is_err());
+        let (hits, _) = string_search(&memory, &regions, &needles, 100, 10).unwrap();
+        assert_eq!((start, 114), (hits[0].context_address, hits[0].context.len()));
+    }
+
+    #[test]
+    fn test_regex_search_caps_matches() {
+        let memory = Auto::new(std::process::id() as i32);
+        let mut data = vec![0u8; 256];
+        data[10..30].copy_from_slice(b"see http://a.example");
+        data[100..136].copy_from_slice(b"12345678-9ABC-DEF0-1234-56789abcdef0");
+        let start = data.as_ptr() as u64;
+        let regions = [region_of(&data)];
+
+        let regex = compile_regex(br"https?://[a-z.]+|(?i)[0-9a-f]{8}-([0-9a-f]{4}-){3}[0-9a-f]{12}").unwrap();
+        let (matches, truncated) = regex_search(&memory, &regions, &regex, 12, 10);
+        assert!(!truncated);
+        assert_eq!(
+            vec![
+                RegexMatch::new(start + 14, b"http://a.exa".to_vec()),
+                RegexMatch::new(start + 100, b"12345678-9AB".to_vec()),
+            ],
+            matches
+        );
+
+        let (matches, truncated) = regex_search(&memory, &regions, &regex, 64, 1);
+        assert!(truncated);
+        assert_eq!(vec![RegexMatch::new(start + 14, b"http://a.example".to_vec())], matches);
+
+        // Empty matches are skipped rather than reported at every offset.
+        let regex = compile_regex(b"q*").unwrap();
+        assert!(regex_search(&memory, &regions, &regex, 64, 10).0.is_empty());
+
+        assert_eq!(ErrorKind::InvalidInput, compile_regex(b"(unclosed").err().unwrap().kind());
+    }
+
+    #[test]
+    fn test_regex_search_across_chunks_and_raw_bytes() {
+        let memory = Auto::new(std::process::id() as i32);
+        let mut data = vec![0u8; CHUNK_SIZE + 64];
+        // `foo` ends where the first chunk's overlap would end without the
+        // byte after it, making `\b` match at the end of the chunk.
+        data[CHUNK_SIZE - 1..CHUNK_SIZE + 3].copy_from_slice(b"foox");
+        data[20..22].copy_from_slice(&[0xFF, 0x80]);
+        let start = data.as_ptr() as u64;
+        let regions = [region_of(&data)];
+
+        assert!(regex_search(&memory, &regions, &compile_regex(br"foo\b").unwrap(), 3, 10).0.is_empty());
+        let regex = compile_regex(b"foox").unwrap();
+        assert_eq!(vec![RegexMatch::new(start + CHUNK_SIZE as u64 - 1, b"foox".to_vec())], regex_search(&memory, &regions, &regex, 4, 10).0);
+        let regex = compile_regex(br"\xFF.").unwrap();
+        assert_eq!(vec![RegexMatch::new(start + 20, vec![0xFF, 0x80])], regex_search(&memory, &regions, &regex, 2, 10).0);
+    }
+}
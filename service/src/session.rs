@@ -1,12 +1,76 @@
 use crate::memory;
-use shared::{process::{*}, protocol::*};
-use std::{io::Error, net::TcpStream};
+use crate::pointer::{self, PointerScan};
+use crate::scan::{Scan, ValueLayout};
+use crate::search::{compile_regex, pattern_scan, regex_search, string_needles, string_search, Pattern, RipRelative};
+use crate::snapshot::{self, Snapshot};
+use crate::storage;
+use shared::{expression::{Expression, ExpressionError}, protocol::*};
+use log::{info, warn};
+use std::{collections::{HashMap, VecDeque}, io::{Error, ErrorKind}, net::TcpStream, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
 use tungstenite::{
     Message, WebSocket,
 };
 
-use memory::Memory;
+use crate::simulated::SimulatedMemory;
+use memory::MemoryBackend;
 
+/// Largest single read the service performs, announced in `S2CServerInfoPacket`.
+const MAX_READ_SIZE: u32 = 64 * 1024 * 1024;
+const CAPABILITIES: u64 = capabilities::READ
+    | capabilities::WRITE
+    | capabilities::PROCESS_LIST
+    | capabilities::REGIONS
+    | capabilities::READ_MANY
+    | capabilities::WRITE_MANY
+    | capabilities::READ_TOLERANT
+    | capabilities::COMPRESSION
+    | capabilities::READ_STREAM
+    | capabilities::SCAN
+    | capabilities::PATTERN_SCAN
+    | capabilities::STRING_SEARCH
+    | capabilities::REGEX_SEARCH
+    | capabilities::POINTER_SCAN
+    | capabilities::RESOLVE_POINTER_CHAIN
+    | capabilities::RESOLVE_EXPRESSION
+    | capabilities::FREEZE
+    | capabilities::WATCH
+    | capabilities::SNAPSHOTS
+    | capabilities::SELECT_BACKEND;
+/// Read payloads below this size are sent uncompressed.
+const COMPRESSION_THRESHOLD: u32 = 64 * 1024;
+/// Chunk size used when a stream request asks for 0.
+const DEFAULT_CHUNK_SIZE: u32 = 64 * 1024;
+/// Largest chunk of a read stream, bounds what a stream holds in memory.
+const MAX_CHUNK_SIZE: u32 = 1024 * 1024;
+const MAX_STREAMS: usize = 16;
+/// How long a read waits for client messages while streams are pending.
+const STREAM_POLL_INTERVAL: Duration = Duration::from_millis(1);
+/// Most results a single `C2SScanResultsPacket` is answered with.
+const MAX_SCAN_RESULTS_PAGE: u32 = 64 * 1024;
+/// Most matches a search answers with.
+const MAX_SEARCH_RESULTS: u32 = 64 * 1024;
+/// Most context bytes a string search returns on either side of a hit.
+const MAX_SEARCH_CONTEXT: u32 = 4096;
+/// Longest match a regex search returns.
+const MAX_REGEX_MATCH_LENGTH: u32 = 4096;
+/// Largest displacement offset and instruction length a pattern scan
+/// resolves with, as they widen the overlap between the chunks it reads.
+const MAX_INSTRUCTION_LENGTH: u32 = 4096;
+/// Longest pointer path a pointer scan looks for.
+const MAX_POINTER_DEPTH: u8 = 8;
+const MAX_FREEZES: usize = 256;
+/// Longest value a freeze writes.
+const MAX_FREEZE_SIZE: usize = 64 * 1024;
+/// How often a freeze asking for 0 writes its value.
+const DEFAULT_FREEZE_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_WATCHES: usize = 64;
+/// Most bytes a watch reads per tick, over all of its targets.
+const MAX_WATCH_SIZE: u64 = 1024 * 1024;
+/// How often a watch asking for 0 reads its targets.
+const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_SNAPSHOTS: usize = 16;
+
+#[allow(dead_code)]
 enum ClientServerStateFlow {
     NewBorn,
     Connected,
@@ -15,100 +79,1392 @@ enum ClientServerStateFlow {
     Unknown,
 }
 
+/// A `C2SReadStreamPacket` that still has chunks to send.
+struct ReadStream {
+    request_id: u32,
+    address: u64,
+    size: u64,
+    chunk_size: u32,
+    offset: u64,
+}
+
+/// A `C2SFreezePacket` until it is unfrozen.
+struct Freeze {
+    request_id: u32,
+    /// Bound, so only the pointers remain to be read.
+    expression: Expression,
+    bytes: Vec<u8>,
+    interval: Duration,
+    due: Instant,
+}
+
+impl Freeze {
+    /// Writes the value and schedules the next write, returning the address.
+    fn write(&mut self, memory: &dyn MemoryBackend) -> std::io::Result<u64> {
+        let address = resolve_bound(&self.expression, memory)?;
+        memory.write(address, &self.bytes)?;
+        // Counted from now rather than from `due`, a session that fell behind
+        // should not write in bursts to catch up.
+        self.due = Instant::now() + self.interval;
+        Ok(address)
+    }
+}
+
+/// A `C2SWatchPacket` until it is unwatched.
+struct Watch {
+    request_id: u32,
+    /// Bound expressions with the number of bytes to read there.
+    targets: Vec<(Expression, usize)>,
+    every_tick: bool,
+    interval: Duration,
+    due: Instant,
+    /// Each target as of the last update, `None` before the first.
+    last: Vec<Option<WatchedRead>>,
+}
+
+/// The address of a watch target and its bytes, or the errno of the read.
+type WatchedRead = (u64, Result<Vec<u8>, i32>);
+
+impl Watch {
+    /// Reads the targets and schedules the next tick, returning the values to
+    /// push: the ones that changed, or all of them with `every_tick`.
+    fn tick(&mut self, memory: &dyn MemoryBackend) -> Vec<WatchValue> {
+        let addresses: Vec<std::io::Result<u64>> = self
+            .targets
+            .iter()
+            .map(|(expression, _)| resolve_bound(expression, memory))
+            .collect();
+        let ranges: Vec<(u64, usize)> = addresses
+            .iter()
+            .zip(&self.targets)
+            .filter_map(|(address, &(_, size))| Some((*address.as_ref().ok()?, size)))
+            .collect();
+        let mut reads = match memory.read_many(&ranges) {
+            Ok(reads) => reads.into_iter().map(|read| read.map_err(|error| errno(&error))).collect(),
+            Err(error) => vec![Err(errno(&error)); ranges.len()],
+        }
+        .into_iter();
+
+        let mut values = Vec::new();
+        for (index, address) in addresses.into_iter().enumerate() {
+            let current = match address {
+                Ok(address) => (address, reads.next().unwrap()),
+                Err(error) => (0, Err(errno(&error))),
+            };
+            if self.every_tick || self.last[index].as_ref() != Some(&current) {
+                let result = match &current.1 {
+                    Ok(data) => ReadManyResult::ok(data.clone()),
+                    Err(errno) => ReadManyResult::failed(*errno),
+                };
+                values.push(WatchValue {
+                    index: index as u32,
+                    address: current.0,
+                    result,
+                });
+            }
+            self.last[index] = Some(current);
+        }
+
+        self.due = Instant::now() + self.interval;
+        values
+    }
+}
+
 pub struct ClientSession {
     pub websocket: WebSocket<TcpStream>,
     state: ClientServerStateFlow,
-    memory: Memory,
+    memory: Box<dyn MemoryBackend>,
+    compression: Compression,
+    streams: VecDeque<ReadStream>,
+    scan: Option<Scan>,
+    /// The pointer scan last run or paged through, by name.
+    pointer_scan: Option<(Vec<u8>, PointerScan)>,
+    freezes: Vec<Freeze>,
+    watches: Vec<Watch>,
+    snapshots: HashMap<Vec<u8>, Snapshot>,
 }
 
 impl ClientSession {
-    pub fn new(websocket: WebSocket<TcpStream>) -> Self {
+    /// `memory` is the session's backend until the client selects another.
+    pub fn new(websocket: WebSocket<TcpStream>, memory: Box<dyn MemoryBackend>) -> Self {
         Self {
             websocket,
             state: ClientServerStateFlow::NewBorn,
-            memory: Memory::new(-1),
+            memory,
+            compression: Compression::None,
+            streams: VecDeque::new(),
+            scan: None,
+            pointer_scan: None,
+            freezes: Vec::new(),
+            watches: Vec::new(),
+            snapshots: HashMap::new(),
         }
     }
 
-    fn error_response(&mut self, error: Error) {
+    /// Serves the client until the connection closes, interleaving pending
+    /// read streams, frozen values and watches with incoming requests so they
+    /// can be cancelled.
+    pub fn run(&mut self) {
+        loop {
+            let timeout = if self.streams.is_empty() {
+                // Zero would be rejected, work that is due waits that little.
+                let now = Instant::now();
+                let freezes = self.freezes.iter().map(|freeze| freeze.due);
+                let watches = self.watches.iter().map(|watch| watch.due);
+                freezes
+                    .chain(watches)
+                    .min()
+                    .map(|due| due.saturating_duration_since(now).max(STREAM_POLL_INTERVAL))
+            } else {
+                Some(STREAM_POLL_INTERVAL)
+            };
+            if let Err(error) = self.websocket.get_ref().set_read_timeout(timeout) {
+                info!("Closing session: {}", error);
+                break;
+            }
+
+            match self.websocket.read() {
+                // We do not want to send back ping/pong messages.
+                Ok(msg) if msg.is_binary() || msg.is_text() => self.message_handler(msg),
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(error))
+                    if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(error) => {
+                    info!("Closing session: {}", error);
+                    break;
+                }
+            }
+
+            self.pump_streams();
+            self.rewrite_frozen();
+            self.tick_watches();
+        }
+    }
+
+    /// Pushes updates for the watches that are due.
+    fn tick_watches(&mut self) {
+        let now = Instant::now();
+        for index in 0..self.watches.len() {
+            if self.watches[index].due <= now {
+                self.send_watch_update(index);
+            }
+        }
+    }
+
+    fn send_watch_update(&mut self, index: usize) {
+        let watch = &mut self.watches[index];
+        let values = watch.tick(self.memory.as_ref());
+        if !values.is_empty() {
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+            self.websocket
+                .send(Message::Binary(S2CWatchUpdatePacket::out_bytes(
+                    watch.request_id,
+                    timestamp.as_micros() as u64,
+                    values,
+                )))
+                .unwrap();
+        }
+    }
+
+    /// Writes the frozen values that are due, ending the freezes that fail.
+    fn rewrite_frozen(&mut self) {
+        let now = Instant::now();
+        let memory = self.memory.as_ref();
+        let mut failed = Vec::new();
+        self.freezes.retain_mut(|freeze| {
+            if freeze.due > now {
+                return true;
+            }
+            match freeze.write(memory) {
+                Ok(_) => true,
+                Err(error) => {
+                    failed.push((freeze.request_id, errno(&error)));
+                    false
+                }
+            }
+        });
+
+        for (request_id, errno) in failed {
+            self.websocket
+                .send(Message::Binary(S2CFreezeEndPacket::out_bytes(request_id, FreezeEnd::Failed, errno)))
+                .unwrap();
+        }
+    }
+
+    /// Sends the next chunk of the oldest pending stream and requeues it,
+    /// so concurrent streams advance round-robin.
+    fn pump_streams(&mut self) {
+        let Some(mut stream) = self.streams.pop_front() else {
+            return;
+        };
+
+        let length = (stream.size - stream.offset).min(stream.chunk_size as u64) as usize;
+        match self.memory.read(stream.address + stream.offset, length) {
+            Ok(data) => {
+                let compression = if length >= COMPRESSION_THRESHOLD as usize {
+                    self.compression
+                } else {
+                    Compression::None
+                };
+                self.websocket
+                    .send(Message::Binary(S2CReadChunkPacket::out_bytes(
+                        stream.request_id,
+                        stream.offset,
+                        data,
+                        compression,
+                    )))
+                    .unwrap();
+                stream.offset += length as u64;
+
+                if stream.offset < stream.size {
+                    self.streams.push_back(stream);
+                } else {
+                    self.end_stream(&stream, StreamEnd::Complete, 0);
+                }
+            }
+            Err(error) => self.end_stream(&stream, StreamEnd::Failed, errno(&error)),
+        }
+    }
+
+    fn end_stream(&mut self, stream: &ReadStream, reason: StreamEnd, errno: i32) {
+        self.websocket
+            .send(Message::Binary(S2CReadStreamEndPacket::out_bytes(
+                stream.request_id,
+                reason,
+                errno,
+                stream.offset,
+            )))
+            .unwrap();
+    }
+
+    fn send_error(&mut self, request_id: u32, code: ErrorCode, errno: i32, packet_type: u8, message: String) {
+        self.websocket
+            .send(Message::Binary(S2CErrorPacket::out_bytes(
+                request_id,
+                code,
+                errno,
+                packet_type,
+                message,
+            )))
+            .unwrap();
+    }
+
+    fn error_response(&mut self, request_id: u32, packet_type: u8, error: Error) {
+        self.send_error(
+            request_id,
+            error_code(&error),
+            errno(&error),
+            packet_type,
+            error.to_string(),
+        );
+    }
+
+    fn protocol_error_response(&mut self, packet_data: &[u8], error: ProtocolError) {
+        self.send_error(
+            peek_request_id(packet_data).unwrap_or(0),
+            error.code(),
+            0,
+            packet_data.first().copied().unwrap_or(0),
+            error.to_string(),
+        );
+    }
+
+    fn send_scan_status(&mut self, request_id: u32, result_count: u64, truncated: bool) {
         self.websocket
-            .write_message(Message::text(error.to_string()))
+            .send(Message::Binary(S2CScanStatusPacket::out_bytes(
+                request_id,
+                result_count,
+                truncated,
+            )))
+            .unwrap();
+    }
+
+    fn no_scan_error(&mut self, request_id: u32, packet_type: u8) {
+        self.send_error(
+            request_id,
+            ErrorCode::NoScan,
+            0,
+            packet_type,
+            "No scan has been started".to_string(),
+        );
+    }
+
+    /// Parses an address expression and binds it to the target's regions.
+    fn bind_expression(&self, text: &EncodedString) -> std::io::Result<Expression> {
+        let text = text
+            .as_str()
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "Expression is not UTF-8"))?;
+        let expression = Expression::parse(text).map_err(expression_error)?;
+        expression.bind(&self.memory.regions()?).map_err(expression_error)
+    }
+
+    /// Refuses reads above `MAX_READ_SIZE` with an error response.
+    fn check_read_size(&mut self, request_id: u32, packet_type: u8, size: u64) -> bool {
+        if size <= MAX_READ_SIZE as u64 {
+            return true;
+        }
+        self.send_error(
+            request_id,
+            ErrorCode::RequestTooLarge,
+            0,
+            packet_type,
+            format!("Read of {} bytes exceeds the limit of {}", size, MAX_READ_SIZE),
+        );
+        false
+    }
+
+    /// Refuses and closes the connection if a hello packet's version differs
+    /// from ours. Checked before decoding, as the rest of the layout depends on it.
+    fn check_protocol_version(&mut self, packet_data: &[u8]) -> bool {
+        let protocol_version = C2SHelloPacket::peek_protocol_version(packet_data);
+        if protocol_version.is_some_and(|version| version != PROTOCOL_VERSION) {
+            self.send_error(
+                peek_request_id(packet_data).unwrap_or(0),
+                ErrorCode::IncompatibleVersion,
+                0,
+                PacketType::Hello as u8,
+                format!(
+                    "Client speaks protocol version {}, service speaks {}",
+                    protocol_version.unwrap(), PROTOCOL_VERSION
+                ),
+            );
+            let _ = self.websocket.close(None);
+            return false;
+        }
+        true
+    }
+
+    fn hello(&mut self, packet: C2SHelloPacket) {
+        self.state = ClientServerStateFlow::Connected;
+        self.compression = packet.compression;
+        self.websocket
+            .send(Message::Binary(S2CServerInfoPacket::out_bytes(
+                packet.request_id,
+                env!("CARGO_PKG_VERSION").to_string(),
+                self.memory.name() == SimulatedMemory::NAME,
+                backend_names(self.memory.name()),
+                MAX_READ_SIZE,
+                CAPABILITIES,
+                self.compression,
+                COMPRESSION_THRESHOLD,
+            )))
             .unwrap();
     }
 
     pub fn message_handler(&mut self, msg: Message) {
         let packet_data = msg.into_data();
 
-        match PacketType::from_u8(packet_data[0]) {
-            Some(PacketType::Read) => {
-                let packet = C2SReadMemoryPacket::parse(&packet_data);
+        if let Err(error) = self.handle_packet(&packet_data) {
+            warn!("Rejected packet: {}", error);
+            self.protocol_error_response(&packet_data, error);
+        }
+    }
+
+    fn handle_packet(&mut self, packet_data: &[u8]) -> Result<(), ProtocolError> {
+        let packet_type = *packet_data.first().ok_or(ProtocolError::Empty)?;
+
+        if let ClientServerStateFlow::NewBorn = self.state {
+            if packet_type != PacketType::Hello as u8 {
+                self.send_error(
+                    peek_request_id(packet_data).unwrap_or(0),
+                    ErrorCode::HandshakeRequired,
+                    0,
+                    packet_type,
+                    "Expected a hello packet first".to_string(),
+                );
+                return Ok(());
+            }
+        }
+        if packet_type == PacketType::Hello as u8 && !self.check_protocol_version(packet_data) {
+            return Ok(());
+        }
+
+        match C2SPacket::decode(packet_data)? {
+            C2SPacket::Hello(packet) => self.hello(packet),
+            C2SPacket::Read(packet) => {
+                if !self.check_read_size(packet.request_id, packet_type, packet.size as u64) {
+                    return Ok(());
+                }
 
                 match self.memory.read(packet.address, packet.size as usize) {
                     Ok(result) => {
+                        let compression = if packet.size >= COMPRESSION_THRESHOLD {
+                            self.compression
+                        } else {
+                            Compression::None
+                        };
                         self.websocket
-                            .write_message(Message::Binary(S2CReadMemoryPacketResponse::out_bytes(
+                            .send(Message::Binary(S2CReadMemoryPacketResponse::out_bytes_compressed(
+                                packet.request_id,
                                 result,
+                                compression,
+                            )))
+                            .unwrap();
+                    }
+                    Err(error) => self.error_response(packet.request_id, packet_type, error),
+                }
+            },
+            C2SPacket::ReadTolerant(packet) => {
+                if !self.check_read_size(packet.request_id, packet_type, packet.size as u64) {
+                    return Ok(());
+                }
+
+                match self.memory.read_tolerant(packet.address, packet.size as usize) {
+                    Ok((data, readable)) => {
+                        self.websocket
+                            .send(Message::Binary(S2CReadTolerantPacketResponse::out_bytes(
+                                packet.request_id,
+                                packet.address,
+                                memory::page_size() as u32,
+                                data,
+                                &readable,
                             )))
                             .unwrap();
                     }
-                    Err(error) => self.error_response(error),
+                    Err(error) => self.error_response(packet.request_id, packet_type, error),
+                }
+            },
+            C2SPacket::ReadStream(packet) => {
+                if self.streams.len() >= MAX_STREAMS {
+                    self.send_error(
+                        packet.request_id,
+                        ErrorCode::RequestTooLarge,
+                        0,
+                        packet_type,
+                        format!("Only {} read streams may run at once", MAX_STREAMS),
+                    );
+                    return Ok(());
+                }
+
+                let stream = ReadStream {
+                    request_id: packet.request_id,
+                    address: packet.address,
+                    size: packet.size,
+                    chunk_size: match packet.chunk_size {
+                        0 => DEFAULT_CHUNK_SIZE,
+                        chunk_size => chunk_size.min(MAX_CHUNK_SIZE),
+                    },
+                    offset: 0,
+                };
+                if stream.size == 0 {
+                    self.end_stream(&stream, StreamEnd::Complete, 0);
+                } else {
+                    self.streams.push_back(stream);
+                }
+            },
+            C2SPacket::CancelStream(packet) => {
+                match self
+                    .streams
+                    .iter()
+                    .position(|stream| stream.request_id == packet.stream_request_id)
+                {
+                    Some(index) => {
+                        let stream = self.streams.remove(index).unwrap();
+                        self.end_stream(&stream, StreamEnd::Cancelled, 0);
+                    }
+                    None => self.send_error(
+                        packet.request_id,
+                        ErrorCode::Other,
+                        0,
+                        packet_type,
+                        format!("No read stream with request id {}", packet.stream_request_id),
+                    ),
                 }
             },
-            Some(PacketType::Write) => {
-                let packet = C2SWriteMemoryPacket::parse(&packet_data);
+            C2SPacket::ReadMany(packet) => {
+                let total: u64 = packet.ranges.iter().map(|range| range.size as u64).sum();
+                if !self.check_read_size(packet.request_id, packet_type, total) {
+                    return Ok(());
+                }
 
+                let ranges: Vec<(u64, usize)> = packet
+                    .ranges
+                    .iter()
+                    .map(|range| (range.address, range.size as usize))
+                    .collect();
+
+                match self.memory.read_many(&ranges) {
+                    Ok(results) => {
+                        let results = results
+                            .into_iter()
+                            .map(|result| match result {
+                                Ok(data) => ReadManyResult::ok(data),
+                                Err(error) => ReadManyResult::failed(errno(&error)),
+                            })
+                            .collect();
+                        self.websocket
+                            .send(Message::Binary(S2CReadManyPacketResponse::out_bytes(
+                                packet.request_id,
+                                results,
+                            )))
+                            .unwrap();
+                    }
+                    Err(error) => self.error_response(packet.request_id, packet_type, error),
+                }
+            },
+            C2SPacket::Write(packet) => {
                 match self.memory.write(packet.address, &packet.bytes) {
                     Ok(result) => {
                         self.websocket
-                            .write_message(Message::Binary(
-                                S2CWriteMemoryPacketResponse::out_bytes(result as u64),
+                            .send(Message::Binary(
+                                S2CWriteMemoryPacketResponse::out_bytes(packet.request_id, result as u64),
                             ))
                             .unwrap();
                     }
-                    Err(error) => self.error_response(error),
+                    Err(error) => self.error_response(packet.request_id, packet_type, error),
+                }
+            },
+            C2SPacket::WriteMany(packet) => {
+                let entries: Vec<(u64, &[u8])> = packet
+                    .entries
+                    .iter()
+                    .map(|entry| (entry.address, entry.bytes.as_slice()))
+                    .collect();
+
+                match self.memory.write_many(&entries) {
+                    Ok(results) => {
+                        let results = results
+                            .into_iter()
+                            .map(|result| match result {
+                                Ok(bytes_written) => WriteManyResult::ok(bytes_written as u32),
+                                Err(error) => WriteManyResult::failed(errno(&error)),
+                            })
+                            .collect();
+                        self.websocket
+                            .send(Message::Binary(S2CWriteManyPacketResponse::out_bytes(
+                                packet.request_id,
+                                results,
+                            )))
+                            .unwrap();
+                    }
+                    Err(error) => self.error_response(packet.request_id, packet_type, error),
+                }
+            },
+            C2SPacket::StartScan(packet) => {
+                let layout = ValueLayout::new(packet.value_type, packet.endianness, packet.alignment);
+                let scan = self.memory.regions().and_then(|regions| {
+                    Scan::first(self.memory.as_ref(), &regions, layout, packet.comparison, &packet.value, &packet.upper)
+                });
+
+                match scan {
+                    Ok(scan) => {
+                        self.send_scan_status(packet.request_id, scan.len() as u64, scan.truncated);
+                        self.scan = Some(scan);
+                    }
+                    Err(error) => self.error_response(packet.request_id, packet_type, error),
+                }
+            },
+            C2SPacket::NextScan(packet) => {
+                let Some(scan) = self.scan.as_mut() else {
+                    self.no_scan_error(packet.request_id, packet_type);
+                    return Ok(());
+                };
+
+                match scan.next(self.memory.as_ref(), packet.comparison, &packet.value, &packet.upper) {
+                    Ok(()) => {
+                        let (result_count, truncated) = (scan.len() as u64, scan.truncated);
+                        self.send_scan_status(packet.request_id, result_count, truncated);
+                    }
+                    Err(error) => self.error_response(packet.request_id, packet_type, error),
+                }
+            },
+            C2SPacket::ScanResults(packet) => {
+                let Some(scan) = self.scan.as_ref() else {
+                    self.no_scan_error(packet.request_id, packet_type);
+                    return Ok(());
+                };
+
+                let results = scan
+                    .results(packet.offset as usize, packet.limit.min(MAX_SCAN_RESULTS_PAGE) as usize)
+                    .into_iter()
+                    .map(|(address, value)| ScanResult::new(address, value))
+                    .collect();
+                let total = scan.len() as u64;
+                self.websocket
+                    .send(Message::Binary(S2CScanResultsPacket::out_bytes(
+                        packet.request_id,
+                        total,
+                        packet.offset,
+                        results,
+                    )))
+                    .unwrap();
+            },
+            C2SPacket::ClearScan(packet) => {
+                self.scan = None;
+                self.send_scan_status(packet.request_id, 0, false);
+            },
+            C2SPacket::PatternScan(packet) => {
+                if packet.resolve
+                    && (packet.displacement_offset > MAX_INSTRUCTION_LENGTH
+                        || packet.instruction_length > MAX_INSTRUCTION_LENGTH)
+                {
+                    self.send_error(
+                        packet.request_id,
+                        ErrorCode::InvalidArgument,
+                        0,
+                        packet_type,
+                        format!(
+                            "The displacement offset and instruction length must be at most {}",
+                            MAX_INSTRUCTION_LENGTH
+                        ),
+                    );
+                    return Ok(());
+                }
+
+                let pattern = packet
+                    .pattern
+                    .as_str()
+                    .map_err(|error| Error::new(ErrorKind::InvalidInput, error))
+                    .and_then(Pattern::parse);
+                let matches = pattern.and_then(|pattern| {
+                    let regions: Vec<Region> = self.memory.regions()?
+                        .into_iter()
+                        .filter(|region| packet.filter.matches(region))
+                        .collect();
+                    let resolve = packet
+                        .resolve
+                        .then(|| RipRelative::new(packet.displacement_offset, packet.instruction_length));
+                    Ok(pattern_scan(self.memory.as_ref(), &regions, &pattern, resolve, search_limit(packet.max_results)))
+                });
+
+                match matches {
+                    Ok((matches, truncated)) => {
+                        self.websocket
+                            .send(Message::Binary(S2CPatternScanPacketResponse::out_bytes(
+                                packet.request_id,
+                                truncated,
+                                matches,
+                            )))
+                            .unwrap();
+                    }
+                    Err(error) => self.error_response(packet.request_id, packet_type, error),
+                }
+            },
+            C2SPacket::StringSearch(packet) => {
+                let needles = string_needles(packet.text.as_bytes(), packet.utf8, packet.utf16, packet.ignore_case);
+                let hits = needles.and_then(|needles| {
+                    let regions: Vec<Region> = self.memory.regions()?
+                        .into_iter()
+                        .filter(|region| packet.filter.matches(region))
+                        .collect();
+                    string_search(
+                        self.memory.as_ref(),
+                        &regions,
+                        &needles,
+                        packet.context.min(MAX_SEARCH_CONTEXT) as usize,
+                        search_limit(packet.max_results),
+                    )
+                });
+
+                match hits {
+                    Ok((hits, truncated)) => {
+                        self.websocket
+                            .send(Message::Binary(S2CStringSearchPacketResponse::out_bytes(
+                                packet.request_id,
+                                truncated,
+                                hits,
+                            )))
+                            .unwrap();
+                    }
+                    Err(error) => self.error_response(packet.request_id, packet_type, error),
+                }
+            },
+            C2SPacket::RegexSearch(packet) => {
+                let matches = compile_regex(packet.pattern.as_bytes()).and_then(|regex| {
+                    let regions: Vec<Region> = self.memory.regions()?
+                        .into_iter()
+                        .filter(|region| packet.filter.matches(region))
+                        .collect();
+                    let max_match_length = match packet.max_match_length {
+                        0 => MAX_REGEX_MATCH_LENGTH,
+                        length => length.min(MAX_REGEX_MATCH_LENGTH),
+                    };
+                    Ok(regex_search(
+                        self.memory.as_ref(),
+                        &regions,
+                        &regex,
+                        max_match_length as usize,
+                        search_limit(packet.max_results),
+                    ))
+                });
+
+                match matches {
+                    Ok((matches, truncated)) => {
+                        self.websocket
+                            .send(Message::Binary(S2CRegexSearchPacketResponse::out_bytes(
+                                packet.request_id,
+                                truncated,
+                                matches,
+                            )))
+                            .unwrap();
+                    }
+                    Err(error) => self.error_response(packet.request_id, packet_type, error),
+                }
+            },
+            C2SPacket::PointerScan(packet) => {
+                if packet.max_depth == 0 || packet.max_depth > MAX_POINTER_DEPTH {
+                    self.send_error(
+                        packet.request_id,
+                        ErrorCode::InvalidArgument,
+                        0,
+                        packet_type,
+                        format!("The depth of a pointer scan must be between 1 and {}", MAX_POINTER_DEPTH),
+                    );
+                    return Ok(());
+                }
+
+                let name = packet.name.as_bytes();
+                let scan = storage::path("pointer-scans", name).and_then(|path| {
+                    let regions = self.memory.regions()?;
+                    let scan = if packet.rescan {
+                        let saved = load_pointer_scan(&path, name)?;
+                        PointerScan {
+                            paths: pointer::rescan(self.memory.as_ref(), &regions, saved.paths, packet.target),
+                            truncated: saved.truncated,
+                        }
+                    } else {
+                        pointer::scan(
+                            self.memory.as_ref(),
+                            &regions,
+                            packet.target,
+                            packet.max_depth as usize,
+                            packet.max_offset,
+                        )
+                    };
+                    pointer::save(&path, &scan)?;
+                    Ok(scan)
+                });
+
+                match scan {
+                    Ok(scan) => {
+                        self.send_scan_status(packet.request_id, scan.paths.len() as u64, scan.truncated);
+                        self.pointer_scan = Some((name.to_vec(), scan));
+                    }
+                    Err(error) => self.error_response(packet.request_id, packet_type, error),
+                }
+            },
+            C2SPacket::PointerScanResults(packet) => {
+                let name = packet.name.as_bytes();
+                if self.pointer_scan.as_ref().is_none_or(|(cached, _)| cached != name) {
+                    match storage::path("pointer-scans", name).and_then(|path| load_pointer_scan(&path, name)) {
+                        Ok(scan) => self.pointer_scan = Some((name.to_vec(), scan)),
+                        Err(error) => {
+                            self.error_response(packet.request_id, packet_type, error);
+                            return Ok(());
+                        }
+                    }
+                }
+
+                let (_, scan) = self.pointer_scan.as_ref().unwrap();
+                let paths = scan
+                    .paths
+                    .iter()
+                    .skip(packet.offset as usize)
+                    .take(packet.limit.min(MAX_SCAN_RESULTS_PAGE) as usize)
+                    .cloned()
+                    .collect();
+                let total = scan.paths.len() as u64;
+                self.websocket
+                    .send(Message::Binary(S2CPointerScanResultsPacket::out_bytes(
+                        packet.request_id,
+                        total,
+                        packet.offset,
+                        paths,
+                    )))
+                    .unwrap();
+            },
+            C2SPacket::ResolvePointerChain(packet) => {
+                if !self.check_read_size(packet.request_id, packet_type, packet.read_size as u64) {
+                    return Ok(());
+                }
+
+                let module = packet.module.as_bytes();
+                let base = if module.is_empty() {
+                    Some(0)
+                } else {
+                    match self.memory.regions() {
+                        Ok(regions) => pointer::Modules::new(&regions).base_by_name(module),
+                        Err(error) => {
+                            self.error_response(packet.request_id, packet_type, error);
+                            return Ok(());
+                        }
+                    }
+                };
+                let Some(base) = base else {
+                    let message = format!("No module named '{}'", String::from_utf8_lossy(module));
+                    self.send_error(packet.request_id, ErrorCode::InvalidArgument, 0, packet_type, message);
+                    return Ok(());
+                };
+
+                let (addresses, error) = pointer::follow(self.memory.as_ref(), base.wrapping_add(packet.base), &packet.offsets);
+                let result = match error {
+                    Some(error) => Err(error),
+                    None => self.memory.read(*addresses.last().unwrap(), packet.read_size as usize),
+                };
+                let response = match result {
+                    Ok(data) => S2CResolvePointerChainPacketResponse::ok(packet.request_id, addresses, data),
+                    Err(error) => S2CResolvePointerChainPacketResponse::failed(
+                        packet.request_id,
+                        addresses,
+                        errno(&error),
+                    ),
+                };
+                self.websocket.send(Message::Binary(response)).unwrap();
+            },
+            C2SPacket::ResolveExpression(packet) => {
+                if !self.check_read_size(packet.request_id, packet_type, packet.read_size as u64) {
+                    return Ok(());
+                }
+
+                let result = self.bind_expression(&packet.expression).and_then(|expression| {
+                    let address = resolve_bound(&expression, self.memory.as_ref())?;
+                    Ok((address, self.memory.read(address, packet.read_size as usize)?))
+                });
+                match result {
+                    Ok((address, data)) => {
+                        self.websocket
+                            .send(Message::Binary(S2CResolveExpressionPacketResponse::out_bytes(
+                                packet.request_id,
+                                address,
+                                data,
+                            )))
+                            .unwrap();
+                    }
+                    Err(error) => self.error_response(packet.request_id, packet_type, error),
+                }
+            },
+            C2SPacket::Freeze(packet) => {
+                if self.freezes.len() >= MAX_FREEZES {
+                    self.send_error(
+                        packet.request_id,
+                        ErrorCode::RequestTooLarge,
+                        0,
+                        packet_type,
+                        format!("Only {} values may be frozen at once", MAX_FREEZES),
+                    );
+                    return Ok(());
+                }
+                if packet.bytes.is_empty() || packet.bytes.len() > MAX_FREEZE_SIZE {
+                    let message = format!("Frozen values must be 1 to {} bytes long", MAX_FREEZE_SIZE);
+                    self.send_error(packet.request_id, ErrorCode::InvalidArgument, 0, packet_type, message);
+                    return Ok(());
+                }
+
+                let freeze = self.bind_expression(&packet.expression).map(|expression| Freeze {
+                    request_id: packet.request_id,
+                    expression,
+                    bytes: packet.bytes,
+                    interval: match packet.interval_ms {
+                        0 => DEFAULT_FREEZE_INTERVAL,
+                        interval_ms => Duration::from_millis(interval_ms as u64),
+                    },
+                    due: Instant::now(),
+                });
+                match freeze.and_then(|mut freeze| Ok((freeze.write(self.memory.as_ref())?, freeze))) {
+                    Ok((address, freeze)) => {
+                        self.freezes.push(freeze);
+                        self.websocket
+                            .send(Message::Binary(S2CFreezePacketResponse::out_bytes(packet.request_id, address)))
+                            .unwrap();
+                    }
+                    Err(error) => self.error_response(packet.request_id, packet_type, error),
+                }
+            },
+            C2SPacket::Unfreeze(packet) => {
+                match self
+                    .freezes
+                    .iter()
+                    .position(|freeze| freeze.request_id == packet.freeze_request_id)
+                {
+                    Some(index) => {
+                        let freeze = self.freezes.remove(index);
+                        self.websocket
+                            .send(Message::Binary(S2CFreezeEndPacket::out_bytes(
+                                freeze.request_id,
+                                FreezeEnd::Unfrozen,
+                                0,
+                            )))
+                            .unwrap();
+                    }
+                    None => self.send_error(
+                        packet.request_id,
+                        ErrorCode::Other,
+                        0,
+                        packet_type,
+                        format!("No frozen value with request id {}", packet.freeze_request_id),
+                    ),
+                }
+            },
+            C2SPacket::Watch(packet) => {
+                if self.watches.len() >= MAX_WATCHES {
+                    self.send_error(
+                        packet.request_id,
+                        ErrorCode::RequestTooLarge,
+                        0,
+                        packet_type,
+                        format!("Only {} watches may run at once", MAX_WATCHES),
+                    );
+                    return Ok(());
+                }
+                let size: u64 = packet.targets.iter().map(|target| target.size as u64).sum();
+                if size > MAX_WATCH_SIZE {
+                    self.send_error(
+                        packet.request_id,
+                        ErrorCode::RequestTooLarge,
+                        0,
+                        packet_type,
+                        format!("Watch of {} bytes exceeds the limit of {}", size, MAX_WATCH_SIZE),
+                    );
+                    return Ok(());
+                }
+
+                let targets: std::io::Result<Vec<(Expression, usize)>> = packet
+                    .targets
+                    .iter()
+                    .map(|target| Ok((self.bind_expression(&target.expression)?, target.size as usize)))
+                    .collect();
+                match targets {
+                    Ok(targets) if targets.is_empty() => {
+                        let message = "A watch needs at least one target".to_string();
+                        self.send_error(packet.request_id, ErrorCode::InvalidArgument, 0, packet_type, message);
+                    }
+                    Ok(targets) => {
+                        self.watches.push(Watch {
+                            request_id: packet.request_id,
+                            last: vec![None; targets.len()],
+                            targets,
+                            every_tick: packet.every_tick,
+                            interval: match packet.interval_ms {
+                                0 => DEFAULT_WATCH_INTERVAL,
+                                interval_ms => Duration::from_millis(interval_ms as u64),
+                            },
+                            due: Instant::now(),
+                        });
+                        self.send_watch_update(self.watches.len() - 1);
+                    }
+                    Err(error) => self.error_response(packet.request_id, packet_type, error),
+                }
+            },
+            C2SPacket::Unwatch(packet) => {
+                match self
+                    .watches
+                    .iter()
+                    .position(|watch| watch.request_id == packet.watch_request_id)
+                {
+                    Some(index) => {
+                        let watch = self.watches.remove(index);
+                        self.websocket
+                            .send(Message::Binary(S2CWatchEndPacket::out_bytes(watch.request_id)))
+                            .unwrap();
+                    }
+                    None => self.send_error(
+                        packet.request_id,
+                        ErrorCode::Other,
+                        0,
+                        packet_type,
+                        format!("No watch with request id {}", packet.watch_request_id),
+                    ),
+                }
+            },
+            C2SPacket::TakeSnapshot(packet) => {
+                let name = packet.name.as_bytes();
+                if name.is_empty() {
+                    let message = "Snapshots need a name".to_string();
+                    self.send_error(packet.request_id, ErrorCode::InvalidArgument, 0, packet_type, message);
+                    return Ok(());
+                }
+                // Dropped first, a retaken snapshot should not need twice the space.
+                if self.snapshots.remove(name).is_none() && self.snapshots.len() >= MAX_SNAPSHOTS {
+                    self.send_error(
+                        packet.request_id,
+                        ErrorCode::RequestTooLarge,
+                        0,
+                        packet_type,
+                        format!("Only {} snapshots may be kept at once", MAX_SNAPSHOTS),
+                    );
+                    return Ok(());
+                }
+
+                let snapshot = self.memory.regions().and_then(|regions| {
+                    let regions: Vec<Region> = regions
+                        .into_iter()
+                        .filter(|region| packet.filter.matches(region))
+                        .collect();
+                    Snapshot::take(self.memory.as_ref(), &regions, packet.to_disk)
+                });
+                match snapshot {
+                    Ok(snapshot) => {
+                        self.websocket
+                            .send(Message::Binary(S2CSnapshotStatusPacket::out_bytes(
+                                packet.request_id,
+                                snapshot.region_count,
+                                snapshot.size,
+                                snapshot.on_disk(),
+                            )))
+                            .unwrap();
+                        self.snapshots.insert(name.to_vec(), snapshot);
+                    }
+                    Err(error) => self.error_response(packet.request_id, packet_type, error),
+                }
+            },
+            C2SPacket::DiffSnapshots(packet) => {
+                let max_results = search_limit(packet.max_results);
+                let diff = find_snapshot(&self.snapshots, packet.old.as_bytes()).and_then(|old| {
+                    match packet.new.as_bytes() {
+                        b"" => snapshot::diff_live(old, self.memory.as_ref(), max_results),
+                        new => snapshot::diff(old, find_snapshot(&self.snapshots, new)?, max_results),
+                    }
+                });
+                match diff {
+                    Ok(diff) => {
+                        self.websocket
+                            .send(Message::Binary(S2CDiffSnapshotsPacketResponse::out_bytes(
+                                packet.request_id,
+                                diff.truncated,
+                                diff.ranges,
+                            )))
+                            .unwrap();
+                    }
+                    Err(error) => self.error_response(packet.request_id, packet_type, error),
+                }
+            },
+            C2SPacket::DeleteSnapshot(packet) => {
+                match self.snapshots.remove(packet.name.as_bytes()) {
+                    Some(_) => {
+                        self.websocket
+                            .send(Message::Binary(S2CSnapshotStatusPacket::out_bytes(packet.request_id, 0, 0, false)))
+                            .unwrap();
+                    }
+                    None => self.error_response(packet.request_id, packet_type, no_snapshot(packet.name.as_bytes())),
+                }
+            },
+            C2SPacket::SelectBackend(packet) => {
+                match memory::backend(packet.backend.as_bytes()) {
+                    Some(mut backend) => {
+                        backend.set_pid(self.memory.pid());
+                        self.memory = backend;
+                        self.websocket
+                            .send(Message::Binary(S2CSelectBackendPacketResponse::out_bytes(
+                                packet.request_id,
+                                self.memory.name().to_string(),
+                            )))
+                            .unwrap();
+                    }
+                    None => {
+                        let error = Error::new(
+                            ErrorKind::InvalidInput,
+                            format!("No backend named '{}'", packet.backend.to_string_lossy()),
+                        );
+                        self.error_response(packet.request_id, packet_type, error);
+                    }
                 }
             },
-            Some(PacketType::TargetPID) => {
-                let packet = C2STargetPidPacket::parse(&packet_data);
+            C2SPacket::TargetPID(packet) => {
                 self.set_target_pid(packet.target_pid);
 
-                match get_regions(packet.target_pid) {
+                match self.memory.regions() {
                     Ok(regions) => {
                         self.websocket
-                            .write_message(Message::Binary(
-                                S2CTargetPidRegionsPacket::out_bytes(regions),
+                            .send(Message::Binary(
+                                S2CTargetPidRegionsPacket::out_bytes(packet.request_id, regions),
                             ))
                             .unwrap();
                     }
-                    Err(error) => self.error_response(error),
+                    Err(error) => self.error_response(packet.request_id, packet_type, error),
                 }
             },
-            Some(PacketType::SendProcesses) => {
-                match get_running_processes() {
+            C2SPacket::SendProcesses(packet) => {
+                match self.memory.processes() {
                     Ok(processes) => {
                         self.websocket
-                            .write_message(Message::Binary(
-                                S2CSendProcessesPacket::out_bytes(processes),
+                            .send(Message::Binary(
+                                S2CSendProcessesPacket::out_bytes(packet.request_id, processes),
                             ))
                             .unwrap();
                     }
-                    Err(error) => self.error_response(error),
+                    Err(error) => self.error_response(packet.request_id, packet_type, error),
                 }
             },
-            _ => println!("Unknown packet type"),
         };
+        Ok(())
     }
 
-    #[cfg(not(feature = "fake_read_write"))]
+    /// Switches to `pid`, ending what was set up for the previous target.
     fn set_target_pid(&mut self, pid: i32) {
+        for stream in std::mem::take(&mut self.streams) {
+            self.end_stream(&stream, StreamEnd::TargetChanged, 0);
+        }
         self.state = ClientServerStateFlow::TargetPID;
-        self.memory.pid = pid;
+        self.memory.set_pid(pid);
+        self.scan = None;
+        for freeze in std::mem::take(&mut self.freezes) {
+            self.websocket
+                .send(Message::Binary(S2CFreezeEndPacket::out_bytes(
+                    freeze.request_id,
+                    FreezeEnd::TargetChanged,
+                    0,
+                )))
+                .unwrap();
+        }
+        for watch in std::mem::take(&mut self.watches) {
+            self.websocket
+                .send(Message::Binary(S2CWatchEndPacket::out_bytes(watch.request_id)))
+                .unwrap();
+        }
+        self.snapshots.clear();
     }
+}
 
-    #[cfg(feature = "fake_read_write")]
-    fn set_target_pid(&mut self, pid: i32) {
-        println!("Target Pid: {}", pid);
-        self.state = ClientServerStateFlow::TargetPID;
-        self.memory.pid = pid;
+/// Resolves an expression from `ClientSession::bind_expression`.
+fn resolve_bound(expression: &Expression, memory: &dyn MemoryBackend) -> std::io::Result<u64> {
+    expression.resolve(&[], memory).map_err(expression_error)
+}
+
+/// Every backend name with `current` first, as `S2CServerInfoPacket` lists them.
+fn backend_names(current: &str) -> Vec<String> {
+    let others = memory::BACKENDS.into_iter().filter(|&name| name != current);
+    std::iter::once(current).chain(others).map(str::to_string).collect()
+}
+
+/// The errno behind `error`, 0 for errors not coming from the OS.
+fn errno(error: &Error) -> i32 {
+    error.raw_os_error().unwrap_or(0)
+}
+
+/// Failed reads keep their errno, anything else is the client's mistake.
+fn expression_error(error: ExpressionError) -> Error {
+    match error {
+        ExpressionError::Read { error, .. } => error,
+        error => Error::new(ErrorKind::InvalidInput, error.to_string()),
+    }
+}
+
+/// Loads the pointer scan saved under `name`, reporting a missing one as an
+/// invalid argument rather than as a missing process.
+fn load_pointer_scan(path: &std::path::Path, name: &[u8]) -> std::io::Result<PointerScan> {
+    pointer::load(path).map_err(|error| match error.kind() {
+        ErrorKind::NotFound => Error::new(
+            ErrorKind::InvalidInput,
+            format!("No pointer scan named '{}'", String::from_utf8_lossy(name)),
+        ),
+        _ => error,
+    })
+}
+
+/// The number of results a search asking for `max_results` returns, 0 asking
+/// for as many as we allow.
+fn search_limit(max_results: u32) -> usize {
+    let limit = match max_results {
+        0 => MAX_SEARCH_RESULTS,
+        max_results => max_results.min(MAX_SEARCH_RESULTS),
+    };
+    limit as usize
+}
+
+/// The snapshot taken under `name`.
+fn find_snapshot<'a>(snapshots: &'a HashMap<Vec<u8>, Snapshot>, name: &[u8]) -> std::io::Result<&'a Snapshot> {
+    snapshots.get(name).ok_or_else(|| no_snapshot(name))
+}
+
+/// Unknown names are the client's mistake, reported as an invalid argument.
+fn no_snapshot(name: &[u8]) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!("No snapshot named '{}'", String::from_utf8_lossy(name)),
+    )
+}
+
+/// Maps failures of the memory backends onto protocol error codes.
+fn error_code(error: &Error) -> ErrorCode {
+    match error.raw_os_error() {
+        Some(libc::ESRCH) => ErrorCode::NoSuchProcess,
+        Some(libc::EPERM) => ErrorCode::PermissionDenied,
+        Some(libc::EFAULT) => ErrorCode::BadAddress,
+        Some(_) => ErrorCode::Other,
+        None => match error.kind() {
+            ErrorKind::NotConnected => ErrorCode::NoTarget,
+            ErrorKind::NotFound => ErrorCode::NoSuchProcess,
+            ErrorKind::PermissionDenied => ErrorCode::PermissionDenied,
+            ErrorKind::UnexpectedEof | ErrorKind::WriteZero => ErrorCode::PartialTransfer,
+            ErrorKind::InvalidInput => ErrorCode::InvalidArgument,
+            _ => ErrorCode::Other,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use tungstenite::stream::MaybeTlsStream;
+
+    type Client = WebSocket<MaybeTlsStream<TcpStream>>;
+
+    /// Serves a session on `memory` and connects to it, past the hello.
+    fn connect(memory: SimulatedMemory) -> Client {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let websocket = tungstenite::accept(listener.accept().unwrap().0).unwrap();
+            ClientSession::new(websocket, Box::new(memory)).run();
+        });
+
+        let (mut client, _) = tungstenite::connect(format!("ws://{}", address)).unwrap();
+        match request(&mut client, C2SHelloPacket::out_bytes(1, Compression::None)) {
+            S2CPacket::ServerInfo(info) => {
+                let backends: Vec<_> = info.backends.iter().map(|name| name.to_string_lossy()).collect();
+                assert_eq!(vec!["simulated", "auto", "process_vm", "procmem"], backends);
+                assert!(info.fake_read_write);
+            }
+            packet => panic!("unexpected {:?}", packet),
+        }
+        client
+    }
+
+    /// Sends `data` and collects what the service sends until a packet
+    /// matching `last`.
+    fn request_until(client: &mut Client, data: Vec<u8>, last: impl Fn(&S2CPacket) -> bool) -> Vec<S2CPacket> {
+        client.send(Message::Binary(data)).unwrap();
+        let mut packets = Vec::new();
+        loop {
+            let packet = S2CPacket::decode(&client.read().unwrap().into_data()).unwrap();
+            let done = last(&packet);
+            packets.push(packet);
+            if done {
+                return packets;
+            }
+        }
+    }
+
+    fn request(client: &mut Client, data: Vec<u8>) -> S2CPacket {
+        client.send(Message::Binary(data)).unwrap();
+        S2CPacket::decode(&client.read().unwrap().into_data()).unwrap()
+    }
+
+    fn read(client: &mut Client, address: u64, size: u32) -> Result<Vec<u8>, ErrorCode> {
+        match request(client, C2SReadMemoryPacket::out_bytes(2, address, size)) {
+            S2CPacket::ReadResponse(packet) => Ok(packet.data),
+            S2CPacket::Error(packet) => Err(packet.code),
+            packet => panic!("unexpected {:?}", packet),
+        }
+    }
+
+    #[test]
+    fn test_session_on_simulated_memory() {
+        let mut client = connect(SimulatedMemory::demo());
+        assert_eq!(Err(ErrorCode::NoTarget), read(&mut client, 0x5555_5556_02A0, 4));
+
+        match request(&mut client, C2SGetProcessesPacket::out_bytes(3)) {
+            S2CPacket::Processes(packet) => {
+                let pids: Vec<_> = packet.processes.iter().map(|process| process.pid).collect();
+                assert_eq!(vec![1000], pids);
+            }
+            packet => panic!("unexpected {:?}", packet),
+        }
+        match request(&mut client, C2STargetPidPacket::out_bytes(4, 1000)) {
+            S2CPacket::Regions(packet) => assert_eq!(5, packet.regions.len()),
+            packet => panic!("unexpected {:?}", packet),
+        }
+
+        let expression = "[simulated+0x2010]".to_string();
+        match request(&mut client, C2SResolveExpressionPacket::out_bytes(5, expression, 4)) {
+            S2CPacket::ResolveExpressionResponse(packet) => {
+                assert_eq!((0x5555_5556_02A0, 100i32.to_le_bytes().to_vec()), (packet.address, packet.data));
+            }
+            packet => panic!("unexpected {:?}", packet),
+        }
+
+        match request(&mut client, C2SWriteMemoryPacket::out_bytes(6, 0x5555_5556_02A0, vec![1, 2])) {
+            S2CPacket::WriteResponse(packet) => assert_eq!(2, packet.bytes_written),
+            packet => panic!("unexpected {:?}", packet),
+        }
+        assert_eq!(Ok(vec![1, 2, 0, 0]), read(&mut client, 0x5555_5556_02A0, 4));
+        assert_eq!(Err(ErrorCode::BadAddress), read(&mut client, 0x1000, 4));
+        assert_eq!(Err(ErrorCode::PartialTransfer), read(&mut client, 0x5555_5558_0FFE, 4));
+        match request(&mut client, C2SWriteMemoryPacket::out_bytes(7, 0x5555_5555_4000, vec![0])) {
+            S2CPacket::Error(packet) => assert_eq!(ErrorCode::BadAddress, packet.code),
+            packet => panic!("unexpected {:?}", packet),
+        }
+
+        let filter = RegionFilter::new(0, Vec::new());
+        let scan = C2SPatternScanPacket::out_bytes(8, filter, "C3".to_string(), 0, true, u32::MAX - 15, 0);
+        match request(&mut client, scan) {
+            S2CPacket::Error(packet) => assert_eq!(ErrorCode::InvalidArgument, packet.code),
+            packet => panic!("unexpected {:?}", packet),
+        }
+
+        // The regions' error, not a missing module, once the target is gone.
+        request(&mut client, C2STargetPidPacket::out_bytes(8, 1001));
+        let chain = C2SResolvePointerChainPacket::out_bytes(9, b"simulated".to_vec(), 0x2010, vec![0], 4);
+        match request(&mut client, chain) {
+            S2CPacket::Error(packet) => assert_eq!(ErrorCode::NoSuchProcess, packet.code),
+            packet => panic!("unexpected {:?}", packet),
+        }
+    }
+
+    #[test]
+    fn test_freeze_and_select_backend() {
+        let mut client = connect(SimulatedMemory::demo());
+        request(&mut client, C2STargetPidPacket::out_bytes(2, 1000));
+
+        let bytes = 250i32.to_le_bytes().to_vec();
+        match request(&mut client, C2SFreezePacket::out_bytes(3, "$heap+0x2A0".to_string(), 10, bytes.clone())) {
+            S2CPacket::FreezeResponse(packet) => assert_eq!(0x5555_5556_02A0, packet.address),
+            packet => panic!("unexpected {:?}", packet),
+        }
+        request(&mut client, C2SWriteMemoryPacket::out_bytes(4, 0x5555_5556_02A0, vec![0; 4]));
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(Ok(bytes), read(&mut client, 0x5555_5556_02A0, 4));
+
+        match request(&mut client, C2SSelectBackendPacket::out_bytes(5, "procmem".to_string())) {
+            S2CPacket::SelectBackendResponse(packet) => assert_eq!(b"procmem", packet.backend.as_bytes()),
+            packet => panic!("unexpected {:?}", packet),
+        }
+        match request(&mut client, C2SSelectBackendPacket::out_bytes(6, "fake".to_string())) {
+            S2CPacket::Error(packet) => assert_eq!(ErrorCode::InvalidArgument, packet.code),
+            packet => panic!("unexpected {:?}", packet),
+        }
+    }
+
+    #[test]
+    fn test_target_change_ends_streams() {
+        let mut client = connect(SimulatedMemory::demo());
+        request(&mut client, C2STargetPidPacket::out_bytes(2, 1000));
+        client.send(Message::Binary(C2SReadStreamPacket::out_bytes(3, 0x5555_5556_0000, 0x21000, 16))).unwrap();
+
+        let packets = request_until(&mut client, C2STargetPidPacket::out_bytes(4, 1000), |packet| {
+            matches!(packet, S2CPacket::Regions(_))
+        });
+        let ends: Vec<_> = packets
+            .iter()
+            .filter_map(|packet| match packet {
+                S2CPacket::ReadStreamEnd(packet) => Some((packet.request_id, packet.reason)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec![(3, StreamEnd::TargetChanged)], ends);
+    }
+
+    #[test]
+    fn test_target_change_ends_freezes_and_watches() {
+        let mut client = connect(SimulatedMemory::demo());
+        request(&mut client, C2STargetPidPacket::out_bytes(2, 1000));
+        request(&mut client, C2SFreezePacket::out_bytes(3, "$heap+0x2A0".to_string(), 1000, vec![1]));
+        let targets = vec![WatchTarget::new("$heap+0x2A0".to_string(), 4)];
+        request(&mut client, C2SWatchPacket::out_bytes(4, 1000, false, targets));
+
+        let packets = request_until(&mut client, C2STargetPidPacket::out_bytes(5, 1000), |packet| {
+            matches!(packet, S2CPacket::Regions(_))
+        });
+        match &packets[0] {
+            S2CPacket::FreezeEnd(packet) => {
+                assert_eq!((3, FreezeEnd::TargetChanged), (packet.request_id, packet.reason));
+            }
+            packet => panic!("unexpected {:?}", packet),
+        }
+        match &packets[1] {
+            S2CPacket::WatchEnd(packet) => assert_eq!(4, packet.request_id),
+            packet => panic!("unexpected {:?}", packet),
+        }
     }
 }
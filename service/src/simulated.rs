@@ -0,0 +1,254 @@
+//! A backend serving made-up processes held in memory, to try the service
+//! and its clients without a real target and to drive the service in tests.
+
+use crate::memory::{check_pid, MemoryBackend};
+use libc::pid_t;
+use shared::protocol::{EncodedString, ProcessEntry, Region};
+use std::cell::RefCell;
+use std::io;
+
+const READ: u8 = 1 << 0;
+const WRITE: u8 = 1 << 1;
+
+struct Mapping {
+    region: Region,
+    bytes: RefCell<Vec<u8>>,
+}
+
+struct SimulatedProcess {
+    entry: ProcessEntry,
+    /// Sorted by address, never overlapping.
+    mappings: Vec<Mapping>,
+}
+
+/// Processes whose memory is plain buffers. Reads and writes fail where the
+/// `process_vm` syscalls would: on unmapped memory, on mappings without the
+/// needed permission, and partway through a range that runs into either.
+pub struct SimulatedMemory {
+    pid: pid_t,
+    processes: Vec<SimulatedProcess>,
+}
+
+impl Default for SimulatedMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimulatedMemory {
+    pub const NAME: &'static str = "simulated";
+
+    /// No processes at all.
+    pub fn new() -> Self {
+        Self {
+            pid: -1,
+            processes: Vec::new(),
+        }
+    }
+
+    /// A single process, pid 1000, with a module, a heap and a stack. The
+    /// module's data holds a pointer to the i32 100 at `[Heap]+0x2A0`.
+    pub fn demo() -> Self {
+        const MODULE: u64 = 0x5555_5555_4000;
+        const HEAP: u64 = 0x5555_5556_0000;
+
+        let mut header = vec![0; 0x1000];
+        header[..4].copy_from_slice(b"\x7fELF");
+        let mut data = vec![0; 0x1000];
+        data[0x10..0x18].copy_from_slice(&(HEAP + 0x2A0).to_le_bytes());
+        let mut heap = vec![0; 0x21000];
+        heap[0x2A0..0x2A4].copy_from_slice(&100i32.to_le_bytes());
+
+        let mut memory = Self::new();
+        memory.add_process(1000, b"simulated");
+        memory.map(1000, MODULE, "r--p", b"/usr/bin/simulated", header);
+        memory.map(1000, MODULE + 0x1000, "r-xp", b"/usr/bin/simulated", vec![0xC3; 0x1000]);
+        memory.map(1000, MODULE + 0x2000, "rw-p", b"/usr/bin/simulated", data);
+        memory.map(1000, HEAP, "rw-p", b"[Heap]", heap);
+        memory.map(1000, 0x7FFF_FFFD_E000, "rw-p", b"[Stack]", vec![0; 0x21000]);
+        memory
+    }
+
+    pub fn add_process(&mut self, pid: pid_t, name: &[u8]) {
+        self.processes.push(SimulatedProcess {
+            entry: ProcessEntry {
+                name: EncodedString::from_bytes(name.to_vec()),
+                pid,
+            },
+            mappings: Vec::new(),
+        });
+    }
+
+    /// Maps `bytes` at `start` in the process `pid`, with `permissions` and
+    /// `pathname` written as in `/proc/<pid>/maps` after `get_regions`
+    /// renamed the special mappings, e.g. `rw-p` and `[Heap]`.
+    ///
+    /// Panics if there is no such process or the mapping overlaps another.
+    pub fn map(&mut self, pid: pid_t, start: u64, permissions: &str, pathname: &[u8], bytes: Vec<u8>) {
+        let process = self
+            .processes
+            .iter_mut()
+            .find(|process| process.entry.pid == pid)
+            .expect("no such simulated process");
+        let end = start + bytes.len() as u64;
+        let index = process.mappings.partition_point(|mapping| mapping.region.start < start);
+        assert!(
+            process.mappings.get(index).is_none_or(|next| end <= next.region.start)
+                && (index == 0 || process.mappings[index - 1].region.end <= start),
+            "overlapping simulated mapping"
+        );
+
+        let permissions = permissions.bytes().fold(0, |bits, permission| {
+            bits | match permission {
+                b'r' => 1 << 0,
+                b'w' => 1 << 1,
+                b'x' => 1 << 2,
+                b's' => 1 << 3,
+                b'p' => 1 << 4,
+                _ => 0,
+            }
+        });
+        let region = Region {
+            start,
+            end,
+            size: end - start,
+            permissions,
+            offset: 0,
+            device: EncodedString::new("0:0".to_string()),
+            inode: 0,
+            pathname: EncodedString::from_bytes(pathname.to_vec()),
+        };
+        process.mappings.insert(
+            index,
+            Mapping {
+                region,
+                bytes: RefCell::new(bytes),
+            },
+        );
+    }
+
+    fn process(&self) -> io::Result<&SimulatedProcess> {
+        check_pid(self.pid)?;
+        self.processes
+            .iter()
+            .find(|process| process.entry.pid == self.pid)
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::ESRCH))
+    }
+
+    /// Hands `copy` the consecutive pieces of `[address, address + size)`
+    /// that lie in mappings with `permission`, as ranges of the mapping's
+    /// bytes and of the caller's buffer. Fails like the syscalls do if it
+    /// cannot get through the whole range.
+    fn transfer(
+        &self,
+        address: u64,
+        size: usize,
+        permission: u8,
+        partial: (io::ErrorKind, &str),
+        mut copy: impl FnMut(&Mapping, usize, std::ops::Range<usize>),
+    ) -> io::Result<()> {
+        let process = self.process()?;
+
+        let mut done = 0;
+        while done < size {
+            let Some(at) = address.checked_add(done as u64) else {
+                break;
+            };
+            let Some(mapping) = process
+                .mappings
+                .iter()
+                .find(|mapping| mapping.region.start <= at && at < mapping.region.end)
+                .filter(|mapping| mapping.region.permissions & permission != 0)
+            else {
+                break;
+            };
+            let offset = (at - mapping.region.start) as usize;
+            let count = (size - done).min(mapping.region.size as usize - offset);
+            copy(mapping, offset, done..done + count);
+            done += count;
+        }
+
+        match done {
+            0 if size > 0 => Err(io::Error::from_raw_os_error(libc::EFAULT)),
+            done if done < size => Err(io::Error::new(partial.0, partial.1)),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl MemoryBackend for SimulatedMemory {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn pid(&self) -> pid_t {
+        self.pid
+    }
+
+    fn set_pid(&mut self, pid: pid_t) {
+        self.pid = pid;
+    }
+
+    fn read(&self, address: u64, size: usize) -> io::Result<Vec<u8>> {
+        let mut result = vec![0; size];
+        let partial = (io::ErrorKind::UnexpectedEof, "Partial read occurred!");
+        self.transfer(address, size, READ, partial, |mapping, offset, range| {
+            let bytes = mapping.bytes.borrow();
+            result[range.clone()].copy_from_slice(&bytes[offset..offset + range.len()]);
+        })?;
+        Ok(result)
+    }
+
+    fn write(&self, address: u64, bytes: &[u8]) -> io::Result<usize> {
+        let partial = (io::ErrorKind::WriteZero, "Partial write occurred!");
+        self.transfer(address, bytes.len(), WRITE, partial, |mapping, offset, range| {
+            mapping.bytes.borrow_mut()[offset..offset + range.len()].copy_from_slice(&bytes[range]);
+        })?;
+        Ok(bytes.len())
+    }
+
+    fn regions(&self) -> io::Result<Vec<Region>> {
+        Ok(self.process()?.mappings.iter().map(|mapping| mapping.region.clone()).collect())
+    }
+
+    fn processes(&self) -> io::Result<Vec<ProcessEntry>> {
+        Ok(self.processes.iter().map(|process| process.entry.clone()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulated_memory_fails_like_the_syscalls() {
+        let mut memory = SimulatedMemory::new();
+        memory.add_process(7, b"target");
+        memory.map(7, 0x1000, "rw-p", b"[Heap]", vec![1; 0x1000]);
+        memory.map(7, 0x2000, "r--p", b"/lib/target.so", vec![2; 0x1000]);
+        assert_eq!(io::ErrorKind::NotConnected, memory.read(0x1000, 4).unwrap_err().kind());
+
+        memory.set_pid(7);
+        let mut expected = vec![1; 4];
+        expected.extend([2; 4]);
+        assert_eq!(expected, memory.read(0x1FFC, 8).unwrap());
+        assert_eq!(Some(libc::EFAULT), memory.read(0x3000, 4).unwrap_err().raw_os_error());
+        assert_eq!(io::ErrorKind::UnexpectedEof, memory.read(0x2FFC, 8).unwrap_err().kind());
+
+        assert_eq!(2, memory.write(0x1000, &[5, 6]).unwrap());
+        assert_eq!(vec![5, 6, 1], memory.read(0x1000, 3).unwrap());
+        assert_eq!(Some(libc::EFAULT), memory.write(0x2000, &[5]).unwrap_err().raw_os_error());
+        // Like process_vm_writev, the part before the read-only mapping is written.
+        assert_eq!(io::ErrorKind::WriteZero, memory.write(0x1FFF, &[7, 7]).unwrap_err().kind());
+        assert_eq!(vec![7], memory.read(0x1FFF, 1).unwrap());
+
+        let results = memory.read_many(&[(0x1000, 2), (0, 2)]).unwrap();
+        assert_eq!(vec![5, 6], *results[0].as_ref().unwrap());
+        assert_eq!(Some(libc::EFAULT), results[1].as_ref().unwrap_err().raw_os_error());
+        assert_eq!(2, memory.regions().unwrap().len());
+
+        memory.set_pid(8);
+        assert_eq!(Some(libc::ESRCH), memory.read_many(&[(0x1000, 2)]).unwrap_err().raw_os_error());
+        assert_eq!(1, memory.processes().unwrap().len());
+    }
+}
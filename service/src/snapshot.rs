@@ -0,0 +1,353 @@
+//! Named copies of the target's memory, and the ranges that changed between
+//! two of them or between one and the live memory.
+//!
+//! Snapshots are kept as lz4 compressed chunks. Once they grow past
+//! `SPILL_SIZE` the chunks move to a file in the data directory, which is
+//! deleted with the snapshot.
+
+use crate::memory::MemoryBackend;
+use crate::scan::for_each_chunk;
+use crate::storage;
+use shared::protocol::{ChangedRange, Region};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Error, ErrorKind, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Compressed bytes a snapshot keeps in memory before spilling to disk.
+const SPILL_SIZE: usize = 64 * 1024 * 1024;
+/// Longest `ChangedRange`, longer runs of changes are split.
+const MAX_RANGE_LENGTH: usize = 64 * 1024;
+/// Most old and new bytes a diff returns, over all of its ranges.
+const MAX_DIFF_SIZE: usize = 32 * 1024 * 1024;
+
+/// Numbers spill files, which are only ever read by the snapshot that wrote them.
+static SPILLS: AtomicU64 = AtomicU64::new(0);
+
+struct Chunk {
+    address: u64,
+    size: usize,
+    data: Vec<u8>,
+}
+
+impl Chunk {
+    fn bytes(&self) -> io::Result<Vec<u8>> {
+        lz4_flex::decompress(&self.data, self.size).map_err(|error| Error::new(ErrorKind::InvalidData, error))
+    }
+
+    /// `address` u64, `size` u32, compressed size u32, then the compressed
+    /// bytes, all little-endian.
+    fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&self.address.to_le_bytes())?;
+        writer.write_all(&(self.size as u32).to_le_bytes())?;
+        writer.write_all(&(self.data.len() as u32).to_le_bytes())?;
+        writer.write_all(&self.data)
+    }
+
+    fn read_from(reader: &mut impl BufRead) -> io::Result<Option<Self>> {
+        if reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        let mut header = [0; 16];
+        reader.read_exact(&mut header)?;
+        let mut data = vec![0; u32::from_le_bytes(header[12..].try_into().unwrap()) as usize];
+        reader.read_exact(&mut data)?;
+        Ok(Some(Self {
+            address: u64::from_le_bytes(header[..8].try_into().unwrap()),
+            size: u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize,
+            data,
+        }))
+    }
+}
+
+/// Addresses and decompressed bytes of a snapshot's chunks.
+type Chunks<'a> = Box<dyn Iterator<Item = io::Result<(u64, Vec<u8>)>> + 'a>;
+
+pub struct Snapshot {
+    /// The chunks, unless they were spilled.
+    chunks: Vec<Chunk>,
+    spill: Option<PathBuf>,
+    pub region_count: u32,
+    /// Bytes copied from the target.
+    pub size: u64,
+}
+
+impl Snapshot {
+    /// Copies the readable parts of `regions`, straight to disk with `to_disk`.
+    pub fn take(memory: &dyn MemoryBackend, regions: &[Region], to_disk: bool) -> io::Result<Self> {
+        let mut snapshot = Self {
+            chunks: Vec::new(),
+            spill: None,
+            region_count: regions.iter().filter(|region| region.permissions & 1 != 0).count() as u32,
+            size: 0,
+        };
+        let mut writer = None;
+        if to_disk {
+            writer = Some(snapshot.spill()?);
+        }
+        let mut stored = 0;
+        let mut result = Ok(());
+        for_each_chunk(memory, regions, 0, |address, data, _| {
+            let chunk = Chunk {
+                address,
+                size: data.len(),
+                data: lz4_flex::compress(data),
+            };
+            snapshot.size += data.len() as u64;
+            stored += chunk.data.len();
+            result = snapshot.push(&mut writer, chunk, stored > SPILL_SIZE);
+            result.is_ok()
+        });
+        result?;
+        if let Some(mut writer) = writer {
+            writer.flush()?;
+        }
+        Ok(snapshot)
+    }
+
+    /// Keeps `chunk` in memory, or in the spill file once there is one or
+    /// `spill` asks for it.
+    fn push(&mut self, writer: &mut Option<BufWriter<File>>, chunk: Chunk, spill: bool) -> io::Result<()> {
+        if writer.is_none() && spill {
+            *writer = Some(self.spill()?);
+        }
+        match writer {
+            Some(writer) => chunk.write_to(writer),
+            None => {
+                self.chunks.push(chunk);
+                Ok(())
+            }
+        }
+    }
+
+    /// Moves the chunks taken so far to a new file, returning it for the rest.
+    fn spill(&mut self) -> io::Result<BufWriter<File>> {
+        let name = format!("{}-{}", std::process::id(), SPILLS.fetch_add(1, Ordering::Relaxed));
+        let path = storage::path("snapshots", name.as_bytes())?;
+        let mut writer = BufWriter::new(storage::create(&path)?);
+        self.spill = Some(path);
+        for chunk in self.chunks.drain(..) {
+            chunk.write_to(&mut writer)?;
+        }
+        Ok(writer)
+    }
+
+    pub fn on_disk(&self) -> bool {
+        self.spill.is_some()
+    }
+
+    /// Calls `visit` with every chunk's address and bytes, in ascending order,
+    /// until it returns `false`.
+    fn for_each_chunk(&self, mut visit: impl FnMut(u64, &[u8]) -> bool) -> io::Result<()> {
+        match &self.spill {
+            Some(path) => {
+                let mut reader = BufReader::new(storage::open(path)?);
+                while let Some(chunk) = Chunk::read_from(&mut reader)? {
+                    if !visit(chunk.address, &chunk.bytes()?) {
+                        break;
+                    }
+                }
+            }
+            None => {
+                for chunk in &self.chunks {
+                    if !visit(chunk.address, &chunk.bytes()?) {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The chunks as an iterator, for walking two snapshots side by side.
+    fn chunks(&self) -> io::Result<Chunks<'_>> {
+        Ok(match &self.spill {
+            Some(path) => {
+                let mut reader = BufReader::new(storage::open(path)?);
+                Box::new(std::iter::from_fn(move || {
+                    Chunk::read_from(&mut reader)
+                        .transpose()
+                        .map(|chunk| chunk.and_then(|chunk| Ok((chunk.address, chunk.bytes()?))))
+                }))
+            }
+            None => Box::new(self.chunks.iter().map(|chunk| Ok((chunk.address, chunk.bytes()?)))),
+        })
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        if let Some(path) = &self.spill {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+pub struct Diff {
+    pub ranges: Vec<ChangedRange>,
+    pub truncated: bool,
+    /// Old and new bytes in `ranges`.
+    size: usize,
+}
+
+impl Diff {
+    fn new() -> Self {
+        Self {
+            ranges: Vec::new(),
+            truncated: false,
+            size: 0,
+        }
+    }
+
+    /// Adds the changes between the equally long `old` and `new` bytes at
+    /// `address`, returning `false` once `max_results` or `MAX_DIFF_SIZE` is
+    /// reached.
+    fn compare(&mut self, address: u64, old: &[u8], new: &[u8], max_results: usize) -> bool {
+        let mut offset = 0;
+        while let Some(start) = old[offset..].iter().zip(&new[offset..]).position(|(old, new)| old != new) {
+            let start = offset + start;
+            let length = old[start..]
+                .iter()
+                .zip(&new[start..])
+                .position(|(old, new)| old == new)
+                .unwrap_or(old.len() - start);
+            offset = start + length;
+            if !self.push(address + start as u64, &old[start..offset], &new[start..offset], max_results) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Appends a run of changes, continuing the last range if it ends at `address`.
+    fn push(&mut self, mut address: u64, mut old: &[u8], mut new: &[u8], max_results: usize) -> bool {
+        while !old.is_empty() {
+            let continues = self.ranges.last().is_some_and(|last| {
+                last.address + last.count as u64 == address && (last.count as usize) < MAX_RANGE_LENGTH
+            });
+            if !continues {
+                if self.ranges.len() >= max_results || self.size >= MAX_DIFF_SIZE {
+                    self.truncated = true;
+                    return false;
+                }
+                self.ranges.push(ChangedRange::new(address, Vec::new(), Vec::new()));
+            }
+
+            let last = self.ranges.last_mut().unwrap();
+            let length = (MAX_RANGE_LENGTH - last.count as usize).min(old.len());
+            last.old.extend_from_slice(&old[..length]);
+            last.new.extend_from_slice(&new[..length]);
+            last.count += length as u32;
+            self.size += length * 2;
+            address += length as u64;
+            old = &old[length..];
+            new = &new[length..];
+        }
+        true
+    }
+}
+
+/// The ranges that differ between `old` and `new` where both hold bytes.
+pub fn diff(old: &Snapshot, new: &Snapshot, max_results: usize) -> io::Result<Diff> {
+    let mut result = Diff::new();
+    let (mut old, mut new) = (old.chunks()?, new.chunks()?);
+    let (mut old_chunk, mut new_chunk) = (old.next().transpose()?, new.next().transpose()?);
+    while let (Some((old_address, old_bytes)), Some((new_address, new_bytes))) = (&old_chunk, &new_chunk) {
+        let old_end = old_address + old_bytes.len() as u64;
+        let new_end = new_address + new_bytes.len() as u64;
+        let (start, end) = ((*old_address).max(*new_address), old_end.min(new_end));
+        if start < end {
+            let old_bytes = &old_bytes[(start - old_address) as usize..(end - old_address) as usize];
+            let new_bytes = &new_bytes[(start - new_address) as usize..(end - new_address) as usize];
+            if !result.compare(start, old_bytes, new_bytes, max_results) {
+                break;
+            }
+        }
+
+        if old_end <= new_end {
+            old_chunk = old.next().transpose()?;
+        } else {
+            new_chunk = new.next().transpose()?;
+        }
+    }
+    Ok(result)
+}
+
+/// The ranges that differ between `old` and the target's memory. Chunks that
+/// cannot be read anymore are skipped.
+pub fn diff_live(old: &Snapshot, memory: &dyn MemoryBackend, max_results: usize) -> io::Result<Diff> {
+    let mut result = Diff::new();
+    old.for_each_chunk(|address, old_bytes| match memory.read(address, old_bytes.len()) {
+        Ok(new_bytes) => result.compare(address, old_bytes, &new_bytes, max_results),
+        Err(_) => true,
+    })?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Auto;
+    use shared::protocol::EncodedString;
+
+    fn region_of(data: &[u8]) -> Region {
+        let start = data.as_ptr() as u64;
+        Region {
+            start,
+            end: start + data.len() as u64,
+            size: data.len() as u64,
+            permissions: 1 | 2,
+            offset: 0,
+            device: EncodedString::new("0:0".to_string()),
+            inode: 0,
+            pathname: EncodedString::new("[Heap]".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_diff_snapshots_and_live_memory() {
+        let memory = Auto::new(std::process::id() as i32);
+        let mut data = vec![0u8; 3 * 1024 * 1024];
+        let base = data.as_ptr() as u64;
+        let regions = [region_of(&data)];
+
+        let before = Snapshot::take(&memory, &regions, false).unwrap();
+        assert!(!before.on_disk());
+        assert_eq!((1, data.len() as u64), (before.region_count, before.size));
+        let on_disk = Snapshot::take(&memory, &regions, true).unwrap();
+        assert!(on_disk.on_disk());
+
+        data[10] = 1;
+        data[11] = 2;
+        // Crosses the boundary between the first two chunks.
+        data[1024 * 1024 - 1] = 3;
+        data[1024 * 1024] = 4;
+        data[2 * 1024 * 1024..2 * 1024 * 1024 + MAX_RANGE_LENGTH + 1].fill(5);
+        let after = Snapshot::take(&memory, &regions, false).unwrap();
+
+        let expected = vec![
+            ChangedRange::new(base + 10, vec![0, 0], vec![1, 2]),
+            ChangedRange::new(base + 1024 * 1024 - 1, vec![0, 0], vec![3, 4]),
+            ChangedRange::new(base + 2 * 1024 * 1024, vec![0; MAX_RANGE_LENGTH], vec![5; MAX_RANGE_LENGTH]),
+            ChangedRange::new(base + 2 * 1024 * 1024 + MAX_RANGE_LENGTH as u64, vec![0], vec![5]),
+        ];
+        for diff in [
+            diff(&before, &after, 100).unwrap(),
+            diff(&on_disk, &after, 100).unwrap(),
+            diff_live(&before, &memory, 100).unwrap(),
+            diff_live(&on_disk, &memory, 100).unwrap(),
+        ] {
+            assert!(!diff.truncated);
+            assert_eq!(expected, diff.ranges);
+        }
+        assert!(diff(&after, &after, 100).unwrap().ranges.is_empty());
+
+        let diff = diff(&before, &after, 2).unwrap();
+        assert!(diff.truncated);
+        assert_eq!(&expected[..2], &diff.ranges[..]);
+
+        let path = on_disk.spill.clone().unwrap();
+        drop(on_disk);
+        assert!(!path.exists());
+    }
+}
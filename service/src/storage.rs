@@ -0,0 +1,118 @@
+//! Files the service keeps across sessions, like saved pointer scans.
+//!
+//! The service usually runs as root, so everything here stays in directories
+//! only its user can enter, and files are never opened through symlinks.
+
+use std::fs::{DirBuilder, File, OpenOptions, Permissions};
+use std::io::{self, Error, ErrorKind};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+/// `MEMWEB_DATA_DIR`, else the `StateDirectory=` systemd gave the service,
+/// else `memweb-data` in the working directory.
+pub fn data_dir() -> io::Result<PathBuf> {
+    if let Some(directory) = std::env::var_os("MEMWEB_DATA_DIR") {
+        return Ok(PathBuf::from(directory));
+    }
+    // systemd separates several state directories with colons.
+    if let Some(directory) = std::env::var("STATE_DIRECTORY")
+        .ok()
+        .and_then(|directories| directories.split(':').next().map(PathBuf::from))
+        .filter(|directory| !directory.as_os_str().is_empty())
+    {
+        return Ok(directory);
+    }
+    Ok(std::env::current_dir()?.join("memweb-data"))
+}
+
+/// Creates `directory` with mode 0700 if it is missing, and refuses it if it
+/// is a symlink, not a directory, or owned by another user. An existing one
+/// others can enter, like a `StateDirectory=` made with mode 0755, is
+/// restricted to mode 0700.
+fn private_dir(directory: &Path) -> io::Result<()> {
+    DirBuilder::new().recursive(true).mode(0o700).create(directory)?;
+    let metadata = std::fs::symlink_metadata(directory)?;
+    if !metadata.is_dir() || metadata.uid() != unsafe { libc::geteuid() } {
+        return Err(Error::new(
+            ErrorKind::PermissionDenied,
+            format!("Refusing to use '{}', it is not a directory owned by us", directory.display()),
+        ));
+    }
+    if metadata.mode() & 0o077 != 0 {
+        std::fs::set_permissions(directory, Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+/// The path of the file called `name` among those of `kind`, creating their
+/// directory. Names are limited to letters, digits, `-`, `_` and `.` so that
+/// clients cannot reach outside of it.
+pub fn path(kind: &str, name: &[u8]) -> io::Result<PathBuf> {
+    let valid = |byte: &u8| byte.is_ascii_alphanumeric() || b"-_.".contains(byte);
+    if name.is_empty() || name[0] == b'.' || !name.iter().all(valid) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid name '{}'", String::from_utf8_lossy(name)),
+        ));
+    }
+
+    let data_dir = data_dir()?;
+    private_dir(&data_dir)?;
+    let directory = data_dir.join(kind);
+    private_dir(&directory)?;
+    // Checked to be ASCII above.
+    Ok(directory.join(std::str::from_utf8(name).unwrap()))
+}
+
+/// Creates or truncates the file at `path`, readable only by us. Fails with
+/// `ELOOP` if `path` is a symlink.
+pub fn create(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)
+}
+
+/// Opens the file at `path` for reading. Fails with `ELOOP` if `path` is a
+/// symlink.
+pub fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new().read(true).custom_flags(libc::O_NOFOLLOW).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_refuses_symlinks() {
+        let directory = std::env::temp_dir().join(format!("memweb-storage-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+
+        private_dir(&directory.join("kind")).unwrap();
+        let mode = std::fs::metadata(directory.join("kind")).unwrap().mode();
+        assert_eq!(0o700, mode & 0o777);
+
+        let target = directory.join("target");
+        std::fs::write(&target, b"kept").unwrap();
+        let link = directory.join("kind").join("link");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        assert_eq!(Some(libc::ELOOP), create(&link).unwrap_err().raw_os_error());
+        assert_eq!(Some(libc::ELOOP), open(&link).unwrap_err().raw_os_error());
+        assert_eq!(b"kept", std::fs::read(&target).unwrap().as_slice());
+
+        let shared = directory.join("shared");
+        std::fs::create_dir(&shared).unwrap();
+        std::fs::set_permissions(&shared, Permissions::from_mode(0o755)).unwrap();
+        private_dir(&shared).unwrap();
+        assert_eq!(0o700, std::fs::metadata(&shared).unwrap().mode() & 0o777);
+
+        let linked_dir = directory.join("linked");
+        std::os::unix::fs::symlink(directory.join("kind"), &linked_dir).unwrap();
+        assert_eq!(ErrorKind::PermissionDenied, private_dir(&linked_dir).unwrap_err().kind());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
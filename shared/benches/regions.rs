@@ -0,0 +1,55 @@
+//! Encoding and decoding of region lists the size of a large game's maps.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use shared::protocol::*;
+
+fn regions(count: usize) -> Vec<Region> {
+    (0..count as u64)
+        .map(|index| Region {
+            start: 0x7f0000000000 + index * 0x2000,
+            end: 0x7f0000001000 + index * 0x2000,
+            size: 0x1000,
+            permissions: 5,
+            offset: index * 0x1000,
+            device: EncodedString::new("103:2".to_string()),
+            inode: 1234567 + index,
+            pathname: EncodedString::new(format!("/usr/lib/x86_64-linux-gnu/libgame_plugin_{}.so", index)),
+        })
+        .collect()
+}
+
+fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode regions");
+    for count in [1_000, 10_000] {
+        let regions = regions(count);
+        group.bench_with_input(BenchmarkId::new("out_bytes", count), &regions, |b, regions| {
+            b.iter_batched(
+                || regions.clone(),
+                |regions| S2CTargetPidRegionsPacket::out_bytes(42, regions),
+                criterion::BatchSize::LargeInput,
+            )
+        });
+        let mut buffer = Vec::new();
+        group.bench_with_input(BenchmarkId::new("out_bytes_into", count), &regions, |b, regions| {
+            b.iter(|| S2CTargetPidRegionsPacket::out_bytes_into(42, regions, &mut buffer))
+        });
+    }
+    group.finish();
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode regions");
+    for count in [1_000, 10_000] {
+        let data = S2CTargetPidRegionsPacket::out_bytes(42, regions(count));
+        group.bench_with_input(BenchmarkId::new("owned", count), &data, |b, data| {
+            b.iter(|| S2CTargetPidRegionsPacket::parse(data).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("borrowed", count), &data, |b, data| {
+            b.iter(|| S2CTargetPidRegionsView::parse(data).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, encode, decode);
+criterion_main!(benches);
@@ -0,0 +1,420 @@
+//! Addresses written symbolically, so they survive the target restarting:
+//!
+//! * `0x1A2B30` or `1715` is an absolute address or offset.
+//! * `libgame.so` is the base of the module with that file name or pathname.
+//!   Names with other characters than letters, digits, `_`, `.`, `/` and `@`
+//!   are quoted, as in `"ld-linux-x86-64.so.2"`.
+//! * `$heap` is the start of a special mapping like `[Heap]` or `[Stack]`.
+//! * `[expression]` is the pointer stored at an address.
+//! * Terms are added and subtracted from left to right, parentheses group them.
+//!
+//! So `[[libgame.so+0x1A2B30]+0x18]+0x40` follows two pointers from a module.
+
+use crate::protocol::Region;
+use std::fmt;
+
+/// Size of the pointers `[...]` reads, in little-endian order.
+const POINTER_SIZE: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(u64),
+    Module(String),
+    Mapping(String),
+    Dereference(Box<Expression>),
+    Add(Box<Expression>, Box<Expression>),
+    Subtract(Box<Expression>, Box<Expression>),
+}
+
+/// Where the reads of `Expression::resolve` go, the target's memory.
+pub trait MemoryReader {
+    fn read(&self, address: u64, size: usize) -> std::io::Result<Vec<u8>>;
+}
+
+#[derive(Debug)]
+pub enum ExpressionError {
+    /// `position` is the byte offset into the text.
+    Syntax { position: usize, message: String },
+    UnknownModule(String),
+    UnknownMapping(String),
+    Read { address: u64, error: std::io::Error },
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax { position, message } => write!(f, "{} at position {}", message, position),
+            Self::UnknownModule(name) => write!(f, "No module named '{}'", name),
+            Self::UnknownMapping(name) => write!(f, "No mapping named '${}'", name),
+            Self::Read { address, error } => write!(f, "Could not read the pointer at {:#x}: {}", address, error),
+        }
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_./@".contains(c)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, ExpressionError> {
+        Err(ExpressionError::Syntax {
+            position: self.position,
+            message: message.into(),
+        })
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        let rest = &self.text[self.position..];
+        self.position += rest.len() - rest.trim_start().len();
+        self.text[self.position..].chars().next()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ExpressionError> {
+        if self.peek() != Some(expected) {
+            return self.error(format!("Expected '{}'", expected));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn name(&mut self) -> &'a str {
+        let start = self.position;
+        let rest = &self.text[start..];
+        self.position += rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+        &self.text[start..self.position]
+    }
+
+    fn quoted(&mut self) -> Result<String, ExpressionError> {
+        let start = self.position;
+        self.position += 1;
+        let mut name = String::new();
+        let mut chars = self.text[self.position..].chars();
+        while let Some(c) = chars.next() {
+            self.position += c.len_utf8();
+            match c {
+                '"' => return Ok(name),
+                '\\' => match chars.next() {
+                    Some(c) => {
+                        self.position += c.len_utf8();
+                        name.push(c);
+                    }
+                    None => break,
+                },
+                c => name.push(c),
+            }
+        }
+        self.position = start;
+        self.error("Unterminated quote")
+    }
+
+    fn sum(&mut self) -> Result<Expression, ExpressionError> {
+        let mut expression = self.term()?;
+        loop {
+            match self.peek() {
+                Some('+') => {
+                    self.position += 1;
+                    expression = Expression::Add(Box::new(expression), Box::new(self.term()?));
+                }
+                Some('-') => {
+                    self.position += 1;
+                    expression = Expression::Subtract(Box::new(expression), Box::new(self.term()?));
+                }
+                _ => return Ok(expression),
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<Expression, ExpressionError> {
+        match self.peek() {
+            Some('[') => {
+                self.position += 1;
+                let inner = self.sum()?;
+                self.expect(']')?;
+                Ok(Expression::Dereference(Box::new(inner)))
+            }
+            Some('(') => {
+                self.position += 1;
+                let inner = self.sum()?;
+                self.expect(')')?;
+                Ok(inner)
+            }
+            Some('$') => {
+                self.position += 1;
+                match self.name() {
+                    "" => self.error("Expected a mapping name"),
+                    name => Ok(Expression::Mapping(name.to_string())),
+                }
+            }
+            Some('"') => match self.quoted()? {
+                name if name.is_empty() => self.error("Expected a module name"),
+                name => Ok(Expression::Module(name)),
+            },
+            Some(c) if is_name_char(c) => {
+                let start = self.position;
+                let name = self.name();
+                match parse_number(name) {
+                    Some(number) => Ok(Expression::Number(number)),
+                    None if name.starts_with(|c: char| c.is_ascii_digit()) => {
+                        self.position = start;
+                        self.error(format!("Invalid number '{}'", name))
+                    }
+                    None => Ok(Expression::Module(name.to_string())),
+                }
+            }
+            Some(c) => self.error(format!("Unexpected '{}'", c)),
+            None => self.error("Unexpected end of expression"),
+        }
+    }
+}
+
+fn parse_number(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// The lowest start of the regions `matches` picks.
+fn base<'a>(regions: &'a [Region], matches: impl Fn(&'a [u8]) -> bool) -> Option<u64> {
+    regions
+        .iter()
+        .filter(|region| matches(region.pathname.as_bytes()))
+        .map(|region| region.start)
+        .min()
+}
+
+impl Expression {
+    pub fn parse(text: &str) -> Result<Self, ExpressionError> {
+        let mut parser = Parser { text, position: 0 };
+        let expression = parser.sum()?;
+        match parser.peek() {
+            None => Ok(expression),
+            Some(c) => parser.error(format!("Unexpected '{}'", c)),
+        }
+    }
+
+    /// Whether the value is fixed for a run of the target, as no pointers are read.
+    pub fn is_static(&self) -> bool {
+        match self {
+            Self::Number(_) | Self::Module(_) | Self::Mapping(_) => true,
+            Self::Dereference(_) => false,
+            Self::Add(left, right) | Self::Subtract(left, right) => left.is_static() && right.is_static(),
+        }
+    }
+
+    /// Replaces module and mapping names with their bases, so that resolving
+    /// the result again, say on every tick, needs no region list.
+    pub fn bind(&self, regions: &[Region]) -> Result<Self, ExpressionError> {
+        /// Never asked to read, as `Dereference` is kept as is.
+        struct NoMemory;
+
+        impl MemoryReader for NoMemory {
+            fn read(&self, _: u64, _: usize) -> std::io::Result<Vec<u8>> {
+                Err(std::io::ErrorKind::Unsupported.into())
+            }
+        }
+
+        Ok(match self {
+            Self::Number(_) | Self::Module(_) | Self::Mapping(_) => Self::Number(self.resolve(regions, &NoMemory)?),
+            Self::Dereference(inner) => Self::Dereference(Box::new(inner.bind(regions)?)),
+            Self::Add(left, right) => Self::Add(Box::new(left.bind(regions)?), Box::new(right.bind(regions)?)),
+            Self::Subtract(left, right) => Self::Subtract(Box::new(left.bind(regions)?), Box::new(right.bind(regions)?)),
+        })
+    }
+
+    /// The address this stands for in a target with `regions`. Arithmetic
+    /// wraps around like the target's own pointer arithmetic would.
+    pub fn resolve(&self, regions: &[Region], memory: &(impl MemoryReader + ?Sized)) -> Result<u64, ExpressionError> {
+        match self {
+            Self::Number(number) => Ok(*number),
+            Self::Module(name) => {
+                let name = name.as_bytes();
+                base(regions, |pathname| {
+                    pathname.starts_with(b"/")
+                        && (pathname == name || pathname.rsplit(|&byte| byte == b'/').next() == Some(name))
+                })
+                .ok_or_else(|| ExpressionError::UnknownModule(String::from_utf8_lossy(name).into_owned()))
+            }
+            Self::Mapping(name) => {
+                // Special mappings are named `[Heap]`, `[Stack]` and so on.
+                base(regions, |pathname| {
+                    pathname.len() == name.len() + 2
+                        && pathname.starts_with(b"[")
+                        && pathname.ends_with(b"]")
+                        && pathname[1..pathname.len() - 1].eq_ignore_ascii_case(name.as_bytes())
+                })
+                .ok_or_else(|| ExpressionError::UnknownMapping(name.clone()))
+            }
+            Self::Dereference(inner) => {
+                let address = inner.resolve(regions, memory)?;
+                let pointer = memory
+                    .read(address, POINTER_SIZE)
+                    .map_err(|error| ExpressionError::Read { address, error })?;
+                match pointer.try_into() {
+                    Ok(pointer) => Ok(u64::from_le_bytes(pointer)),
+                    Err(_) => Err(ExpressionError::Read {
+                        address,
+                        error: std::io::ErrorKind::UnexpectedEof.into(),
+                    }),
+                }
+            }
+            Self::Add(left, right) => Ok(left.resolve(regions, memory)?.wrapping_add(right.resolve(regions, memory)?)),
+            Self::Subtract(left, right) => {
+                Ok(left.resolve(regions, memory)?.wrapping_sub(right.resolve(regions, memory)?))
+            }
+        }
+    }
+}
+
+/// Prints the canonical form, which parses back to the same expression.
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(number) => write!(f, "{:#X}", number),
+            // Unquoted names starting with a digit are taken for numbers.
+            Self::Module(name) if name.chars().all(is_name_char) && name.starts_with(|c: char| !c.is_ascii_digit()) => {
+                write!(f, "{}", name)
+            }
+            Self::Module(name) => {
+                write!(f, "\"")?;
+                for c in name.chars() {
+                    if c == '"' || c == '\\' {
+                        write!(f, "\\")?;
+                    }
+                    write!(f, "{}", c)?;
+                }
+                write!(f, "\"")
+            }
+            Self::Mapping(name) => write!(f, "${}", name),
+            Self::Dereference(inner) => write!(f, "[{}]", inner),
+            Self::Add(left, right) => write!(f, "{}+{}", left, Operand(right)),
+            Self::Subtract(left, right) => write!(f, "{}-{}", left, Operand(right)),
+        }
+    }
+}
+
+/// The right side of a sum, grouped if it is a sum itself.
+struct Operand<'a>(&'a Expression);
+
+impl fmt::Display for Operand<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Expression::Add(..) | Expression::Subtract(..) => write!(f, "({})", self.0),
+            expression => write!(f, "{}", expression),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::EncodedString;
+    use proptest::prelude::*;
+    use std::collections::HashMap;
+    use std::io::{Error, ErrorKind};
+
+    fn region(start: u64, end: u64, pathname: &str) -> Region {
+        Region {
+            start,
+            end,
+            size: end - start,
+            permissions: 1,
+            offset: 0,
+            device: EncodedString::new("0:0".to_string()),
+            inode: 0,
+            pathname: EncodedString::new(pathname.to_string()),
+        }
+    }
+
+    struct Pointers(HashMap<u64, u64>);
+
+    impl MemoryReader for Pointers {
+        fn read(&self, address: u64, size: usize) -> std::io::Result<Vec<u8>> {
+            let pointer = self.0.get(&address).ok_or_else(|| Error::from(ErrorKind::InvalidInput))?;
+            Ok(pointer.to_le_bytes()[..size].to_vec())
+        }
+    }
+
+    #[test]
+    fn test_parse_and_print() {
+        let expression = Expression::parse("[[libgame.so+0x1A2B30]+0x18]+0x40").unwrap();
+        let module = Expression::Add(
+            Box::new(Expression::Module("libgame.so".to_string())),
+            Box::new(Expression::Number(0x1A2B30)),
+        );
+        let inner = Expression::Add(
+            Box::new(Expression::Dereference(Box::new(module))),
+            Box::new(Expression::Number(0x18)),
+        );
+        let expected = Expression::Add(
+            Box::new(Expression::Dereference(Box::new(inner))),
+            Box::new(Expression::Number(0x40)),
+        );
+        assert_eq!(expected, expression);
+        assert_eq!("[[libgame.so+0x1A2B30]+0x18]+0x40", expression.to_string());
+        assert!(!expression.is_static());
+
+        let expression = Expression::parse(" $heap + 256 - (0x10 - 8) ").unwrap();
+        assert_eq!("$heap+0x100-(0x10-0x8)", expression.to_string());
+        assert!(expression.is_static());
+
+        let expression = Expression::parse(r#""ld-linux \"x86\".so"+0x8"#).unwrap();
+        assert_eq!(r#""ld-linux \"x86\".so"+0x8"#, expression.to_string());
+        assert_eq!(expression, Expression::parse(&expression.to_string()).unwrap());
+
+        for (text, position) in [("", 0), ("[0x10", 5), ("0x10+", 5), ("12ab", 0), ("$", 1), ("\"lib", 0), ("1 2", 2)] {
+            match Expression::parse(text) {
+                Err(ExpressionError::Syntax { position: found, .. }) => assert_eq!(position, found, "{}", text),
+                result => panic!("{} parsed to {:?}", text, result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let regions = vec![
+            region(0x1000, 0x2000, "/usr/lib/libgame.so"),
+            region(0x800, 0x1000, "/usr/lib/libgame.so"),
+            region(0x2000, 0x3000, "[Anonymous]"),
+            region(0x10000, 0x20000, "[Heap]"),
+        ];
+        let memory = Pointers(HashMap::from([(0x800 + 0x30, 0x10000), (0x10018, 0x10100)]));
+
+        let resolve = |text: &str| Expression::parse(text).unwrap().resolve(&regions, &memory);
+        assert_eq!(0x10140, resolve("[[libgame.so+0x30]+0x18]+0x40").unwrap());
+        assert_eq!(0x830, resolve("/usr/lib/libgame.so+0x30").unwrap());
+        assert_eq!(0x10100, resolve("$HEAP+0x100").unwrap());
+        assert_eq!(u64::MAX, resolve("0-1").unwrap());
+
+        assert!(matches!(resolve("libother.so"), Err(ExpressionError::UnknownModule(_))));
+        assert!(matches!(resolve("lib"), Err(ExpressionError::UnknownModule(_))));
+        assert!(matches!(resolve("$stack"), Err(ExpressionError::UnknownMapping(_))));
+        assert!(matches!(resolve("[[libgame.so]]"), Err(ExpressionError::Read { address: 0x800, .. })));
+
+        let bound = Expression::parse("[[libgame.so+0x30]+0x18]+0x40").unwrap().bind(&regions).unwrap();
+        assert_eq!("[[0x800+0x30]+0x18]+0x40", bound.to_string());
+        assert_eq!(0x10140, bound.resolve(&[], &memory).unwrap());
+        assert!(Expression::parse("[$stack]").unwrap().bind(&regions).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_text_never_panics(text in ".{0,32}") {
+            let _ = Expression::parse(&text);
+        }
+
+        #[test]
+        fn printed_expressions_parse_back(text in r#"[\[\]()$+\- 0-9a-fxl."\\]{0,24}"#) {
+            if let Ok(expression) = Expression::parse(&text) {
+                prop_assert_eq!(&expression, &Expression::parse(&expression.to_string()).unwrap());
+            }
+        }
+    }
+}
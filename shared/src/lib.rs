@@ -1,4 +1,8 @@
+pub mod expression;
 pub mod protocol;
 
 #[cfg(not(target_arch = "wasm32"))]
-pub mod process;
\ No newline at end of file
+pub mod process;
+
+#[cfg(test)]
+mod test;
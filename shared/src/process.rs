@@ -1,54 +1,195 @@
-use procfs::{*, process::{Process, MMapPath}};
+use procfs::{*, process::Process};
 
 
 use crate::protocol::{Region, ProcessEntry, EncodedString};
 
+/// Keeps the kind of procfs failures so callers can tell a vanished process
+/// from a permission problem.
+fn proc_error(error: ProcError) -> std::io::Error {
+    match error {
+        ProcError::PermissionDenied(_) => std::io::Error::new(std::io::ErrorKind::PermissionDenied, error),
+        ProcError::NotFound(_) => std::io::Error::new(std::io::ErrorKind::NotFound, error),
+        ProcError::Io(error, _) => error,
+        _ => std::io::Error::other(error),
+    }
+}
+
+/// Names the special mappings like procfs' `MMapPath` does. Paths are kept
+/// as the raw bytes the kernel reports, they need not be UTF-8.
+fn pathname_bytes(pathname: &[u8]) -> Vec<u8> {
+    let special = match pathname {
+        b"" => Some("[Anonymous]".to_string()),
+        b"[heap]" => Some("[Heap]".to_string()),
+        b"[stack]" => Some("[Stack]".to_string()),
+        b"[vdso]" => Some("[Vdso]".to_string()),
+        b"[vvar]" => Some("[Vvar]".to_string()),
+        b"[vsyscall]" => Some("[Vsyscall]".to_string()),
+        b"[rollup]" => Some("[Rollup]".to_string()),
+        [b'[', name @ .., b']'] => match name.strip_prefix(b"stack:") {
+            Some(tid) => Some(format!("TStack: {}", String::from_utf8_lossy(tid))),
+            None => return name.to_vec(),
+        },
+        [b'/', b'S', b'Y', b'S', b'V', key @ ..] if key.len() >= 8 => std::str::from_utf8(&key[..8])
+            .ok()
+            .and_then(|key| u32::from_str_radix(key, 16).ok())
+            .map(|key| format!("Vsys: {}", key as i32)),
+        _ => None,
+    };
+    special.map_or_else(|| pathname.to_vec(), String::into_bytes)
+}
+
+fn parse_hex(field: &[u8]) -> Option<u64> {
+    u64::from_str_radix(std::str::from_utf8(field).ok()?, 16).ok()
+}
+
+/// Parses a line of /proc/pid/maps as raw bytes, unlike procfs which
+/// rejects paths that are not UTF-8:
+/// `start-end perms offset major:minor inode pathname`.
+fn parse_map(line: &[u8]) -> Option<Region> {
+    let mut fields = line.splitn(6, |&byte| byte == b' ');
+    let range = fields.next()?;
+    let dash = range.iter().position(|&byte| byte == b'-')?;
+    let (start, end) = (parse_hex(&range[..dash])?, parse_hex(&range[dash + 1..])?);
+    let permissions = fields.next()?.iter().fold(0, |bits, permission| {
+        bits | match permission {
+            b'r' => 1 << 0,
+            b'w' => 1 << 1,
+            b'x' => 1 << 2,
+            b's' => 1 << 3,
+            b'p' => 1 << 4,
+            _ => 0,
+        }
+    });
+    let offset = parse_hex(fields.next()?)?;
+    let mut device = fields.next()?.split(|&byte| byte == b':');
+    let (major, minor) = (parse_hex(device.next()?)?, parse_hex(device.next()?)?);
+    let inode = std::str::from_utf8(fields.next()?).ok()?.parse().ok()?;
+    let pathname = fields.next().unwrap_or_default();
+    let pathname = &pathname[pathname.iter().take_while(|&&byte| byte == b' ').count()..];
+
+    Some(Region {
+        start,
+        end,
+        size: end - start,
+        permissions,
+        offset,
+        device: EncodedString::new(format!("{}:{}", major, minor)),
+        inode,
+        pathname: EncodedString::from_bytes(pathname_bytes(pathname)),
+    })
+}
+
 pub fn get_regions(pid: i32) -> std::io::Result<Vec<Region>> {
-    let process = Process::new(pid).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-    let maps = process.maps().map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-
-    Ok(maps.iter()
-        .map(|map_range| Region {
-            start: map_range.address.0,
-            end: map_range.address.1,
-            size: map_range.address.1 - map_range.address.0,
-            permissions: map_range.perms.bits(),
-            offset: map_range.offset,
-            device: EncodedString::new(map_range.dev.0.to_string() + ":" + &map_range.dev.1.to_string()),
-            inode: map_range.inode,
-            pathname: EncodedString::new(match &map_range.pathname {
-                MMapPath::Path(path) => path.to_str().unwrap().to_string(),
-                MMapPath::Other(s) => s.clone(),
-                MMapPath::Heap => "[Heap]".into(),
-                MMapPath::Stack => "[Stack]".into(),
-                MMapPath::TStack(tid) => format!("TStack: {}", tid),
-                MMapPath::Vdso => "[Vdso]".into(),
-                MMapPath::Vvar => "[Vvar]".into(),
-                MMapPath::Vsyscall => "[Vsyscall]".into(),
-                MMapPath::Rollup => "[Rollup]".into(),
-                MMapPath::Anonymous => "[Anonymous]".into(),
-                MMapPath::Vsys(shared_mem_seg) => format!("Vsys: {}", shared_mem_seg),
+    // Goes through procfs first so a missing process or permission problem
+    // is reported the same way as for the process list.
+    Process::new(pid).map_err(proc_error)?;
+    parse_maps(&std::fs::read(format!("/proc/{}/maps", pid))?)
+}
+
+fn parse_maps(maps: &[u8]) -> std::io::Result<Vec<Region>> {
+    maps.split(|&byte| byte == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            parse_map(line).ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Malformed maps line: {}", String::from_utf8_lossy(line)),
+                )
             })
         })
-        .collect())
+        .collect()
 }
 
-fn create_process_entry(proc: Result<Process, ProcError>) -> ProcessEntry {
-    let p = proc.unwrap();
-    let mut name = p.cmdline().unwrap().join(" ");
-    if name.is_empty() {
-        name = p.stat().unwrap().comm;
+/// The command line with its arguments joined by spaces, or the comm name for
+/// kernel threads and zombies. Both are read as raw bytes, procfs would
+/// insist on UTF-8. `None` if the process is gone.
+fn process_name(pid: i32) -> Option<Vec<u8>> {
+    let cmdline = std::fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    let arguments: Vec<&[u8]> = cmdline
+        .split(|&byte| byte == 0)
+        .filter(|argument| !argument.is_empty())
+        .collect();
+    if !arguments.is_empty() {
+        return Some(arguments.join(&b' '));
     }
 
-    ProcessEntry {
-        pid: p.pid(),
-        name: EncodedString::new(name),
+    let mut comm = std::fs::read(format!("/proc/{}/comm", pid)).ok()?;
+    if comm.last() == Some(&b'\n') {
+        comm.pop();
     }
+    Some(comm)
 }
 
 pub fn get_running_processes() -> std::io::Result<Vec<ProcessEntry>> {
-    let procs = process::all_processes().unwrap();
+    let procs = process::all_processes().map_err(proc_error)?;
     Ok(procs
-        .map(create_process_entry)
+        // Processes may exit while we list them, skip those.
+        .filter_map(|proc| {
+            let pid = proc.ok()?.pid();
+            Some(ProcessEntry {
+                pid,
+                name: EncodedString::from_bytes(process_name(pid)?),
+            })
+        })
         .collect())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::process::{Child, Command};
+
+    /// Runs a copy of `sleep` whose path is not valid UTF-8.
+    fn spawn_non_utf8_sleep() -> (Child, Vec<u8>) {
+        let mut path = std::env::temp_dir().into_os_string().into_vec();
+        path.extend(format!("/memweb-{}-", std::process::id()).into_bytes());
+        path.extend(b"\xff\xfe-sleep");
+        std::fs::copy("/bin/sleep", OsStr::from_bytes(&path)).unwrap();
+
+        let child = Command::new(OsStr::from_bytes(&path)).arg("5").spawn().unwrap();
+        (child, path)
+    }
+
+    #[test]
+    fn test_regions_match_procfs() {
+        let snapshot = std::fs::read("/proc/self/maps").unwrap();
+        let maps = process::MemoryMaps::from_reader(&snapshot[..]).unwrap();
+        let regions = parse_maps(&snapshot).unwrap();
+
+        assert_eq!(maps.memory_maps.len(), regions.len());
+        for (map, region) in maps.iter().zip(&regions) {
+            assert_eq!((map.address.0, map.address.1), (region.start, region.end));
+            assert_eq!(map.perms.bits(), region.permissions);
+            assert_eq!(map.offset, region.offset);
+            assert_eq!(format!("{}:{}", map.dev.0, map.dev.1), region.device.to_string_lossy());
+            assert_eq!(map.inode, region.inode);
+        }
+        assert!(regions.iter().any(|region| region.pathname.as_bytes() == b"[Stack]"));
+    }
+
+    #[test]
+    fn test_non_utf8_names_round_trip() {
+        let (mut child, path) = spawn_non_utf8_sleep();
+        // Wait for the child to exec the copy before its maps are read.
+        let exe = format!("/proc/{}/exe", child.id());
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while std::fs::read_link(&exe).map(|exe| exe.into_os_string().into_vec()).ok() != Some(path.clone()) {
+            assert!(std::time::Instant::now() < deadline, "the child never ran the copied binary");
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        let regions = get_regions(child.id() as i32);
+        let processes = get_running_processes();
+        child.kill().unwrap();
+        child.wait().unwrap();
+        std::fs::remove_file(OsStr::from_bytes(&path)).unwrap();
+
+        assert!(regions.unwrap().iter().any(|region| region.pathname.as_bytes() == path));
+        let processes = processes.unwrap();
+        let entry = processes.iter().find(|entry| entry.pid == child.id() as i32).unwrap();
+        assert_eq!([&path[..], b" 5"].concat(), entry.name.as_bytes());
+        assert!(entry.name.to_string_lossy().contains('\u{FFFD}'));
+    }
+}
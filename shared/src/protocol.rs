@@ -1,11 +1,227 @@
 #![allow(non_snake_case)]
-use std::vec;
+#![allow(clippy::manual_div_ceil)] // emitted by the deku derives
 
+use deku::bitvec::{BitSlice, Msb0};
 use deku::prelude::*;
+use std::borrow::Cow;
 
 type Pid = i32;
 
-#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+/// Revision of the wire format, bumped on every incompatible change.
+/// The leading `_type`, `request_id` and `protocol_version` of `C2SHelloPacket`
+/// and the whole `S2CErrorPacket` must never change so that mismatching
+/// clients can always be told apart and refused.
+pub const PROTOCOL_VERSION: u16 = 3;
+
+/// Bits of `S2CServerInfoPacket::capabilities`.
+pub mod capabilities {
+    pub const READ: u64 = 1 << 0;
+    pub const WRITE: u64 = 1 << 1;
+    pub const PROCESS_LIST: u64 = 1 << 2;
+    pub const REGIONS: u64 = 1 << 3;
+    pub const READ_MANY: u64 = 1 << 4;
+    pub const WRITE_MANY: u64 = 1 << 5;
+    pub const READ_TOLERANT: u64 = 1 << 6;
+    pub const COMPRESSION: u64 = 1 << 7;
+    pub const READ_STREAM: u64 = 1 << 8;
+    pub const SCAN: u64 = 1 << 9;
+    pub const PATTERN_SCAN: u64 = 1 << 10;
+    pub const STRING_SEARCH: u64 = 1 << 11;
+    pub const REGEX_SEARCH: u64 = 1 << 12;
+    pub const POINTER_SCAN: u64 = 1 << 13;
+    pub const RESOLVE_POINTER_CHAIN: u64 = 1 << 14;
+    pub const RESOLVE_EXPRESSION: u64 = 1 << 15;
+    pub const FREEZE: u64 = 1 << 16;
+    pub const WATCH: u64 = 1 << 17;
+    pub const SNAPSHOTS: u64 = 1 << 18;
+    pub const SELECT_BACKEND: u64 = 1 << 19;
+}
+
+/// Smallest encoding of an `EncodedString`: just the length.
+const ENCODED_STRING_MIN_SIZE: usize = 4;
+/// Encoding of a `ReadRange`.
+const READ_RANGE_SIZE: usize = 8 + 4;
+/// Smallest encoding of a `ReadManyResult`: a failure without data.
+const READ_MANY_RESULT_MIN_SIZE: usize = 1 + 4 + 4;
+/// Smallest encoding of a `WriteEntry`: an address without bytes.
+const WRITE_ENTRY_MIN_SIZE: usize = 8 + 4;
+/// Encoding of a `WriteManyResult`.
+const WRITE_MANY_RESULT_SIZE: usize = 1 + 4 + 4;
+/// Smallest encoding of a `ScanResult`: an address without a value.
+const SCAN_RESULT_MIN_SIZE: usize = 8 + 4;
+/// Encoding of a `PatternMatch`.
+const PATTERN_MATCH_SIZE: usize = 8 + 8;
+/// Smallest encoding of a `StringHit`: a hit without context.
+const STRING_HIT_MIN_SIZE: usize = 8 + 4 + 1 + 8 + 4;
+/// Smallest encoding of a `RegexMatch`: an address without bytes.
+const REGEX_MATCH_MIN_SIZE: usize = 8 + 4;
+/// Smallest encoding of a `PointerPath`: an empty module name and no offsets.
+const POINTER_PATH_MIN_SIZE: usize = 4 + 8 + 4;
+/// Smallest encoding of a `WatchTarget`: an empty expression and the size.
+const WATCH_TARGET_MIN_SIZE: usize = 4 + 4;
+/// Smallest encoding of a `WatchValue`: a failed read.
+const WATCH_VALUE_MIN_SIZE: usize = 4 + 8 + READ_MANY_RESULT_MIN_SIZE;
+/// Smallest encoding of a `ChangedRange`: an address without bytes.
+const CHANGED_RANGE_MIN_SIZE: usize = 8 + 4;
+/// Smallest encoding of a `ProcessEntry`: an empty name and the pid.
+const PROCESS_ENTRY_MIN_SIZE: usize = 4 + 4;
+/// Smallest encoding of a `Region`: the integer fields and two empty strings.
+const REGION_MIN_SIZE: usize = 8 * 5 + 1 + 4 * 2;
+
+/// Why a frame could not be decoded.
+#[derive(Debug, PartialEq)]
+pub enum ProtocolError {
+    Empty,
+    UnknownPacketType(u8),
+    UnexpectedPacketType { expected: PacketType, found: u8 },
+    TrailingBytes(usize),
+    Malformed(DekuError),
+    Decompression(String),
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "Empty packet"),
+            Self::UnknownPacketType(value) => write!(f, "Unknown packet type {}", value),
+            Self::UnexpectedPacketType { expected, found } => {
+                write!(f, "Expected packet type {:?}, found {}", expected, found)
+            }
+            Self::TrailingBytes(count) => write!(f, "{} trailing bytes after packet", count),
+            Self::Malformed(error) => write!(f, "Malformed packet: {}", error),
+            Self::Decompression(error) => write!(f, "Could not decompress payload: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<DekuError> for ProtocolError {
+    fn from(error: DekuError) -> Self {
+        Self::Malformed(error)
+    }
+}
+
+impl ProtocolError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::UnknownPacketType(_) => ErrorCode::UnknownPacketType,
+            _ => ErrorCode::MalformedPacket,
+        }
+    }
+}
+
+/// Length prefixes are checked against what is left of the frame before
+/// anything gets allocated for them.
+fn checked_count(count: u32, element_size: usize, rest: &BitSlice<u8, Msb0>) -> Result<u32, DekuError> {
+    if (count as usize).saturating_mul(element_size) > rest.len() / 8 {
+        return Err(DekuError::Parse(format!(
+            "count {} exceeds the {} remaining bytes",
+            count,
+            rest.len() / 8
+        )));
+    }
+    Ok(count)
+}
+
+fn check_packet_type(data: &[u8], expected: PacketType) -> Result<(), ProtocolError> {
+    match data.first() {
+        None => Err(ProtocolError::Empty),
+        Some(&found) if PacketType::from_u8(found).is_none() => Err(ProtocolError::UnknownPacketType(found)),
+        Some(&found) if found != expected as u8 => Err(ProtocolError::UnexpectedPacketType { expected, found }),
+        _ => Ok(()),
+    }
+}
+
+/// Decodes a whole frame as `T`, which has to start with `expected`.
+fn decode<'a, T: DekuContainerRead<'a>>(data: &'a [u8], expected: PacketType) -> Result<T, ProtocolError> {
+    check_packet_type(data, expected)?;
+
+    let ((rest, _), value) = T::from_bytes((data, 0))?;
+    if !rest.is_empty() {
+        return Err(ProtocolError::TrailingBytes(rest.len()));
+    }
+    Ok(value)
+}
+
+/// Writes the deku layout of a value straight into a byte buffer. The bulk
+/// packets use it in `out_bytes_into`, as deku's bit-level writer is far
+/// slower than appending bytes.
+trait EncodeInto {
+    fn encode_into(&self, buffer: &mut Vec<u8>);
+}
+
+macro_rules! encode_big_endian {
+    ($($type:ty),*) => {
+        $(
+            impl EncodeInto for $type {
+                fn encode_into(&self, buffer: &mut Vec<u8>) {
+                    buffer.extend_from_slice(&self.to_be_bytes());
+                }
+            }
+        )*
+    };
+}
+
+encode_big_endian!(u8, u32, u64, i32);
+
+impl EncodeInto for PacketType {
+    fn encode_into(&self, buffer: &mut Vec<u8>) {
+        buffer.push(*self as u8);
+    }
+}
+
+impl EncodeInto for Compression {
+    fn encode_into(&self, buffer: &mut Vec<u8>) {
+        buffer.push(*self as u8);
+    }
+}
+
+/// A length-prefixed byte string, as `EncodedString` and the payloads.
+impl EncodeInto for [u8] {
+    fn encode_into(&self, buffer: &mut Vec<u8>) {
+        (self.len() as u32).encode_into(buffer);
+        buffer.extend_from_slice(self);
+    }
+}
+
+impl EncodeInto for EncodedString {
+    fn encode_into(&self, buffer: &mut Vec<u8>) {
+        self.string.encode_into(buffer);
+    }
+}
+
+impl EncodeInto for Region {
+    fn encode_into(&self, buffer: &mut Vec<u8>) {
+        self.start.encode_into(buffer);
+        self.end.encode_into(buffer);
+        self.size.encode_into(buffer);
+        self.permissions.encode_into(buffer);
+        self.offset.encode_into(buffer);
+        self.device.encode_into(buffer);
+        self.inode.encode_into(buffer);
+        self.pathname.encode_into(buffer);
+    }
+}
+
+impl EncodeInto for ProcessEntry {
+    fn encode_into(&self, buffer: &mut Vec<u8>) {
+        self.name.encode_into(buffer);
+        self.pid.encode_into(buffer);
+    }
+}
+
+/// Starts a frame in `buffer`, dropping what it held before but keeping its allocation.
+fn begin_frame(buffer: &mut Vec<u8>, packet_type: PacketType, request_id: u32) {
+    buffer.clear();
+    packet_type.encode_into(buffer);
+    request_id.encode_into(buffer);
+}
+
+/// Leading byte of every frame. Requests sent by the client use the low
+/// values, responses have the high bit set, mostly on their request's type.
+/// `Error` predates that split and keeps its value.
+#[derive(Debug, Clone, Copy, PartialEq, DekuRead, DekuWrite)]
 #[deku(type = "u8")]
 #[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")] // context passed from `DekuTest` top-level endian
 pub enum PacketType {
@@ -13,12 +229,206 @@ pub enum PacketType {
     Write = 1,
     TargetPID = 2,
     SendProcesses = 3,
+    Error = 4,
+    Hello = 5,
+    ReadMany = 6,
+    WriteMany = 7,
+    ReadTolerant = 8,
+    ReadStream = 9,
+    CancelStream = 10,
+    StartScan = 11,
+    NextScan = 12,
+    ScanResults = 13,
+    ClearScan = 14,
+    PatternScan = 15,
+    StringSearch = 16,
+    RegexSearch = 17,
+    PointerScan = 18,
+    PointerScanResults = 19,
+    ResolvePointerChain = 20,
+    ResolveExpression = 21,
+    Freeze = 22,
+    Unfreeze = 23,
+    Watch = 24,
+    Unwatch = 25,
+    TakeSnapshot = 26,
+    DiffSnapshots = 27,
+    DeleteSnapshot = 28,
+    SelectBackend = 29,
+
+    ReadResponse = 0x80,
+    WriteResponse = 0x81,
+    Regions = 0x82,
+    Processes = 0x83,
+    ServerInfo = 0x85,
+    ReadManyResponse = 0x86,
+    WriteManyResponse = 0x87,
+    ReadTolerantResponse = 0x88,
+    ReadChunk = 0x89,
+    /// Ends a read stream, either on its own or in answer to `CancelStream`.
+    ReadStreamEnd = 0x8A,
+    /// Answers `StartScan`, `NextScan`, `ClearScan` and `PointerScan`.
+    ScanStatus = 0x8B,
+    ScanResultsResponse = 0x8D,
+    PatternScanResponse = 0x8F,
+    StringSearchResponse = 0x90,
+    RegexSearchResponse = 0x91,
+    PointerScanResultsResponse = 0x93,
+    ResolvePointerChainResponse = 0x94,
+    ResolveExpressionResponse = 0x95,
+    FreezeResponse = 0x96,
+    FreezeEnd = 0x97,
+    WatchUpdate = 0x98,
+    WatchEnd = 0x99,
+    SnapshotStatus = 0x9A,
+    DiffSnapshotsResponse = 0x9B,
+    SelectBackendResponse = 0x9C,
+}
+
+/// Type of the values a scan compares.
+#[derive(Debug, Clone, Copy, PartialEq, DekuRead, DekuWrite)]
+#[deku(type = "u8")]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub enum ValueType {
+    I8 = 0,
+    I16 = 1,
+    I32 = 2,
+    I64 = 3,
+    U8 = 4,
+    U16 = 5,
+    U32 = 6,
+    U64 = 7,
+    F32 = 8,
+    F64 = 9,
+}
+
+impl ValueType {
+    pub fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::I64 | Self::U64 | Self::F64 => 8,
+        }
+    }
+}
+
+/// Byte order of scanned values in the target's memory.
+#[derive(Debug, Clone, Copy, PartialEq, DekuRead, DekuWrite)]
+#[deku(type = "u8")]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub enum Endianness {
+    Little = 0,
+    Big = 1,
+}
+
+/// Which candidates a scan keeps. `Changed`, `Unchanged`, `Increased`,
+/// `Decreased`, `IncreasedBy` and `DecreasedBy` compare against the value seen
+/// by the previous scan, so they only apply to `C2SNextScanPacket`.
+#[derive(Debug, Clone, Copy, PartialEq, DekuRead, DekuWrite)]
+#[deku(type = "u8")]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub enum ScanComparison {
+    Equal = 0,
+    Changed = 1,
+    Unchanged = 2,
+    Increased = 3,
+    Decreased = 4,
+    /// Between `value` and `upper`, both inclusive.
+    Between = 5,
+    /// Every value of the writable regions, for when the initial value is
+    /// not known. The service keeps a compressed snapshot that later scans
+    /// are compared against. Only applies to `C2SStartScanPacket`.
+    Unknown = 6,
+    /// Grew by exactly `value` since the previous scan.
+    IncreasedBy = 7,
+    /// Shrank by exactly `value` since the previous scan.
+    DecreasedBy = 8,
+}
+
+/// How the text of a `C2SStringSearchPacket` was found encoded.
+#[derive(Debug, Clone, Copy, PartialEq, DekuRead, DekuWrite)]
+#[deku(type = "u8")]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub enum StringEncoding {
+    /// Also covers ASCII.
+    Utf8 = 0,
+    Utf16Le = 1,
+}
+
+/// Why a read stream ended, see `S2CReadStreamEndPacket`.
+#[derive(Debug, Clone, Copy, PartialEq, DekuRead, DekuWrite)]
+#[deku(type = "u8")]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub enum StreamEnd {
+    Complete = 0,
+    /// By `C2SCancelStreamPacket`.
+    Cancelled = 1,
+    /// A chunk could not be read, `errno` tells why.
+    Failed = 2,
+    /// The session's target changed, see `C2STargetPidPacket`.
+    TargetChanged = 3,
+}
+
+/// Why a freeze ended, see `S2CFreezeEndPacket`.
+#[derive(Debug, Clone, Copy, PartialEq, DekuRead, DekuWrite)]
+#[deku(type = "u8")]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub enum FreezeEnd {
+    Unfrozen = 0,
+    /// The address could not be resolved or written, `errno` tells why.
+    Failed = 1,
+    /// The session's target changed, see `C2STargetPidPacket`.
+    TargetChanged = 2,
+}
+
+/// Compression of a read payload.
+#[derive(Debug, Clone, Copy, PartialEq, DekuRead, DekuWrite)]
+#[deku(type = "u8")]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub enum Compression {
+    None = 0,
+    /// LZ4 block format.
+    Lz4 = 1,
+}
+
+/// Machine-readable reason carried by `S2CErrorPacket`.
+#[derive(Debug, Clone, Copy, PartialEq, DekuRead, DekuWrite)]
+#[deku(type = "u8")]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub enum ErrorCode {
+    /// No target process has been selected yet.
+    NoTarget = 0,
+    /// ESRCH: the target process does not exist (anymore).
+    NoSuchProcess = 1,
+    /// EPERM: not allowed to access the target process.
+    PermissionDenied = 2,
+    /// EFAULT: the address range is not mapped in the target.
+    BadAddress = 3,
+    /// Only part of the requested range could be transferred.
+    PartialTransfer = 4,
+    /// The packet could not be decoded.
+    MalformedPacket = 5,
+    /// The first byte of the packet is not a known `PacketType`.
+    UnknownPacketType = 6,
+    /// The client speaks a different `PROTOCOL_VERSION`.
+    IncompatibleVersion = 7,
+    /// A request was sent before the `C2SHelloPacket`.
+    HandshakeRequired = 8,
+    /// The request exceeds one of the limits announced in `S2CServerInfoPacket`.
+    RequestTooLarge = 9,
+    /// There is no scan to refine or page through.
+    NoScan = 10,
+    /// A request field is out of range, like a scan value of the wrong size.
+    InvalidArgument = 11,
+    Other = 255,
 }
 
 #[derive(Debug, PartialEq, DekuRead, DekuWrite)]
 #[deku(endian = "big")]
 pub struct C2SReadMemoryPacket {
     _type: PacketType,
+    pub request_id: u32,
     pub address: u64,
     pub size: u32,
 }
@@ -27,16 +437,90 @@ pub struct C2SReadMemoryPacket {
 #[deku(endian = "big")]
 pub struct C2SWriteMemoryPacket {
     _type: PacketType,
+    pub request_id: u32,
+    pub address: u64,
+    pub count: u32,
+    #[deku(count = "checked_count(*count, 1, deku::rest)?")]
+    pub bytes: Vec<u8>,
+}
+
+/// Like `C2SReadMemoryPacket`, but unreadable pages do not fail the request.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SReadTolerantPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub address: u64,
+    pub size: u32,
+}
+
+/// Reads a range of any size as a sequence of `S2CReadChunkPacket`s of at
+/// most `chunk_size` bytes, terminated by a `S2CReadStreamEndPacket`.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SReadStreamPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub address: u64,
+    pub size: u64,
+    pub chunk_size: u32,
+}
+
+/// Stops the stream started by the request with `stream_request_id`.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SCancelStreamPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub stream_request_id: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub struct ReadRange {
+    pub address: u64,
+    pub size: u32,
+}
+
+/// Reads many ranges at once, see `S2CReadManyPacketResponse`.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SReadManyPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    #[deku(update = "self.ranges.len() as u32")]
+    pub count: u32,
+    #[deku(count = "checked_count(*count, READ_RANGE_SIZE, deku::rest)?")]
+    pub ranges: Vec<ReadRange>,
+}
+
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub struct WriteEntry {
     pub address: u64,
+    #[deku(update = "self.bytes.len() as u32")]
     pub count: u32,
-    #[deku(count = "count")]
+    #[deku(count = "checked_count(*count, 1, deku::rest)?")]
     pub bytes: Vec<u8>,
 }
 
+/// Writes many entries at once, see `S2CWriteManyPacketResponse`.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SWriteManyPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    #[deku(update = "self.entries.len() as u32")]
+    pub count: u32,
+    #[deku(count = "checked_count(*count, WRITE_ENTRY_MIN_SIZE, deku::rest)?")]
+    pub entries: Vec<WriteEntry>,
+}
+
 #[derive(Debug, PartialEq, DekuRead, DekuWrite)]
 #[deku(endian = "big")]
 pub struct C2STargetPidPacket {
     _type: PacketType,
+    pub request_id: u32,
     pub target_pid: Pid,
 }
 
@@ -44,9 +528,10 @@ pub struct C2STargetPidPacket {
 #[deku(endian = "big")]
 pub struct C2SGetProcessesPacket {
     _type: PacketType,
+    pub request_id: u32,
 }
 
-#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
 #[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
 pub struct ProcessEntry {
     pub name: EncodedString,
@@ -57,223 +542,2810 @@ pub struct ProcessEntry {
 #[deku(endian = "big")]
 pub struct S2CSendProcessesPacket {
     _type: PacketType,
+    pub request_id: u32,
     #[deku(update = "self.processes.len() as u32")]
     pub count: u32,
-    #[deku(count = "count")]
+    #[deku(count = "checked_count(*count, PROCESS_ENTRY_MIN_SIZE, deku::rest)?")]
     pub processes: Vec<ProcessEntry>,
 }
 
 #[derive(Debug, PartialEq, DekuRead, DekuWrite)]
 #[deku(endian = "big")]
+/// `data` is compressed according to `compression`, use `payload` to get the memory.
 pub struct S2CReadMemoryPacketResponse {
     _type: PacketType,
+    pub request_id: u32,
+    pub compression: Compression,
+    pub uncompressed_size: u32,
     pub count: u32,
-    #[deku(count = "count")]
+    #[deku(count = "checked_count(*count, 1, deku::rest)?")]
     pub data: Vec<u8>,
 }
 
+/// One chunk of a read stream, `offset` is relative to the streamed address.
+/// `data` is compressed like in `S2CReadMemoryPacketResponse`.
 #[derive(Debug, PartialEq, DekuRead, DekuWrite)]
 #[deku(endian = "big")]
-pub struct S2CWriteMemoryPacketResponse {
+pub struct S2CReadChunkPacket {
     _type: PacketType,
-    pub bytes_written: u64,
+    pub request_id: u32,
+    pub offset: u64,
+    pub compression: Compression,
+    pub uncompressed_size: u32,
+    pub count: u32,
+    #[deku(count = "checked_count(*count, 1, deku::rest)?")]
+    pub data: Vec<u8>,
+}
+
+/// Last packet of a read stream.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CReadStreamEndPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub reason: StreamEnd,
+    pub errno: i32,
+    pub bytes_sent: u64,
+}
+
+/// Scans every readable region of the target for values of `value_type`,
+/// replacing the session's previous scan. `value` and `upper` hold the
+/// operands as they appear in memory, i.e. `value_type.size()` bytes in
+/// `endianness`; `upper` is only used by `Between`. An `alignment` of 0 means
+/// the size of the value. Answered by `S2CScanStatusPacket`.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SStartScanPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub value_type: ValueType,
+    pub endianness: Endianness,
+    pub alignment: u32,
+    pub comparison: ScanComparison,
+    pub value_count: u32,
+    #[deku(count = "checked_count(*value_count, 1, deku::rest)?")]
+    pub value: Vec<u8>,
+    pub upper_count: u32,
+    #[deku(count = "checked_count(*upper_count, 1, deku::rest)?")]
+    pub upper: Vec<u8>,
+}
+
+/// Narrows the session's scan to the candidates whose current value passes
+/// `comparison`. Answered by `S2CScanStatusPacket`.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SNextScanPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub comparison: ScanComparison,
+    pub value_count: u32,
+    #[deku(count = "checked_count(*value_count, 1, deku::rest)?")]
+    pub value: Vec<u8>,
+    pub upper_count: u32,
+    #[deku(count = "checked_count(*upper_count, 1, deku::rest)?")]
+    pub upper: Vec<u8>,
+}
+
+/// Asks for up to `limit` results of the session's scan, starting at `offset`.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SScanResultsPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub offset: u64,
+    pub limit: u32,
 }
 
+/// Drops the session's scan and the memory it holds.
 #[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SClearScanPacket {
+    _type: PacketType,
+    pub request_id: u32,
+}
+
+/// Number of candidates left after a scan. `truncated` is set when the
+/// first scan found more than the service keeps.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CScanStatusPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub result_count: u64,
+    pub truncated: bool,
+}
+
+/// A candidate and its value as seen by the last scan.
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
 #[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
-pub struct EncodedString {
-    pub length: u32,
-    #[deku(count = "length")]
-    pub string: Vec<u8>,
+pub struct ScanResult {
+    pub address: u64,
+    pub count: u32,
+    #[deku(count = "checked_count(*count, 1, deku::rest)?")]
+    pub value: Vec<u8>,
 }
 
 #[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CScanResultsPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    /// Number of candidates in the whole scan.
+    pub total: u64,
+    pub offset: u64,
+    #[deku(update = "self.results.len() as u32")]
+    pub count: u32,
+    #[deku(count = "checked_count(*count, SCAN_RESULT_MIN_SIZE, deku::rest)?")]
+    pub results: Vec<ScanResult>,
+}
+
+/// Restricts a search to the regions that have all `permissions` bits (as in
+/// `Region`) and whose pathname contains `pathname`. Zero permissions and an
+/// empty pathname match every region.
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
 #[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
-pub struct Region {
-    pub start: u64,
-    pub end: u64,
-    pub size: u64,
+pub struct RegionFilter {
     pub permissions: u8,
-    pub offset: u64,
-    pub device: EncodedString,
-    pub inode: u64,
     pub pathname: EncodedString,
 }
 
-#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
-#[deku(endian = "big")]
-pub struct S2CTargetPidRegionsPacket {
-    _type: PacketType,
-    #[deku(update = "self.regions.len() as u32")]
-    pub count: u32,
-    #[deku(count = "count")]
-    pub regions: Vec<Region>,
+/// Searches the regions passing `filter` for an IDA-style byte pattern like
+/// `48 8B 05 ?? ?? ?? ?? 48 85 C0`, where `??` matches any byte and `4?` any
+/// byte whose high nibble is 4. With `resolve` every match also reports the
+/// target of the RIP-relative `i32` displacement `displacement_offset` bytes
+/// into it, which is relative to the end of the `instruction_length` byte
+/// instruction (0 means it ends right after the displacement), both at most
+/// 4096. Answered by `S2CPatternScanPacketResponse` with at most
+/// `max_results` matches, 0 meaning the service's limit.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SPatternScanPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub filter: RegionFilter,
+    pub pattern: EncodedString,
+    pub max_results: u32,
+    pub resolve: bool,
+    pub displacement_offset: u32,
+    pub instruction_length: u32,
+}
+
+/// `resolved` is the target of the displacement when resolving was asked
+/// for and the displacement lies within the region, 0 otherwise.
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub struct PatternMatch {
+    pub address: u64,
+    pub resolved: u64,
+}
+
+/// `truncated` is set when there were more matches than returned.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CPatternScanPacketResponse {
+    _type: PacketType,
+    pub request_id: u32,
+    pub truncated: bool,
+    #[deku(update = "self.matches.len() as u32")]
+    pub count: u32,
+    #[deku(count = "checked_count(*count, PATTERN_MATCH_SIZE, deku::rest)?")]
+    pub matches: Vec<PatternMatch>,
+}
+
+/// Searches the regions passing `filter` for `text` encoded as UTF-8 and/or
+/// UTF-16LE. With `ignore_case` letters match in either case. Every hit comes
+/// with up to `context` bytes around it. Answered by
+/// `S2CStringSearchPacketResponse` with at most `max_results` hits, 0 meaning
+/// the service's limit.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SStringSearchPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub filter: RegionFilter,
+    pub text: EncodedString,
+    pub utf8: bool,
+    pub utf16: bool,
+    pub ignore_case: bool,
+    pub context: u32,
+    pub max_results: u32,
+}
+
+/// `length` bytes at `address` matched. `context` holds the bytes from
+/// `context_address` on, cut short at the ends of the region.
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub struct StringHit {
+    pub address: u64,
+    pub length: u32,
+    pub encoding: StringEncoding,
+    pub context_address: u64,
+    pub count: u32,
+    #[deku(count = "checked_count(*count, 1, deku::rest)?")]
+    pub context: Vec<u8>,
+}
+
+/// `truncated` is set when there were more hits than returned.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CStringSearchPacketResponse {
+    _type: PacketType,
+    pub request_id: u32,
+    pub truncated: bool,
+    #[deku(update = "self.hits.len() as u32")]
+    pub count: u32,
+    #[deku(count = "checked_count(*count, STRING_HIT_MIN_SIZE, deku::rest)?")]
+    pub hits: Vec<StringHit>,
+}
+
+/// Searches the regions passing `filter` for a byte regex in the syntax of
+/// Rust's `regex::bytes` with Unicode off, so `.`, classes and `\xNN` match
+/// single bytes; `(?u)` turns it back on. Matches are cut short after `max_match_length`
+/// bytes and empty matches are skipped. Answered by
+/// `S2CRegexSearchPacketResponse` with at most `max_results` matches; 0 means
+/// the service's limit for either.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SRegexSearchPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub filter: RegionFilter,
+    pub pattern: EncodedString,
+    pub max_results: u32,
+    pub max_match_length: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub struct RegexMatch {
+    pub address: u64,
+    pub count: u32,
+    #[deku(count = "checked_count(*count, 1, deku::rest)?")]
+    pub bytes: Vec<u8>,
+}
+
+/// `truncated` is set when there were more matches than returned.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CRegexSearchPacketResponse {
+    _type: PacketType,
+    pub request_id: u32,
+    pub truncated: bool,
+    #[deku(update = "self.matches.len() as u32")]
+    pub count: u32,
+    #[deku(count = "checked_count(*count, REGEX_MATCH_MIN_SIZE, deku::rest)?")]
+    pub matches: Vec<RegexMatch>,
+}
+
+/// Copies the readable regions `filter` matches into a snapshot called
+/// `name`, replacing any snapshot of that name. Snapshots belong to the
+/// session and are kept compressed; large ones, or all of them with
+/// `to_disk`, are spilled to the service's data directory. Answered by
+/// `S2CSnapshotStatusPacket`.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2STakeSnapshotPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub name: EncodedString,
+    pub filter: RegionFilter,
+    pub to_disk: bool,
+}
+
+/// Compares the snapshot `old` with the snapshot `new`, or with the target's
+/// memory if `new` is empty. Only addresses present in both are compared.
+/// Answered by `S2CDiffSnapshotsPacketResponse` with at most `max_results`
+/// ranges, 0 meaning the service's limit.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SDiffSnapshotsPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub old: EncodedString,
+    pub new: EncodedString,
+    pub max_results: u32,
+}
+
+/// Answered by `S2CSnapshotStatusPacket` with everything zeroed.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SDeleteSnapshotPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub name: EncodedString,
+}
+
+/// `size` is the number of bytes the snapshot holds, from `region_count`
+/// regions.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CSnapshotStatusPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub region_count: u32,
+    pub size: u64,
+    pub on_disk: bool,
+}
+
+/// Consecutive bytes that differ, as they were in the old snapshot and as
+/// they are in the new one. Long runs are split into several ranges.
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub struct ChangedRange {
+    pub address: u64,
+    pub count: u32,
+    #[deku(count = "checked_count(*count, 1, deku::rest)?")]
+    pub old: Vec<u8>,
+    #[deku(count = "checked_count(*count, 1, deku::rest)?")]
+    pub new: Vec<u8>,
+}
+
+/// `truncated` is set when there were more changes than returned.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CDiffSnapshotsPacketResponse {
+    _type: PacketType,
+    pub request_id: u32,
+    pub truncated: bool,
+    #[deku(update = "self.ranges.len() as u32")]
+    pub count: u32,
+    #[deku(count = "checked_count(*count, CHANGED_RANGE_MIN_SIZE, deku::rest)?")]
+    pub ranges: Vec<ChangedRange>,
+}
+
+/// Switches how the session reaches the target's memory, for every later
+/// request. `backend` is one of the names in `S2CServerInfoPacket::backends`.
+/// Answered by `S2CSelectBackendPacketResponse`.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SSelectBackendPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub backend: EncodedString,
+}
+
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CSelectBackendPacketResponse {
+    _type: PacketType,
+    pub request_id: u32,
+    pub backend: EncodedString,
+}
+
+/// Looks for chains of pointers from a module's static memory to `target`,
+/// of the form `module+offset -> +o1 -> ... -> target`: the pointer at
+/// `module+offset` plus `o1` is where the next pointer is read, and so on,
+/// with at most `max_depth` pointers and offsets of at most `max_offset`.
+/// The paths are saved by the service under `name` (letters, digits, `-`,
+/// `_` and `.`). With `rescan` the paths saved under `name` are resolved
+/// again instead, e.g. after the target restarted, and only those still
+/// leading to `target` are kept. Answered by `S2CScanStatusPacket`.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SPointerScanPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub name: EncodedString,
+    pub target: u64,
+    pub max_depth: u8,
+    pub max_offset: u32,
+    pub rescan: bool,
+}
+
+/// Asks for up to `limit` paths saved under `name`, starting at `offset`.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SPointerScanResultsPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub name: EncodedString,
+    pub offset: u64,
+    pub limit: u32,
+}
+
+/// `module` is the pathname of a file-backed `Region`, `module_offset` is
+/// relative to the start of the file's lowest mapping.
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub struct PointerPath {
+    pub module: EncodedString,
+    pub module_offset: u64,
+    #[deku(update = "self.offsets.len() as u32")]
+    pub count: u32,
+    #[deku(count = "checked_count(*count, 4, deku::rest)?")]
+    pub offsets: Vec<u32>,
+}
+
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CPointerScanResultsPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    /// Number of paths saved under the name.
+    pub total: u64,
+    pub offset: u64,
+    #[deku(update = "self.paths.len() as u32")]
+    pub count: u32,
+    #[deku(count = "checked_count(*count, POINTER_PATH_MIN_SIZE, deku::rest)?")]
+    pub paths: Vec<PointerPath>,
+}
+
+/// Walks a pointer chain in one round-trip. It starts at `base`, relative to
+/// the module whose pathname or file name is `module`, or absolute if
+/// `module` is empty. For every offset the pointer at the current address is
+/// read and the offset added to it, then `read_size` bytes are read at the
+/// final address. A `PointerPath` maps onto this directly. Answered by
+/// `S2CResolvePointerChainPacketResponse`.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SResolvePointerChainPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub module: EncodedString,
+    pub base: u64,
+    pub offset_count: u32,
+    #[deku(count = "checked_count(*offset_count, 8, deku::rest)?")]
+    pub offsets: Vec<i64>,
+    pub read_size: u32,
+}
+
+/// `addresses` starts with the chain's base, followed by the address reached
+/// after each offset. If a read failed, `failed_step` is its index into
+/// `addresses`: the pointer at that address, or the final bytes if it is the
+/// last one, could not be read, and `errno` says why.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CResolvePointerChainPacketResponse {
+    _type: PacketType,
+    pub request_id: u32,
+    pub success: bool,
+    pub failed_step: u32,
+    pub errno: i32,
+    #[deku(update = "self.addresses.len() as u32")]
+    pub address_count: u32,
+    #[deku(count = "checked_count(*address_count, 8, deku::rest)?")]
+    pub addresses: Vec<u64>,
+    #[deku(update = "self.data.len() as u32")]
+    pub count: u32,
+    #[deku(count = "checked_count(*count, 1, deku::rest)?")]
+    pub data: Vec<u8>,
+}
+
+/// Resolves an address written in the language of `crate::expression` and
+/// reads `read_size` bytes there. Expressions that do not parse or name
+/// unknown modules are answered with `ErrorCode::InvalidArgument`, failed
+/// reads with the errno of the read.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SResolveExpressionPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub expression: EncodedString,
+    pub read_size: u32,
+}
+
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CResolveExpressionPacketResponse {
+    _type: PacketType,
+    pub request_id: u32,
+    pub address: u64,
+    #[deku(update = "self.data.len() as u32")]
+    pub count: u32,
+    #[deku(count = "checked_count(*count, 1, deku::rest)?")]
+    pub data: Vec<u8>,
+}
+
+/// Keeps writing `bytes` at the address `expression` resolves to, every
+/// `interval_ms` milliseconds, until a `C2SUnfreezePacket` names this
+/// request's id, the target changes or the session ends. Pointers in the
+/// expression are followed anew on every write. The first write happens right
+/// away and is answered with `S2CFreezePacketResponse`, or an error packet
+/// if it fails.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SFreezePacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub expression: EncodedString,
+    pub interval_ms: u32,
+    pub count: u32,
+    #[deku(count = "checked_count(*count, 1, deku::rest)?")]
+    pub bytes: Vec<u8>,
+}
+
+/// Answered with the freeze's `S2CFreezeEndPacket`.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SUnfreezePacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub freeze_request_id: u32,
+}
+
+/// `address` is where the first write went.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CFreezePacketResponse {
+    _type: PacketType,
+    pub request_id: u32,
+    pub address: u64,
+}
+
+/// Last packet of a freeze, carrying the request id of its `C2SFreezePacket`.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CFreezeEndPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub reason: FreezeEnd,
+    pub errno: i32,
+}
+
+/// `size` bytes at the address `expression` resolves to.
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub struct WatchTarget {
+    pub expression: EncodedString,
+    pub size: u32,
+}
+
+/// Reads `targets` every `interval_ms` milliseconds, following their pointers
+/// anew each time, and pushes a `S2CWatchUpdatePacket` with this request's id
+/// for the targets whose bytes or read errors changed, or for all of them if
+/// `every_tick` is set. The first update is sent right away and covers every
+/// target. Runs until a `C2SUnwatchPacket` names it, the target changes or
+/// the session ends.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SWatchPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub interval_ms: u32,
+    pub every_tick: bool,
+    #[deku(update = "self.targets.len() as u32")]
+    pub count: u32,
+    #[deku(count = "checked_count(*count, WATCH_TARGET_MIN_SIZE, deku::rest)?")]
+    pub targets: Vec<WatchTarget>,
+}
+
+/// Answered with the watch's `S2CWatchEndPacket`.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SUnwatchPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub watch_request_id: u32,
+}
+
+/// The read of the target at `index` in the `C2SWatchPacket`, at `address`
+/// unless its expression could not be resolved.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub struct WatchValue {
+    pub index: u32,
+    pub address: u64,
+    pub result: ReadManyResult,
+}
+
+/// `timestamp_us` is when the targets were read, in microseconds since the
+/// Unix epoch.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CWatchUpdatePacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub timestamp_us: u64,
+    #[deku(update = "self.values.len() as u32")]
+    pub count: u32,
+    #[deku(count = "checked_count(*count, WATCH_VALUE_MIN_SIZE, deku::rest)?")]
+    pub values: Vec<WatchValue>,
+}
+
+/// Last packet of a watch, carrying the request id of its `C2SWatchPacket`.
+/// Sent for `C2SUnwatchPacket` or because the session's target changed.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CWatchEndPacket {
+    _type: PacketType,
+    pub request_id: u32,
+}
+
+/// The requested range split at `page_size` boundaries into pages, see `page_chunks`.
+/// Bit `i` of `bitmap` (least significant bit first) is set when page `i` could be
+/// read; the bytes of unreadable pages are zero in `data`.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CReadTolerantPacketResponse {
+    _type: PacketType,
+    pub request_id: u32,
+    pub address: u64,
+    pub page_size: u32,
+    pub count: u32,
+    #[deku(count = "checked_count(*count, 1, deku::rest)?")]
+    pub data: Vec<u8>,
+    pub bitmap_count: u32,
+    #[deku(count = "checked_count(*bitmap_count, 1, deku::rest)?")]
+    pub bitmap: Vec<u8>,
+}
+
+/// Outcome of a single `ReadRange`. `data` is empty unless `success` is set.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub struct ReadManyResult {
+    pub success: bool,
+    pub errno: i32,
+    pub count: u32,
+    #[deku(count = "checked_count(*count, 1, deku::rest)?")]
+    pub data: Vec<u8>,
+}
+
+/// One `ReadManyResult` per range of the `C2SReadManyPacket`, in the same order.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CReadManyPacketResponse {
+    _type: PacketType,
+    pub request_id: u32,
+    #[deku(update = "self.results.len() as u32")]
+    pub count: u32,
+    #[deku(count = "checked_count(*count, READ_MANY_RESULT_MIN_SIZE, deku::rest)?")]
+    pub results: Vec<ReadManyResult>,
+}
+
+/// Outcome of a single `WriteEntry`.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub struct WriteManyResult {
+    pub success: bool,
+    pub errno: i32,
+    pub bytes_written: u32,
+}
+
+/// One `WriteManyResult` per entry of the `C2SWriteManyPacket`, in the same order.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CWriteManyPacketResponse {
+    _type: PacketType,
+    pub request_id: u32,
+    #[deku(update = "self.results.len() as u32")]
+    pub count: u32,
+    #[deku(count = "checked_count(*count, WRITE_MANY_RESULT_SIZE, deku::rest)?")]
+    pub results: Vec<WriteManyResult>,
+}
+
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CWriteMemoryPacketResponse {
+    _type: PacketType,
+    pub request_id: u32,
+    pub bytes_written: u64,
+}
+
+/// Sent instead of the regular response when a request fails.
+/// `packet_type` is the raw type byte of the request, since it might not be a known `PacketType`.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CErrorPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub code: ErrorCode,
+    pub errno: i32,
+    pub packet_type: u8,
+    pub message: EncodedString,
+}
+
+/// First packet of every session. `compression` is the compression the
+/// client accepts for large read payloads.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct C2SHelloPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub protocol_version: u16,
+    pub compression: Compression,
+}
+
+/// Answer to `C2SHelloPacket` describing what this service supports.
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CServerInfoPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    pub protocol_version: u16,
+    pub service_version: EncodedString,
+    /// Set when the session starts on the simulated backend, whose
+    /// processes are not real.
+    pub fake_read_write: bool,
+    /// Names accepted by `C2SSelectBackendPacket`, the session's default first.
+    #[deku(update = "self.backends.len() as u32")]
+    pub backend_count: u32,
+    #[deku(count = "checked_count(*backend_count, ENCODED_STRING_MIN_SIZE, deku::rest)?")]
+    pub backends: Vec<EncodedString>,
+    pub max_read_size: u32,
+    pub capabilities: u64,
+    /// Compression used for read payloads of at least `compression_threshold` bytes.
+    pub compression: Compression,
+    pub compression_threshold: u32,
+}
+
+/// Carries raw bytes: paths and process names come straight from the OS and
+/// need not be UTF-8. Use `to_string_lossy` to display them.
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub struct EncodedString {
+    pub length: u32,
+    #[deku(count = "checked_count(*length, 1, deku::rest)?")]
+    pub string: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
+pub struct Region {
+    pub start: u64,
+    pub end: u64,
+    pub size: u64,
+    pub permissions: u8,
+    pub offset: u64,
+    pub device: EncodedString,
+    pub inode: u64,
+    pub pathname: EncodedString,
+}
+
+#[derive(Debug, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct S2CTargetPidRegionsPacket {
+    _type: PacketType,
+    pub request_id: u32,
+    #[deku(update = "self.regions.len() as u32")]
+    pub count: u32,
+    #[deku(count = "checked_count(*count, REGION_MIN_SIZE, deku::rest)?")]
+    pub regions: Vec<Region>,
+}
+
+impl EncodedString {
+    pub fn new(string: String) -> Self {
+        Self::from_bytes(string.into_bytes())
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self {
+            length: bytes.len() as u32,
+            string: bytes,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.string
+    }
+
+    pub fn as_str(&self) -> Result<&str, std::str::Utf8Error> {
+        std::str::from_utf8(&self.string)
+    }
+
+    /// The string for display, with invalid UTF-8 replaced by U+FFFD.
+    pub fn to_string_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.string)
+    }
+}
+
+
+/// Every packet starts with its `PacketType` byte followed by the big-endian
+/// request id picked by the client. Responses echo the id of the request they
+/// answer, so this can be used to route a frame before fully parsing it.
+pub fn peek_request_id(data: &[u8]) -> Option<u32> {
+    let bytes = data.get(1..5)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+impl PacketType {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Read),
+            1 => Some(Self::Write),
+            2 => Some(Self::TargetPID),
+            3 => Some(Self::SendProcesses),
+            4 => Some(Self::Error),
+            5 => Some(Self::Hello),
+            6 => Some(Self::ReadMany),
+            7 => Some(Self::WriteMany),
+            8 => Some(Self::ReadTolerant),
+            9 => Some(Self::ReadStream),
+            10 => Some(Self::CancelStream),
+            11 => Some(Self::StartScan),
+            12 => Some(Self::NextScan),
+            13 => Some(Self::ScanResults),
+            14 => Some(Self::ClearScan),
+            15 => Some(Self::PatternScan),
+            16 => Some(Self::StringSearch),
+            17 => Some(Self::RegexSearch),
+            18 => Some(Self::PointerScan),
+            19 => Some(Self::PointerScanResults),
+            20 => Some(Self::ResolvePointerChain),
+            21 => Some(Self::ResolveExpression),
+            22 => Some(Self::Freeze),
+            23 => Some(Self::Unfreeze),
+            24 => Some(Self::Watch),
+            25 => Some(Self::Unwatch),
+            26 => Some(Self::TakeSnapshot),
+            27 => Some(Self::DiffSnapshots),
+            28 => Some(Self::DeleteSnapshot),
+            29 => Some(Self::SelectBackend),
+            0x80 => Some(Self::ReadResponse),
+            0x81 => Some(Self::WriteResponse),
+            0x82 => Some(Self::Regions),
+            0x83 => Some(Self::Processes),
+            0x85 => Some(Self::ServerInfo),
+            0x86 => Some(Self::ReadManyResponse),
+            0x87 => Some(Self::WriteManyResponse),
+            0x88 => Some(Self::ReadTolerantResponse),
+            0x89 => Some(Self::ReadChunk),
+            0x8A => Some(Self::ReadStreamEnd),
+            0x8B => Some(Self::ScanStatus),
+            0x8D => Some(Self::ScanResultsResponse),
+            0x8F => Some(Self::PatternScanResponse),
+            0x90 => Some(Self::StringSearchResponse),
+            0x91 => Some(Self::RegexSearchResponse),
+            0x93 => Some(Self::PointerScanResultsResponse),
+            0x94 => Some(Self::ResolvePointerChainResponse),
+            0x95 => Some(Self::ResolveExpressionResponse),
+            0x96 => Some(Self::FreezeResponse),
+            0x97 => Some(Self::FreezeEnd),
+            0x98 => Some(Self::WatchUpdate),
+            0x99 => Some(Self::WatchEnd),
+            0x9A => Some(Self::SnapshotStatus),
+            0x9B => Some(Self::DiffSnapshotsResponse),
+            0x9C => Some(Self::SelectBackendResponse),
+            _ => None,
+        }
+    }
+}
+
+/// Declares the packets one side may send as an enum whose variants are named
+/// after their `PacketType`, along with the single decode/encode entry point.
+macro_rules! packets {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident($packet:ty),)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, PartialEq)]
+        pub enum $name {
+            $($variant($packet),)*
+        }
+
+        impl $name {
+            /// Decodes a whole frame, dispatching on its leading packet type.
+            pub fn decode(data: &[u8]) -> Result<Self, ProtocolError> {
+                let packet_type = *data.first().ok_or(ProtocolError::Empty)?;
+                match PacketType::from_u8(packet_type) {
+                    $(Some(PacketType::$variant) => <$packet>::parse(data).map(Self::$variant),)*
+                    _ => Err(ProtocolError::UnknownPacketType(packet_type)),
+                }
+            }
+
+            pub fn encode(&self) -> Vec<u8> {
+                match self {
+                    $(Self::$variant(packet) => packet.to_bytes().unwrap(),)*
+                }
+            }
+
+            pub fn packet_type(&self) -> PacketType {
+                match self {
+                    $(Self::$variant(_) => PacketType::$variant,)*
+                }
+            }
+
+            pub fn request_id(&self) -> u32 {
+                match self {
+                    $(Self::$variant(packet) => packet.request_id,)*
+                }
+            }
+        }
+
+        $(
+            impl From<$packet> for $name {
+                fn from(packet: $packet) -> Self {
+                    Self::$variant(packet)
+                }
+            }
+        )*
+    };
+}
+
+packets! {
+    /// Every packet a client may send.
+    C2SPacket {
+        Hello(C2SHelloPacket),
+        Read(C2SReadMemoryPacket),
+        ReadTolerant(C2SReadTolerantPacket),
+        ReadMany(C2SReadManyPacket),
+        ReadStream(C2SReadStreamPacket),
+        CancelStream(C2SCancelStreamPacket),
+        StartScan(C2SStartScanPacket),
+        NextScan(C2SNextScanPacket),
+        ScanResults(C2SScanResultsPacket),
+        ClearScan(C2SClearScanPacket),
+        PatternScan(C2SPatternScanPacket),
+        StringSearch(C2SStringSearchPacket),
+        RegexSearch(C2SRegexSearchPacket),
+        PointerScan(C2SPointerScanPacket),
+        PointerScanResults(C2SPointerScanResultsPacket),
+        ResolvePointerChain(C2SResolvePointerChainPacket),
+        ResolveExpression(C2SResolveExpressionPacket),
+        Freeze(C2SFreezePacket),
+        Unfreeze(C2SUnfreezePacket),
+        Watch(C2SWatchPacket),
+        Unwatch(C2SUnwatchPacket),
+        TakeSnapshot(C2STakeSnapshotPacket),
+        DiffSnapshots(C2SDiffSnapshotsPacket),
+        DeleteSnapshot(C2SDeleteSnapshotPacket),
+        SelectBackend(C2SSelectBackendPacket),
+        Write(C2SWriteMemoryPacket),
+        WriteMany(C2SWriteManyPacket),
+        TargetPID(C2STargetPidPacket),
+        SendProcesses(C2SGetProcessesPacket),
+    }
+}
+
+packets! {
+    /// Every packet the service may send.
+    S2CPacket {
+        Error(S2CErrorPacket),
+        ServerInfo(S2CServerInfoPacket),
+        ReadResponse(S2CReadMemoryPacketResponse),
+        ReadTolerantResponse(S2CReadTolerantPacketResponse),
+        ReadManyResponse(S2CReadManyPacketResponse),
+        ReadChunk(S2CReadChunkPacket),
+        ReadStreamEnd(S2CReadStreamEndPacket),
+        ScanStatus(S2CScanStatusPacket),
+        ScanResultsResponse(S2CScanResultsPacket),
+        PatternScanResponse(S2CPatternScanPacketResponse),
+        StringSearchResponse(S2CStringSearchPacketResponse),
+        RegexSearchResponse(S2CRegexSearchPacketResponse),
+        PointerScanResultsResponse(S2CPointerScanResultsPacket),
+        ResolvePointerChainResponse(S2CResolvePointerChainPacketResponse),
+        ResolveExpressionResponse(S2CResolveExpressionPacketResponse),
+        FreezeResponse(S2CFreezePacketResponse),
+        FreezeEnd(S2CFreezeEndPacket),
+        WatchUpdate(S2CWatchUpdatePacket),
+        WatchEnd(S2CWatchEndPacket),
+        SnapshotStatus(S2CSnapshotStatusPacket),
+        DiffSnapshotsResponse(S2CDiffSnapshotsPacketResponse),
+        SelectBackendResponse(S2CSelectBackendPacketResponse),
+        WriteResponse(S2CWriteMemoryPacketResponse),
+        WriteManyResponse(S2CWriteManyPacketResponse),
+        Regions(S2CTargetPidRegionsPacket),
+        Processes(S2CSendProcessesPacket),
+    }
+}
+
+impl C2STargetPidPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::TargetPID)
+    }
+
+    pub fn out_bytes(request_id: u32, target_pid: Pid) -> Vec<u8> {
+        let object = C2STargetPidPacket {
+            _type: PacketType::TargetPID,
+            request_id,
+            target_pid,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SReadMemoryPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::Read)
+    }
+
+    pub fn out_bytes(request_id: u32, address: u64, size: u32) -> Vec<u8> {
+        let object = C2SReadMemoryPacket {
+            _type: PacketType::Read,
+            request_id,
+            address,
+            size,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+fn compress(compression: Compression, data: &[u8]) -> Cow<'_, [u8]> {
+    match compression {
+        Compression::None => Cow::Borrowed(data),
+        Compression::Lz4 => Cow::Owned(lz4_flex::compress(data)),
+    }
+}
+
+fn decompress(compression: Compression, uncompressed_size: u32, data: Cow<'_, [u8]>) -> Result<Cow<'_, [u8]>, ProtocolError> {
+    match compression {
+        Compression::None => Ok(data),
+        Compression::Lz4 => {
+            // LZ4 cannot expand data by more than 255:1, so anything above
+            // that is a lie we should not allocate for.
+            if uncompressed_size as usize > data.len().saturating_mul(255) {
+                return Err(ProtocolError::Decompression(format!(
+                    "{} compressed bytes cannot hold {} bytes",
+                    data.len(),
+                    uncompressed_size
+                )));
+            }
+            let decompressed = lz4_flex::decompress(&data, uncompressed_size as usize)
+                .map_err(|error| ProtocolError::Decompression(error.to_string()))?;
+            if decompressed.len() != uncompressed_size as usize {
+                return Err(ProtocolError::Decompression(format!(
+                    "expected {} bytes, got {}",
+                    uncompressed_size,
+                    decompressed.len()
+                )));
+            }
+            Ok(Cow::Owned(decompressed))
+        }
+    }
+}
+
+/// Splits `[address, address + size)` at `page_size` boundaries into `(address, size)` chunks.
+/// A `page_size` of 0 is treated as 1.
+pub fn page_chunks(address: u64, size: u64, page_size: u64) -> impl Iterator<Item = (u64, usize)> {
+    let page_size = page_size.max(1);
+    let end = address.saturating_add(size);
+    let mut current = address;
+    std::iter::from_fn(move || {
+        if current >= end {
+            return None;
+        }
+        let page_end = (current / page_size + 1).saturating_mul(page_size).min(end);
+        let chunk = (current, (page_end - current) as usize);
+        current = page_end;
+        Some(chunk)
+    })
+}
+
+impl C2SReadTolerantPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::ReadTolerant)
+    }
+
+    pub fn out_bytes(request_id: u32, address: u64, size: u32) -> Vec<u8> {
+        let object = C2SReadTolerantPacket {
+            _type: PacketType::ReadTolerant,
+            request_id,
+            address,
+            size,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl S2CReadTolerantPacketResponse {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::ReadTolerantResponse)
+    }
+
+    pub fn out_bytes(request_id: u32, address: u64, page_size: u32, data: Vec<u8>, readable: &[bool]) -> Vec<u8> {
+        let mut bitmap = vec![0u8; readable.len().div_ceil(8)];
+        for (page, _) in readable.iter().enumerate().filter(|(_, &readable)| readable) {
+            bitmap[page / 8] |= 1 << (page % 8);
+        }
+
+        let object = S2CReadTolerantPacketResponse {
+            _type: PacketType::ReadTolerantResponse,
+            request_id,
+            address,
+            page_size,
+            count: data.len() as u32,
+            data,
+            bitmap_count: bitmap.len() as u32,
+            bitmap,
+        };
+        object.to_bytes().unwrap()
+    }
+
+    pub fn is_page_readable(&self, page: usize) -> bool {
+        self.bitmap
+            .get(page / 8)
+            .is_some_and(|byte| byte & (1 << (page % 8)) != 0)
+    }
+
+    /// The readable parts of `data` as `(offset, length)`, adjacent pages merged.
+    pub fn readable_ranges(&self) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        let mut offset = 0;
+        let chunks = page_chunks(self.address, self.data.len() as u64, self.page_size as u64);
+        for (page, (_, size)) in chunks.enumerate() {
+            if self.is_page_readable(page) {
+                match ranges.last_mut() {
+                    Some((start, length)) if *start + *length == offset => *length += size,
+                    _ => ranges.push((offset, size)),
+                }
+            }
+            offset += size;
+        }
+        ranges
+    }
+}
+
+impl C2SReadStreamPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::ReadStream)
+    }
+
+    pub fn out_bytes(request_id: u32, address: u64, size: u64, chunk_size: u32) -> Vec<u8> {
+        let object = C2SReadStreamPacket {
+            _type: PacketType::ReadStream,
+            request_id,
+            address,
+            size,
+            chunk_size,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SCancelStreamPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::CancelStream)
+    }
+
+    pub fn out_bytes(request_id: u32, stream_request_id: u32) -> Vec<u8> {
+        let object = C2SCancelStreamPacket {
+            _type: PacketType::CancelStream,
+            request_id,
+            stream_request_id,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl S2CReadChunkPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::ReadChunk)
+    }
+
+    pub fn out_bytes(request_id: u32, offset: u64, data: Vec<u8>, compression: Compression) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        Self::out_bytes_into(request_id, offset, &data, compression, &mut buffer);
+        buffer
+    }
+
+    pub fn out_bytes_into(request_id: u32, offset: u64, data: &[u8], compression: Compression, buffer: &mut Vec<u8>) {
+        begin_frame(buffer, PacketType::ReadChunk, request_id);
+        offset.encode_into(buffer);
+        compression.encode_into(buffer);
+        (data.len() as u32).encode_into(buffer);
+        compress(compression, data).encode_into(buffer);
+    }
+
+    /// The memory of this chunk, decompressed if needed.
+    pub fn payload(self) -> Result<Vec<u8>, ProtocolError> {
+        decompress(self.compression, self.uncompressed_size, Cow::Owned(self.data)).map(Cow::into_owned)
+    }
+}
+
+impl S2CReadStreamEndPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::ReadStreamEnd)
+    }
+
+    pub fn out_bytes(request_id: u32, reason: StreamEnd, errno: i32, bytes_sent: u64) -> Vec<u8> {
+        let object = S2CReadStreamEndPacket {
+            _type: PacketType::ReadStreamEnd,
+            request_id,
+            reason,
+            errno,
+            bytes_sent,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SStartScanPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::StartScan)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn out_bytes(
+        request_id: u32,
+        value_type: ValueType,
+        endianness: Endianness,
+        alignment: u32,
+        comparison: ScanComparison,
+        value: Vec<u8>,
+        upper: Vec<u8>,
+    ) -> Vec<u8> {
+        let object = C2SStartScanPacket {
+            _type: PacketType::StartScan,
+            request_id,
+            value_type,
+            endianness,
+            alignment,
+            comparison,
+            value_count: value.len() as u32,
+            value,
+            upper_count: upper.len() as u32,
+            upper,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SNextScanPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::NextScan)
+    }
+
+    pub fn out_bytes(request_id: u32, comparison: ScanComparison, value: Vec<u8>, upper: Vec<u8>) -> Vec<u8> {
+        let object = C2SNextScanPacket {
+            _type: PacketType::NextScan,
+            request_id,
+            comparison,
+            value_count: value.len() as u32,
+            value,
+            upper_count: upper.len() as u32,
+            upper,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SScanResultsPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::ScanResults)
+    }
+
+    pub fn out_bytes(request_id: u32, offset: u64, limit: u32) -> Vec<u8> {
+        let object = C2SScanResultsPacket {
+            _type: PacketType::ScanResults,
+            request_id,
+            offset,
+            limit,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SClearScanPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::ClearScan)
+    }
+
+    pub fn out_bytes(request_id: u32) -> Vec<u8> {
+        let object = C2SClearScanPacket {
+            _type: PacketType::ClearScan,
+            request_id,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl S2CScanStatusPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::ScanStatus)
+    }
+
+    pub fn out_bytes(request_id: u32, result_count: u64, truncated: bool) -> Vec<u8> {
+        let object = S2CScanStatusPacket {
+            _type: PacketType::ScanStatus,
+            request_id,
+            result_count,
+            truncated,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl ScanResult {
+    pub fn new(address: u64, value: Vec<u8>) -> Self {
+        Self {
+            address,
+            count: value.len() as u32,
+            value,
+        }
+    }
+}
+
+impl S2CScanResultsPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::ScanResultsResponse)
+    }
+
+    pub fn out_bytes(request_id: u32, total: u64, offset: u64, results: Vec<ScanResult>) -> Vec<u8> {
+        let object = S2CScanResultsPacket {
+            _type: PacketType::ScanResultsResponse,
+            request_id,
+            total,
+            offset,
+            count: results.len() as u32,
+            results,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl RegionFilter {
+    pub fn new(permissions: u8, pathname: Vec<u8>) -> Self {
+        Self {
+            permissions,
+            pathname: EncodedString::from_bytes(pathname),
+        }
+    }
+
+    pub fn matches(&self, region: &Region) -> bool {
+        let pathname = self.pathname.as_bytes();
+        region.permissions & self.permissions == self.permissions
+            && (pathname.is_empty()
+                || region
+                    .pathname
+                    .as_bytes()
+                    .windows(pathname.len())
+                    .any(|window| window == pathname))
+    }
+}
+
+impl C2SPatternScanPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::PatternScan)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn out_bytes(
+        request_id: u32,
+        filter: RegionFilter,
+        pattern: String,
+        max_results: u32,
+        resolve: bool,
+        displacement_offset: u32,
+        instruction_length: u32,
+    ) -> Vec<u8> {
+        let object = C2SPatternScanPacket {
+            _type: PacketType::PatternScan,
+            request_id,
+            filter,
+            pattern: EncodedString::new(pattern),
+            max_results,
+            resolve,
+            displacement_offset,
+            instruction_length,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl S2CPatternScanPacketResponse {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::PatternScanResponse)
+    }
+
+    pub fn out_bytes(request_id: u32, truncated: bool, matches: Vec<PatternMatch>) -> Vec<u8> {
+        let object = S2CPatternScanPacketResponse {
+            _type: PacketType::PatternScanResponse,
+            request_id,
+            truncated,
+            count: matches.len() as u32,
+            matches,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SStringSearchPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::StringSearch)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn out_bytes(
+        request_id: u32,
+        filter: RegionFilter,
+        text: String,
+        utf8: bool,
+        utf16: bool,
+        ignore_case: bool,
+        context: u32,
+        max_results: u32,
+    ) -> Vec<u8> {
+        let object = C2SStringSearchPacket {
+            _type: PacketType::StringSearch,
+            request_id,
+            filter,
+            text: EncodedString::new(text),
+            utf8,
+            utf16,
+            ignore_case,
+            context,
+            max_results,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl StringHit {
+    pub fn new(address: u64, length: u32, encoding: StringEncoding, context_address: u64, context: Vec<u8>) -> Self {
+        Self {
+            address,
+            length,
+            encoding,
+            context_address,
+            count: context.len() as u32,
+            context,
+        }
+    }
+}
+
+impl S2CStringSearchPacketResponse {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::StringSearchResponse)
+    }
+
+    pub fn out_bytes(request_id: u32, truncated: bool, hits: Vec<StringHit>) -> Vec<u8> {
+        let object = S2CStringSearchPacketResponse {
+            _type: PacketType::StringSearchResponse,
+            request_id,
+            truncated,
+            count: hits.len() as u32,
+            hits,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SRegexSearchPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::RegexSearch)
+    }
+
+    pub fn out_bytes(
+        request_id: u32,
+        filter: RegionFilter,
+        pattern: String,
+        max_results: u32,
+        max_match_length: u32,
+    ) -> Vec<u8> {
+        let object = C2SRegexSearchPacket {
+            _type: PacketType::RegexSearch,
+            request_id,
+            filter,
+            pattern: EncodedString::new(pattern),
+            max_results,
+            max_match_length,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl RegexMatch {
+    pub fn new(address: u64, bytes: Vec<u8>) -> Self {
+        Self {
+            address,
+            count: bytes.len() as u32,
+            bytes,
+        }
+    }
+}
+
+impl S2CRegexSearchPacketResponse {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::RegexSearchResponse)
+    }
+
+    pub fn out_bytes(request_id: u32, truncated: bool, matches: Vec<RegexMatch>) -> Vec<u8> {
+        let object = S2CRegexSearchPacketResponse {
+            _type: PacketType::RegexSearchResponse,
+            request_id,
+            truncated,
+            count: matches.len() as u32,
+            matches,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2STakeSnapshotPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::TakeSnapshot)
+    }
+
+    pub fn out_bytes(request_id: u32, name: Vec<u8>, filter: RegionFilter, to_disk: bool) -> Vec<u8> {
+        let object = C2STakeSnapshotPacket {
+            _type: PacketType::TakeSnapshot,
+            request_id,
+            name: EncodedString::from_bytes(name),
+            filter,
+            to_disk,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SDiffSnapshotsPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::DiffSnapshots)
+    }
+
+    pub fn out_bytes(request_id: u32, old: Vec<u8>, new: Vec<u8>, max_results: u32) -> Vec<u8> {
+        let object = C2SDiffSnapshotsPacket {
+            _type: PacketType::DiffSnapshots,
+            request_id,
+            old: EncodedString::from_bytes(old),
+            new: EncodedString::from_bytes(new),
+            max_results,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SDeleteSnapshotPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::DeleteSnapshot)
+    }
+
+    pub fn out_bytes(request_id: u32, name: Vec<u8>) -> Vec<u8> {
+        let object = C2SDeleteSnapshotPacket {
+            _type: PacketType::DeleteSnapshot,
+            request_id,
+            name: EncodedString::from_bytes(name),
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl S2CSnapshotStatusPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::SnapshotStatus)
+    }
+
+    pub fn out_bytes(request_id: u32, region_count: u32, size: u64, on_disk: bool) -> Vec<u8> {
+        let object = S2CSnapshotStatusPacket {
+            _type: PacketType::SnapshotStatus,
+            request_id,
+            region_count,
+            size,
+            on_disk,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SSelectBackendPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::SelectBackend)
+    }
+
+    pub fn out_bytes(request_id: u32, backend: String) -> Vec<u8> {
+        let object = C2SSelectBackendPacket {
+            _type: PacketType::SelectBackend,
+            request_id,
+            backend: EncodedString::new(backend),
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl S2CSelectBackendPacketResponse {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::SelectBackendResponse)
+    }
+
+    pub fn out_bytes(request_id: u32, backend: String) -> Vec<u8> {
+        let object = S2CSelectBackendPacketResponse {
+            _type: PacketType::SelectBackendResponse,
+            request_id,
+            backend: EncodedString::new(backend),
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl ChangedRange {
+    /// `old` and `new` must have the same length.
+    pub fn new(address: u64, old: Vec<u8>, new: Vec<u8>) -> Self {
+        debug_assert_eq!(old.len(), new.len());
+        Self {
+            address,
+            count: old.len() as u32,
+            old,
+            new,
+        }
+    }
+}
+
+impl S2CDiffSnapshotsPacketResponse {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::DiffSnapshotsResponse)
+    }
+
+    pub fn out_bytes(request_id: u32, truncated: bool, ranges: Vec<ChangedRange>) -> Vec<u8> {
+        let object = S2CDiffSnapshotsPacketResponse {
+            _type: PacketType::DiffSnapshotsResponse,
+            request_id,
+            truncated,
+            count: ranges.len() as u32,
+            ranges,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SPointerScanPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::PointerScan)
+    }
+
+    pub fn out_bytes(request_id: u32, name: String, target: u64, max_depth: u8, max_offset: u32, rescan: bool) -> Vec<u8> {
+        let object = C2SPointerScanPacket {
+            _type: PacketType::PointerScan,
+            request_id,
+            name: EncodedString::new(name),
+            target,
+            max_depth,
+            max_offset,
+            rescan,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SPointerScanResultsPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::PointerScanResults)
+    }
+
+    pub fn out_bytes(request_id: u32, name: String, offset: u64, limit: u32) -> Vec<u8> {
+        let object = C2SPointerScanResultsPacket {
+            _type: PacketType::PointerScanResults,
+            request_id,
+            name: EncodedString::new(name),
+            offset,
+            limit,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl PointerPath {
+    pub fn new(module: Vec<u8>, module_offset: u64, offsets: Vec<u32>) -> Self {
+        Self {
+            module: EncodedString::from_bytes(module),
+            module_offset,
+            count: offsets.len() as u32,
+            offsets,
+        }
+    }
+}
+
+impl S2CPointerScanResultsPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::PointerScanResultsResponse)
+    }
+
+    pub fn out_bytes(request_id: u32, total: u64, offset: u64, paths: Vec<PointerPath>) -> Vec<u8> {
+        let object = S2CPointerScanResultsPacket {
+            _type: PacketType::PointerScanResultsResponse,
+            request_id,
+            total,
+            offset,
+            count: paths.len() as u32,
+            paths,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SResolvePointerChainPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::ResolvePointerChain)
+    }
+
+    pub fn out_bytes(request_id: u32, module: Vec<u8>, base: u64, offsets: Vec<i64>, read_size: u32) -> Vec<u8> {
+        let object = C2SResolvePointerChainPacket {
+            _type: PacketType::ResolvePointerChain,
+            request_id,
+            module: EncodedString::from_bytes(module),
+            base,
+            offset_count: offsets.len() as u32,
+            offsets,
+            read_size,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl S2CResolvePointerChainPacketResponse {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::ResolvePointerChainResponse)
+    }
+
+    pub fn ok(request_id: u32, addresses: Vec<u64>, data: Vec<u8>) -> Vec<u8> {
+        Self::out_bytes(request_id, true, 0, 0, addresses, data)
+    }
+
+    pub fn failed(request_id: u32, addresses: Vec<u64>, errno: i32) -> Vec<u8> {
+        let failed_step = addresses.len() as u32 - 1;
+        Self::out_bytes(request_id, false, failed_step, errno, addresses, Vec::new())
+    }
+
+    fn out_bytes(
+        request_id: u32,
+        success: bool,
+        failed_step: u32,
+        errno: i32,
+        addresses: Vec<u64>,
+        data: Vec<u8>,
+    ) -> Vec<u8> {
+        let object = S2CResolvePointerChainPacketResponse {
+            _type: PacketType::ResolvePointerChainResponse,
+            request_id,
+            success,
+            failed_step,
+            errno,
+            address_count: addresses.len() as u32,
+            addresses,
+            count: data.len() as u32,
+            data,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SResolveExpressionPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::ResolveExpression)
+    }
+
+    pub fn out_bytes(request_id: u32, expression: String, read_size: u32) -> Vec<u8> {
+        let object = C2SResolveExpressionPacket {
+            _type: PacketType::ResolveExpression,
+            request_id,
+            expression: EncodedString::new(expression),
+            read_size,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl S2CResolveExpressionPacketResponse {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::ResolveExpressionResponse)
+    }
+
+    pub fn out_bytes(request_id: u32, address: u64, data: Vec<u8>) -> Vec<u8> {
+        let object = S2CResolveExpressionPacketResponse {
+            _type: PacketType::ResolveExpressionResponse,
+            request_id,
+            address,
+            count: data.len() as u32,
+            data,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SFreezePacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::Freeze)
+    }
+
+    pub fn out_bytes(request_id: u32, expression: String, interval_ms: u32, bytes: Vec<u8>) -> Vec<u8> {
+        let object = C2SFreezePacket {
+            _type: PacketType::Freeze,
+            request_id,
+            expression: EncodedString::new(expression),
+            interval_ms,
+            count: bytes.len() as u32,
+            bytes,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SUnfreezePacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::Unfreeze)
+    }
+
+    pub fn out_bytes(request_id: u32, freeze_request_id: u32) -> Vec<u8> {
+        let object = C2SUnfreezePacket {
+            _type: PacketType::Unfreeze,
+            request_id,
+            freeze_request_id,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl S2CFreezePacketResponse {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::FreezeResponse)
+    }
+
+    pub fn out_bytes(request_id: u32, address: u64) -> Vec<u8> {
+        let object = S2CFreezePacketResponse {
+            _type: PacketType::FreezeResponse,
+            request_id,
+            address,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl S2CFreezeEndPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::FreezeEnd)
+    }
+
+    pub fn out_bytes(request_id: u32, reason: FreezeEnd, errno: i32) -> Vec<u8> {
+        let object = S2CFreezeEndPacket {
+            _type: PacketType::FreezeEnd,
+            request_id,
+            reason,
+            errno,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl WatchTarget {
+    pub fn new(expression: String, size: u32) -> Self {
+        Self {
+            expression: EncodedString::new(expression),
+            size,
+        }
+    }
+}
+
+impl C2SWatchPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::Watch)
+    }
+
+    pub fn out_bytes(request_id: u32, interval_ms: u32, every_tick: bool, targets: Vec<WatchTarget>) -> Vec<u8> {
+        let object = C2SWatchPacket {
+            _type: PacketType::Watch,
+            request_id,
+            interval_ms,
+            every_tick,
+            count: targets.len() as u32,
+            targets,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SUnwatchPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::Unwatch)
+    }
+
+    pub fn out_bytes(request_id: u32, watch_request_id: u32) -> Vec<u8> {
+        let object = C2SUnwatchPacket {
+            _type: PacketType::Unwatch,
+            request_id,
+            watch_request_id,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl S2CWatchUpdatePacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::WatchUpdate)
+    }
+
+    pub fn out_bytes(request_id: u32, timestamp_us: u64, values: Vec<WatchValue>) -> Vec<u8> {
+        let object = S2CWatchUpdatePacket {
+            _type: PacketType::WatchUpdate,
+            request_id,
+            timestamp_us,
+            count: values.len() as u32,
+            values,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl S2CWatchEndPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::WatchEnd)
+    }
+
+    pub fn out_bytes(request_id: u32) -> Vec<u8> {
+        let object = S2CWatchEndPacket {
+            _type: PacketType::WatchEnd,
+            request_id,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SReadManyPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::ReadMany)
+    }
+
+    pub fn out_bytes(request_id: u32, ranges: Vec<ReadRange>) -> Vec<u8> {
+        let object = C2SReadManyPacket {
+            _type: PacketType::ReadMany,
+            request_id,
+            count: ranges.len() as u32,
+            ranges,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SWriteMemoryPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::Write)
+    }
+
+    pub fn out_bytes(request_id: u32, address: u64, bytes: Vec<u8>) -> Vec<u8> {
+        let object = C2SWriteMemoryPacket {
+            _type: PacketType::Write,
+            request_id,
+            address,
+            count: bytes.len() as u32,
+            bytes,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl WriteEntry {
+    pub fn new(address: u64, bytes: Vec<u8>) -> Self {
+        Self {
+            address,
+            count: bytes.len() as u32,
+            bytes,
+        }
+    }
+}
+
+impl C2SWriteManyPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::WriteMany)
+    }
+
+    pub fn out_bytes(request_id: u32, entries: Vec<WriteEntry>) -> Vec<u8> {
+        let object = C2SWriteManyPacket {
+            _type: PacketType::WriteMany,
+            request_id,
+            count: entries.len() as u32,
+            entries,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SGetProcessesPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::SendProcesses)
+    }
+
+    pub fn out_bytes(request_id: u32) -> Vec<u8> {
+        let object = C2SGetProcessesPacket {
+            _type: PacketType::SendProcesses,
+            request_id,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl S2CReadMemoryPacketResponse {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::ReadResponse)
+    }
+
+    pub fn out_bytes(request_id: u32, data: Vec<u8>) -> Vec<u8> {
+        Self::out_bytes_compressed(request_id, data, Compression::None)
+    }
+
+    pub fn out_bytes_compressed(request_id: u32, data: Vec<u8>, compression: Compression) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        Self::out_bytes_into(request_id, &data, compression, &mut buffer);
+        buffer
+    }
+
+    /// Like `out_bytes_compressed`, but borrows `data` and reuses `buffer`.
+    pub fn out_bytes_into(request_id: u32, data: &[u8], compression: Compression, buffer: &mut Vec<u8>) {
+        begin_frame(buffer, PacketType::ReadResponse, request_id);
+        compression.encode_into(buffer);
+        (data.len() as u32).encode_into(buffer);
+        compress(compression, data).encode_into(buffer);
+    }
+
+    /// The memory that was read, decompressed if needed.
+    pub fn payload(self) -> Result<Vec<u8>, ProtocolError> {
+        decompress(self.compression, self.uncompressed_size, Cow::Owned(self.data)).map(Cow::into_owned)
+    }
+}
+
+impl ReadManyResult {
+    pub fn ok(data: Vec<u8>) -> Self {
+        Self {
+            success: true,
+            errno: 0,
+            count: data.len() as u32,
+            data,
+        }
+    }
+
+    pub fn failed(errno: i32) -> Self {
+        Self {
+            success: false,
+            errno,
+            count: 0,
+            data: Vec::new(),
+        }
+    }
+}
+
+impl S2CReadManyPacketResponse {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::ReadManyResponse)
+    }
+
+    pub fn out_bytes(request_id: u32, results: Vec<ReadManyResult>) -> Vec<u8> {
+        let object = S2CReadManyPacketResponse {
+            _type: PacketType::ReadManyResponse,
+            request_id,
+            count: results.len() as u32,
+            results,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl WriteManyResult {
+    pub fn ok(bytes_written: u32) -> Self {
+        Self {
+            success: true,
+            errno: 0,
+            bytes_written,
+        }
+    }
+
+    pub fn failed(errno: i32) -> Self {
+        Self {
+            success: false,
+            errno,
+            bytes_written: 0,
+        }
+    }
+}
+
+impl S2CWriteManyPacketResponse {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::WriteManyResponse)
+    }
+
+    pub fn out_bytes(request_id: u32, results: Vec<WriteManyResult>) -> Vec<u8> {
+        let object = S2CWriteManyPacketResponse {
+            _type: PacketType::WriteManyResponse,
+            request_id,
+            count: results.len() as u32,
+            results,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl S2CWriteMemoryPacketResponse {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::WriteResponse)
+    }
+    pub fn out_bytes(request_id: u32, bytes_written: u64) -> Vec<u8> {
+        let object = S2CWriteMemoryPacketResponse {
+            _type: PacketType::WriteResponse,
+            request_id,
+            bytes_written,
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl S2CTargetPidRegionsPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::Regions)
+    }
+    pub fn out_bytes(request_id: u32, regions: Vec<Region>) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        Self::out_bytes_into(request_id, &regions, &mut buffer);
+        buffer
+    }
+
+    pub fn out_bytes_into(request_id: u32, regions: &[Region], buffer: &mut Vec<u8>) {
+        begin_frame(buffer, PacketType::Regions, request_id);
+        (regions.len() as u32).encode_into(buffer);
+        for region in regions {
+            region.encode_into(buffer);
+        }
+    }
+}
+
+impl S2CSendProcessesPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::Processes)
+    }
+
+    pub fn out_bytes(request_id: u32, processes: Vec<ProcessEntry>) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        Self::out_bytes_into(request_id, &processes, &mut buffer);
+        buffer
+    }
+
+    pub fn out_bytes_into(request_id: u32, processes: &[ProcessEntry], buffer: &mut Vec<u8>) {
+        begin_frame(buffer, PacketType::Processes, request_id);
+        (processes.len() as u32).encode_into(buffer);
+        for process in processes {
+            process.encode_into(buffer);
+        }
+    }
+}
+
+impl S2CErrorPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::Error)
+    }
+
+    pub fn out_bytes(request_id: u32, code: ErrorCode, errno: i32, packet_type: u8, message: String) -> Vec<u8> {
+        let object = S2CErrorPacket {
+            _type: PacketType::Error,
+            request_id,
+            code,
+            errno,
+            packet_type,
+            message: EncodedString::new(message),
+        };
+        object.to_bytes().unwrap()
+    }
+}
+
+impl C2SHelloPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::Hello)
+    }
+
+    pub fn out_bytes(request_id: u32, compression: Compression) -> Vec<u8> {
+        let object = C2SHelloPacket {
+            _type: PacketType::Hello,
+            request_id,
+            protocol_version: PROTOCOL_VERSION,
+            compression,
+        };
+        object.to_bytes().unwrap()
+    }
+
+    /// Reads the version of a hello from any protocol revision, before the
+    /// rest of the packet is known to be decodable.
+    pub fn peek_protocol_version(data: &[u8]) -> Option<u16> {
+        let bytes = data.get(5..7)?;
+        Some(u16::from_be_bytes(bytes.try_into().unwrap()))
+    }
+}
+
+impl S2CServerInfoPacket {
+    pub fn parse(data: &[u8]) -> Result<Self, ProtocolError> {
+        decode(data, PacketType::ServerInfo)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn out_bytes(
+        request_id: u32,
+        service_version: String,
+        fake_read_write: bool,
+        backends: Vec<String>,
+        max_read_size: u32,
+        capabilities: u64,
+        compression: Compression,
+        compression_threshold: u32,
+    ) -> Vec<u8> {
+        let object = S2CServerInfoPacket {
+            _type: PacketType::ServerInfo,
+            request_id,
+            protocol_version: PROTOCOL_VERSION,
+            service_version: EncodedString::new(service_version),
+            fake_read_write,
+            backend_count: backends.len() as u32,
+            backends: backends.into_iter().map(EncodedString::new).collect(),
+            max_read_size,
+            capabilities,
+            compression,
+            compression_threshold,
+        };
+        object.to_bytes().unwrap()
+    }
+
+    pub fn has_capability(&self, capability: u64) -> bool {
+        self.capabilities & capability == capability
+    }
+}
+
+// Borrowed views decoding frames without copying their strings and payloads,
+// for region and process lists with thousands of entries or large reads. They
+// read the same layout as the deku structs through `Reader`, as deku's bit-level
+// reader is far slower than slicing the frame.
+
+/// Cursor over a frame for the borrowed views.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// Checks the packet type and skips it.
+    fn new(data: &'a [u8], expected: PacketType) -> Result<Self, ProtocolError> {
+        check_packet_type(data, expected)?;
+        Ok(Self { data: &data[1..] })
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], DekuError> {
+        if count > self.data.len() {
+            return Err(DekuError::Incomplete(deku::error::NeedSize::new(count * 8)));
+        }
+        let (bytes, rest) = self.data.split_at(count);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], DekuError> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, DekuError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, DekuError> {
+        self.array().map(u32::from_be_bytes)
+    }
+
+    fn i32(&mut self) -> Result<i32, DekuError> {
+        self.array().map(i32::from_be_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64, DekuError> {
+        self.array().map(u64::from_be_bytes)
+    }
+
+    /// A length prefix, checked like `checked_count` before anything is allocated for it.
+    fn count(&mut self, element_size: usize) -> Result<usize, DekuError> {
+        let count = self.u32()?;
+        if (count as usize).saturating_mul(element_size) > self.data.len() {
+            return Err(DekuError::Parse(format!(
+                "count {} exceeds the {} remaining bytes",
+                count,
+                self.data.len()
+            )));
+        }
+        Ok(count as usize)
+    }
+
+    fn string(&mut self) -> Result<EncodedStr<'a>, DekuError> {
+        let length = self.count(1)?;
+        Ok(EncodedStr {
+            length: length as u32,
+            string: self.bytes(length)?,
+        })
+    }
+
+    fn compression(&mut self) -> Result<Compression, DekuError> {
+        match self.u8()? {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Lz4),
+            value => Err(DekuError::Parse(format!(
+                "Could not match enum variant id = {:?} on enum `Compression`",
+                value
+            ))),
+        }
+    }
+
+    fn finish<T>(self, value: T) -> Result<T, ProtocolError> {
+        if !self.data.is_empty() {
+            return Err(ProtocolError::TrailingBytes(self.data.len()));
+        }
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncodedStr<'a> {
+    pub length: u32,
+    pub string: &'a [u8],
+}
+
+impl<'a> EncodedStr<'a> {
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.string
+    }
+
+    pub fn as_str(&self) -> Result<&'a str, std::str::Utf8Error> {
+        std::str::from_utf8(self.string)
+    }
+
+    /// The string for display, with invalid UTF-8 replaced by U+FFFD.
+    pub fn to_string_lossy(&self) -> Cow<'a, str> {
+        String::from_utf8_lossy(self.string)
+    }
+
+    pub fn to_owned(&self) -> EncodedString {
+        EncodedString {
+            length: self.length,
+            string: self.string.to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RegionRef<'a> {
+    pub start: u64,
+    pub end: u64,
+    pub size: u64,
+    pub permissions: u8,
+    pub offset: u64,
+    pub device: EncodedStr<'a>,
+    pub inode: u64,
+    pub pathname: EncodedStr<'a>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ProcessEntryRef<'a> {
+    pub name: EncodedStr<'a>,
+    pub pid: Pid,
+}
+
+/// Borrowed `S2CTargetPidRegionsPacket`.
+#[derive(Debug, PartialEq)]
+pub struct S2CTargetPidRegionsView<'a> {
+    pub request_id: u32,
+    pub regions: Vec<RegionRef<'a>>,
 }
 
-impl EncodedString {
-    //todo!("Avoid copying the string");
-    pub fn new(string: String) -> Self {
-        Self {
-            length: string.len() as u32,
-            string: string.as_bytes().to_vec(),
-        }
-    }
+/// Borrowed `S2CSendProcessesPacket`.
+#[derive(Debug, PartialEq)]
+pub struct S2CSendProcessesView<'a> {
+    pub request_id: u32,
+    pub processes: Vec<ProcessEntryRef<'a>>,
+}
 
-    pub fn to_string(&self) -> String {
-        String::from_utf8(self.string.clone()).unwrap()
-    }
+/// Borrowed `S2CReadMemoryPacketResponse`.
+#[derive(Debug, PartialEq)]
+pub struct S2CReadMemoryResponseView<'a> {
+    pub request_id: u32,
+    pub compression: Compression,
+    pub uncompressed_size: u32,
+    pub data: &'a [u8],
 }
 
+/// Borrowed `S2CReadChunkPacket`.
+#[derive(Debug, PartialEq)]
+pub struct S2CReadChunkView<'a> {
+    pub request_id: u32,
+    pub offset: u64,
+    pub compression: Compression,
+    pub uncompressed_size: u32,
+    pub data: &'a [u8],
+}
 
-impl PacketType {
-    pub fn from_u8(value: u8) -> Option<Self> {
-        match value {
-            0 => Some(Self::Read),
-            1 => Some(Self::Write),
-            2 => Some(Self::TargetPID),
-            3 => Some(Self::SendProcesses),
-            _ => None,
+impl<'a> S2CTargetPidRegionsView<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, ProtocolError> {
+        let mut reader = Reader::new(data, PacketType::Regions)?;
+        let request_id = reader.u32()?;
+        let count = reader.count(REGION_MIN_SIZE)?;
+        let mut regions = Vec::with_capacity(count);
+        for _ in 0..count {
+            regions.push(RegionRef {
+                start: reader.u64()?,
+                end: reader.u64()?,
+                size: reader.u64()?,
+                permissions: reader.u8()?,
+                offset: reader.u64()?,
+                device: reader.string()?,
+                inode: reader.u64()?,
+                pathname: reader.string()?,
+            });
         }
+        reader.finish(Self { request_id, regions })
     }
 }
 
-impl C2STargetPidPacket {
-    pub fn parse(data: &[u8]) -> Self {
-        let (_, value) = C2STargetPidPacket::from_bytes((data, 0)).unwrap();
-        value
-    }
-
-    pub fn out_bytes(target_pid: Pid) -> Vec<u8> {
-        let object = C2STargetPidPacket {
-            _type: PacketType::TargetPID,
-            target_pid,
-        };
-        object.to_bytes().unwrap()
+impl<'a> S2CSendProcessesView<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, ProtocolError> {
+        let mut reader = Reader::new(data, PacketType::Processes)?;
+        let request_id = reader.u32()?;
+        let count = reader.count(PROCESS_ENTRY_MIN_SIZE)?;
+        let mut processes = Vec::with_capacity(count);
+        for _ in 0..count {
+            processes.push(ProcessEntryRef {
+                name: reader.string()?,
+                pid: reader.i32()?,
+            });
+        }
+        reader.finish(Self { request_id, processes })
     }
 }
 
-impl C2SReadMemoryPacket {
-    pub fn parse(data: &[u8]) -> Self {
-        let (_, value) = C2SReadMemoryPacket::from_bytes((data, 0)).unwrap();
-        value
+impl<'a> S2CReadMemoryResponseView<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, ProtocolError> {
+        let mut reader = Reader::new(data, PacketType::ReadResponse)?;
+        let request_id = reader.u32()?;
+        let compression = reader.compression()?;
+        let uncompressed_size = reader.u32()?;
+        let count = reader.count(1)?;
+        let data = reader.bytes(count)?;
+        reader.finish(Self {
+            request_id,
+            compression,
+            uncompressed_size,
+            data,
+        })
     }
 
-    pub fn out_bytes(address: u64, size: u32) -> Vec<u8> {
-        let object = C2SReadMemoryPacket {
-            _type: PacketType::Read,
-            address,
-            size,
-        };
-        object.to_bytes().unwrap()
+    /// The memory that was read, only copied if it has to be decompressed.
+    pub fn payload(&self) -> Result<Cow<'a, [u8]>, ProtocolError> {
+        decompress(self.compression, self.uncompressed_size, Cow::Borrowed(self.data))
     }
 }
 
-impl C2SWriteMemoryPacket {
-    pub fn parse(data: &[u8]) -> Self {
-        let (_, value) = C2SWriteMemoryPacket::from_bytes((data, 0)).unwrap();
-        value
+impl<'a> S2CReadChunkView<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, ProtocolError> {
+        let mut reader = Reader::new(data, PacketType::ReadChunk)?;
+        let request_id = reader.u32()?;
+        let offset = reader.u64()?;
+        let compression = reader.compression()?;
+        let uncompressed_size = reader.u32()?;
+        let count = reader.count(1)?;
+        let data = reader.bytes(count)?;
+        reader.finish(Self {
+            request_id,
+            offset,
+            compression,
+            uncompressed_size,
+            data,
+        })
     }
 
-    pub fn out_bytes(address: u64, bytes: Vec<u8>) -> Vec<u8> {
-        let object = C2SWriteMemoryPacket {
-            _type: PacketType::Write,
-            address,
-            count: bytes.len() as u32,
-            bytes,
-        };
-        object.to_bytes().unwrap()
+    /// The memory of this chunk, only copied if it has to be decompressed.
+    pub fn payload(&self) -> Result<Cow<'a, [u8]>, ProtocolError> {
+        decompress(self.compression, self.uncompressed_size, Cow::Borrowed(self.data))
     }
 }
 
-impl C2SGetProcessesPacket {
-    pub fn parse(data: &[u8]) -> Self {
-        let (_, value) = C2SGetProcessesPacket::from_bytes((data, 0)).unwrap();
-        value
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_memory_packet() {
+        let data = C2SReadMemoryPacket::out_bytes(42, 1337, 100);
+        let packet = C2SReadMemoryPacket::parse(&data).unwrap();
+
+        assert_eq!(
+            C2SReadMemoryPacket {
+                _type: PacketType::Read,
+                request_id: 42,
+                address: 1337,
+                size: 100,
+            },
+            packet
+        );
     }
 
-    pub fn out_bytes() -> Vec<u8> {
-        let object = C2SGetProcessesPacket {
-            _type: PacketType::SendProcesses,
-        };
-        object.to_bytes().unwrap()
+    #[test]
+    fn test_page_chunks() {
+        let chunks: Vec<(u64, usize)> = page_chunks(4000, 5000, 4096).collect();
+        assert_eq!(vec![(4000, 96), (4096, 4096), (8192, 808)], chunks);
+
+        assert_eq!(0, page_chunks(4096, 0, 4096).count());
+        assert_eq!(3, page_chunks(10, 3, 0).count());
     }
-}
 
-impl S2CReadMemoryPacketResponse {
-    pub fn parse(data: &[u8]) -> Self {
-        let (_, value) = S2CReadMemoryPacketResponse::from_bytes((data, 0)).unwrap();
-        value
+    #[test]
+    fn test_read_tolerant_packet_response() {
+        let data = S2CReadTolerantPacketResponse::out_bytes(42, 4000, 4096, vec![7; 5000], &[true, false, true]);
+        let packet = S2CReadTolerantPacketResponse::parse(&data).unwrap();
+
+        assert_eq!(42, packet.request_id);
+        assert_eq!(vec![0b101], packet.bitmap);
+        assert!(packet.is_page_readable(0));
+        assert!(!packet.is_page_readable(1));
+        assert!(packet.is_page_readable(2));
+        assert!(!packet.is_page_readable(3));
+        assert_eq!(vec![(0, 96), (4192, 808)], packet.readable_ranges());
     }
 
-    pub fn out_bytes(data: Vec<u8>) -> Vec<u8> {
-        let object = S2CReadMemoryPacketResponse {
-            _type: PacketType::Read,
-            count: data.len() as u32,
-            data,
+    #[test]
+    fn test_scan_packets() {
+        let data = C2SStartScanPacket::out_bytes(
+            42,
+            ValueType::F32,
+            Endianness::Little,
+            0,
+            ScanComparison::Between,
+            1.5f32.to_le_bytes().to_vec(),
+            2.5f32.to_le_bytes().to_vec(),
+        );
+        let packet = C2SStartScanPacket::parse(&data).unwrap();
+        assert_eq!(ValueType::F32, packet.value_type);
+        assert_eq!(4, packet.value_type.size());
+        assert_eq!(ScanComparison::Between, packet.comparison);
+        assert_eq!(2.5f32.to_le_bytes().to_vec(), packet.upper);
+
+        let data = C2SNextScanPacket::out_bytes(43, ScanComparison::Increased, vec![], vec![]);
+        let packet = C2SNextScanPacket::parse(&data).unwrap();
+        assert_eq!(ScanComparison::Increased, packet.comparison);
+        assert!(packet.value.is_empty());
+
+        let data = S2CScanResultsPacket::out_bytes(44, 1000, 10, vec![ScanResult::new(0x1000, vec![1, 0, 0, 0])]);
+        let packet = S2CScanResultsPacket::parse(&data).unwrap();
+        assert_eq!((1000, 10), (packet.total, packet.offset));
+        assert_eq!(vec![ScanResult::new(0x1000, vec![1, 0, 0, 0])], packet.results);
+
+        let data = S2CScanStatusPacket::out_bytes(45, 7, false);
+        assert_eq!(7, S2CScanStatusPacket::parse(&data).unwrap().result_count);
+
+        let data = C2SPointerScanPacket::out_bytes(52, "health".to_string(), 0x5000, 4, 0x800, true);
+        let packet = C2SPointerScanPacket::parse(&data).unwrap();
+        assert_eq!((0x5000, 4, 0x800, true), (packet.target, packet.max_depth, packet.max_offset, packet.rescan));
+
+        let paths = vec![PointerPath::new(b"/usr/lib/libgame.so".to_vec(), 0x1A2B30, vec![0x18, 0x40])];
+        let data = S2CPointerScanResultsPacket::out_bytes(53, 3, 1, paths.clone());
+        let packet = S2CPointerScanResultsPacket::parse(&data).unwrap();
+        assert_eq!((3, 1), (packet.total, packet.offset));
+        assert_eq!(paths, packet.paths);
+
+        let data = C2SResolvePointerChainPacket::out_bytes(54, b"libgame.so".to_vec(), 0x1A2B30, vec![0x18, -8], 4);
+        let packet = C2SResolvePointerChainPacket::parse(&data).unwrap();
+        assert_eq!(b"libgame.so", packet.module.as_bytes());
+        assert_eq!((0x1A2B30, vec![0x18, -8], 4), (packet.base, packet.offsets, packet.read_size));
+
+        let data = S2CResolvePointerChainPacketResponse::failed(55, vec![0x1000, 0x2018], 14);
+        let packet = S2CResolvePointerChainPacketResponse::parse(&data).unwrap();
+        assert_eq!((false, 1, 14), (packet.success, packet.failed_step, packet.errno));
+        let data = S2CResolvePointerChainPacketResponse::ok(56, vec![0x1000], vec![1, 2]);
+        let packet = S2CResolvePointerChainPacketResponse::parse(&data).unwrap();
+        assert_eq!((true, vec![1, 2]), (packet.success, packet.data));
+
+        let data = C2SResolveExpressionPacket::out_bytes(57, "$heap+0x100".to_string(), 8);
+        let packet = C2SResolveExpressionPacket::parse(&data).unwrap();
+        assert_eq!((b"$heap+0x100".as_slice(), 8), (packet.expression.as_bytes(), packet.read_size));
+        let data = S2CResolveExpressionPacketResponse::out_bytes(58, 0x1100, vec![7]);
+        let packet = S2CResolveExpressionPacketResponse::parse(&data).unwrap();
+        assert_eq!((0x1100, vec![7]), (packet.address, packet.data));
+    }
+
+    #[test]
+    fn test_pattern_scan_packets() {
+        let filter = RegionFilter::new(1 | 4, b"libgame".to_vec());
+        let data = C2SPatternScanPacket::out_bytes(46, filter.clone(), "48 8B 05 ?? ?? ?? ??".to_string(), 0, true, 3, 7);
+        let packet = C2SPatternScanPacket::parse(&data).unwrap();
+        assert_eq!(filter, packet.filter);
+        assert_eq!(Ok("48 8B 05 ?? ?? ?? ??"), packet.pattern.as_str());
+        assert_eq!((true, 3, 7), (packet.resolve, packet.displacement_offset, packet.instruction_length));
+
+        let region = |permissions, pathname: &[u8]| Region {
+            start: 0,
+            end: 0x1000,
+            size: 0x1000,
+            permissions,
+            offset: 0,
+            device: EncodedString::new("0:0".to_string()),
+            inode: 0,
+            pathname: EncodedString::from_bytes(pathname.to_vec()),
         };
-        object.to_bytes().unwrap()
+        assert!(filter.matches(&region(1 | 4 | 16, b"/usr/lib/libgame.so")));
+        assert!(!filter.matches(&region(1 | 2 | 16, b"/usr/lib/libgame.so")));
+        assert!(!filter.matches(&region(1 | 4 | 16, b"/usr/lib/libc.so.6")));
+        assert!(RegionFilter::new(0, Vec::new()).matches(&region(0, b"[Anonymous]")));
+
+        let matches = vec![PatternMatch { address: 0x1000, resolved: 0x2000 }];
+        let data = S2CPatternScanPacketResponse::out_bytes(47, true, matches.clone());
+        let packet = S2CPatternScanPacketResponse::parse(&data).unwrap();
+        assert!(packet.truncated);
+        assert_eq!(matches, packet.matches);
     }
-}
 
-impl S2CWriteMemoryPacketResponse {
-    pub fn parse(data: &[u8]) -> Self {
-        let (_, value) = S2CWriteMemoryPacketResponse::from_bytes((data, 0)).unwrap();
-        value
+    #[test]
+    fn test_string_and_regex_search_packets() {
+        let data = C2SStringSearchPacket::out_bytes(
+            48,
+            RegionFilter::new(1 | 2, Vec::new()),
+            "Player".to_string(),
+            true,
+            true,
+            true,
+            16,
+            100,
+        );
+        let packet = C2SStringSearchPacket::parse(&data).unwrap();
+        assert_eq!(Ok("Player"), packet.text.as_str());
+        assert_eq!((true, true, true, 16, 100), (packet.utf8, packet.utf16, packet.ignore_case, packet.context, packet.max_results));
+
+        let hits = vec![StringHit::new(0x1010, 12, StringEncoding::Utf16Le, 0x1000, vec![0; 44])];
+        let data = S2CStringSearchPacketResponse::out_bytes(49, false, hits.clone());
+        let packet = S2CStringSearchPacketResponse::parse(&data).unwrap();
+        assert_eq!(hits, packet.hits);
+
+        let data = C2SRegexSearchPacket::out_bytes(50, RegionFilter::new(1, Vec::new()), r"https?://\S+".to_string(), 0, 256);
+        let packet = C2SRegexSearchPacket::parse(&data).unwrap();
+        assert_eq!(Ok(r"https?://\S+"), packet.pattern.as_str());
+        assert_eq!((0, 256), (packet.max_results, packet.max_match_length));
+
+        let matches = vec![RegexMatch::new(0x2000, b"http://a".to_vec())];
+        let data = S2CRegexSearchPacketResponse::out_bytes(51, true, matches.clone());
+        assert_eq!(matches, S2CRegexSearchPacketResponse::parse(&data).unwrap().matches);
     }
-    pub fn out_bytes(bytes_written: u64) -> Vec<u8> {
-        let object = S2CWriteMemoryPacketResponse {
-            _type: PacketType::Write,
-            bytes_written,
-        };
-        object.to_bytes().unwrap()
+
+    #[test]
+    fn test_read_stream_packets() {
+        let data = C2SReadStreamPacket::out_bytes(42, 0x7f0000000000, 1 << 33, 65536);
+        let packet = C2SReadStreamPacket::parse(&data).unwrap();
+        assert_eq!(
+            C2SReadStreamPacket {
+                _type: PacketType::ReadStream,
+                request_id: 42,
+                address: 0x7f0000000000,
+                size: 1 << 33,
+                chunk_size: 65536,
+            },
+            packet
+        );
+
+        let data = C2SCancelStreamPacket::out_bytes(43, 42);
+        let packet = C2SCancelStreamPacket::parse(&data).unwrap();
+        assert_eq!(
+            C2SCancelStreamPacket {
+                _type: PacketType::CancelStream,
+                request_id: 43,
+                stream_request_id: 42,
+            },
+            packet
+        );
+
+        let data = S2CReadChunkPacket::out_bytes(42, 65536, vec![0; 1024], Compression::Lz4);
+        let packet = S2CReadChunkPacket::parse(&data).unwrap();
+        assert_eq!(65536, packet.offset);
+        assert_eq!(vec![0; 1024], packet.payload().unwrap());
+
+        let data = S2CReadStreamEndPacket::out_bytes(42, StreamEnd::Cancelled, 0, 65536);
+        let packet = S2CReadStreamEndPacket::parse(&data).unwrap();
+        assert_eq!(
+            S2CReadStreamEndPacket {
+                _type: PacketType::ReadStreamEnd,
+                request_id: 42,
+                reason: StreamEnd::Cancelled,
+                errno: 0,
+                bytes_sent: 65536,
+            },
+            packet
+        );
     }
-}
 
-impl S2CTargetPidRegionsPacket {
-    pub fn parse(data: &[u8]) -> Self {
-        let (_, value) = S2CTargetPidRegionsPacket::from_bytes((data, 0)).unwrap();
-        value
+    #[test]
+    fn test_freeze_packets() {
+        let data = C2SFreezePacket::out_bytes(60, "[$heap+0x10]+0x8".to_string(), 50, vec![0xE7, 0x03, 0, 0]);
+        let packet = C2SFreezePacket::parse(&data).unwrap();
+        assert_eq!(b"[$heap+0x10]+0x8", packet.expression.as_bytes());
+        assert_eq!((50, vec![0xE7, 0x03, 0, 0]), (packet.interval_ms, packet.bytes));
+
+        let data = C2SUnfreezePacket::out_bytes(61, 60);
+        assert_eq!(60, C2SUnfreezePacket::parse(&data).unwrap().freeze_request_id);
+
+        let data = S2CFreezePacketResponse::out_bytes(60, 0x1010);
+        assert_eq!(0x1010, S2CFreezePacketResponse::parse(&data).unwrap().address);
+
+        let data = S2CFreezeEndPacket::out_bytes(60, FreezeEnd::Failed, 14);
+        let packet = S2CFreezeEndPacket::parse(&data).unwrap();
+        assert_eq!((60, FreezeEnd::Failed, 14), (packet.request_id, packet.reason, packet.errno));
     }
-    pub fn out_bytes(regions: Vec<Region>) -> Vec<u8> {
-        let object = S2CTargetPidRegionsPacket {
-            _type: PacketType::TargetPID,
-            count: regions.len() as u32,
-            regions: regions,
-        };
-        object.to_bytes().unwrap()
+
+    #[test]
+    fn test_watch_packets() {
+        let targets = vec![WatchTarget::new("0x1000".to_string(), 4), WatchTarget::new("[$heap]+0x8".to_string(), 8)];
+        let data = C2SWatchPacket::out_bytes(62, 250, true, targets.clone());
+        let packet = C2SWatchPacket::parse(&data).unwrap();
+        assert_eq!((250, true), (packet.interval_ms, packet.every_tick));
+        assert_eq!(targets, packet.targets);
+
+        let data = C2SUnwatchPacket::out_bytes(63, 62);
+        assert_eq!(62, C2SUnwatchPacket::parse(&data).unwrap().watch_request_id);
+
+        let values = vec![
+            WatchValue {
+                index: 0,
+                address: 0x1000,
+                result: ReadManyResult::ok(vec![1, 2, 3, 4]),
+            },
+            WatchValue {
+                index: 1,
+                address: 0,
+                result: ReadManyResult::failed(14),
+            },
+        ];
+        let data = S2CWatchUpdatePacket::out_bytes(62, 1_700_000_000_000_000, values);
+        let packet = S2CWatchUpdatePacket::parse(&data).unwrap();
+        assert_eq!(1_700_000_000_000_000, packet.timestamp_us);
+        assert_eq!(vec![1, 2, 3, 4], packet.values[0].result.data);
+        assert_eq!((1, 14), (packet.values[1].index, packet.values[1].result.errno));
+
+        assert_eq!(62, S2CWatchEndPacket::parse(&S2CWatchEndPacket::out_bytes(62)).unwrap().request_id);
     }
-}
 
-impl S2CSendProcessesPacket {
-    pub fn parse(data: &[u8]) -> Self {
-        let (_, value) = S2CSendProcessesPacket::from_bytes((data, 0)).unwrap();
-        value
+    #[test]
+    fn test_snapshot_packets() {
+        let filter = RegionFilter::new(2, b"[Heap]".to_vec());
+        let data = C2STakeSnapshotPacket::out_bytes(64, b"before".to_vec(), filter.clone(), true);
+        let packet = C2STakeSnapshotPacket::parse(&data).unwrap();
+        assert_eq!((b"before".as_slice(), &filter, true), (packet.name.as_bytes(), &packet.filter, packet.to_disk));
+
+        let data = C2SDiffSnapshotsPacket::out_bytes(65, b"before".to_vec(), Vec::new(), 10);
+        let packet = C2SDiffSnapshotsPacket::parse(&data).unwrap();
+        assert_eq!((b"before".as_slice(), b"".as_slice(), 10), (packet.old.as_bytes(), packet.new.as_bytes(), packet.max_results));
+
+        let data = C2SDeleteSnapshotPacket::out_bytes(66, b"before".to_vec());
+        assert_eq!(b"before", C2SDeleteSnapshotPacket::parse(&data).unwrap().name.as_bytes());
+
+        let data = S2CSnapshotStatusPacket::out_bytes(64, 3, 1 << 32, true);
+        let packet = S2CSnapshotStatusPacket::parse(&data).unwrap();
+        assert_eq!((3, 1 << 32, true), (packet.region_count, packet.size, packet.on_disk));
+
+        let ranges = vec![ChangedRange::new(0x1000, vec![1, 2], vec![3, 4]), ChangedRange::new(0x2000, vec![5], vec![6])];
+        let data = S2CDiffSnapshotsPacketResponse::out_bytes(65, true, ranges.clone());
+        let packet = S2CDiffSnapshotsPacketResponse::parse(&data).unwrap();
+        assert_eq!((true, ranges), (packet.truncated, packet.ranges));
     }
 
-    pub fn out_bytes(processes: Vec<ProcessEntry>) -> Vec<u8> {
-        let object = S2CSendProcessesPacket {
-            _type: PacketType::SendProcesses,
-            count: processes.len() as u32,
-            processes: processes,
-        };
-        object.to_bytes().unwrap()
+    #[test]
+    fn test_select_backend_packets() {
+        let data = C2SSelectBackendPacket::out_bytes(67, "procmem".to_string());
+        assert_eq!(b"procmem", C2SSelectBackendPacket::parse(&data).unwrap().backend.as_bytes());
+
+        let data = S2CSelectBackendPacketResponse::out_bytes(67, "procmem".to_string());
+        let packet = S2CSelectBackendPacketResponse::parse(&data).unwrap();
+        assert_eq!((67, b"procmem".as_slice()), (packet.request_id, packet.backend.as_bytes()));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_read_many_packet() {
+        let ranges = vec![
+            ReadRange { address: 1337, size: 4 },
+            ReadRange { address: 4096, size: 8 },
+        ];
+        let data = C2SReadManyPacket::out_bytes(42, ranges.clone());
+        let packet = C2SReadManyPacket::parse(&data).unwrap();
+
+        assert_eq!(
+            C2SReadManyPacket {
+                _type: PacketType::ReadMany,
+                request_id: 42,
+                count: 2,
+                ranges,
+            },
+            packet
+        );
+    }
 
     #[test]
-    fn test_read_memory_packet() {
-        let data = C2SReadMemoryPacket::out_bytes(1337, 100);
-        let packet = C2SReadMemoryPacket::parse(&data);
+    fn test_read_many_packet_response() {
+        let results = vec![ReadManyResult::ok(vec![1, 2, 3, 4]), ReadManyResult::failed(14)];
+        let data = S2CReadManyPacketResponse::out_bytes(42, results);
+        let packet = S2CReadManyPacketResponse::parse(&data).unwrap();
 
         assert_eq!(
-            C2SReadMemoryPacket {
-                _type: PacketType::Read,
-                address: 1337,
-                size: 100,
+            S2CReadManyPacketResponse {
+                _type: PacketType::ReadManyResponse,
+                request_id: 42,
+                count: 2,
+                results: vec![
+                    ReadManyResult {
+                        success: true,
+                        errno: 0,
+                        count: 4,
+                        data: vec![1, 2, 3, 4],
+                    },
+                    ReadManyResult {
+                        success: false,
+                        errno: 14,
+                        count: 0,
+                        data: vec![],
+                    },
+                ],
             },
             packet
         );
@@ -281,12 +3353,13 @@ mod tests {
 
     #[test]
     fn test_write_memory_packet() {
-        let data = C2SWriteMemoryPacket::out_bytes(1337, vec![123, 255]);
-        let packet = C2SWriteMemoryPacket::parse(&data);
+        let data = C2SWriteMemoryPacket::out_bytes(42, 1337, vec![123, 255]);
+        let packet = C2SWriteMemoryPacket::parse(&data).unwrap();
 
         assert_eq!(
             C2SWriteMemoryPacket {
                 _type: PacketType::Write,
+                request_id: 42,
                 address: 1337,
                 count: 2,
                 bytes: vec![123, 255],
@@ -295,14 +3368,71 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_write_many_packet() {
+        let entries = vec![WriteEntry::new(1337, vec![1, 2]), WriteEntry::new(4096, vec![])];
+        let data = C2SWriteManyPacket::out_bytes(42, entries);
+        let packet = C2SWriteManyPacket::parse(&data).unwrap();
+
+        assert_eq!(
+            C2SWriteManyPacket {
+                _type: PacketType::WriteMany,
+                request_id: 42,
+                count: 2,
+                entries: vec![
+                    WriteEntry {
+                        address: 1337,
+                        count: 2,
+                        bytes: vec![1, 2],
+                    },
+                    WriteEntry {
+                        address: 4096,
+                        count: 0,
+                        bytes: vec![],
+                    },
+                ],
+            },
+            packet
+        );
+    }
+
+    #[test]
+    fn test_write_many_packet_response() {
+        let results = vec![WriteManyResult::ok(2), WriteManyResult::failed(14)];
+        let data = S2CWriteManyPacketResponse::out_bytes(42, results);
+        let packet = S2CWriteManyPacketResponse::parse(&data).unwrap();
+
+        assert_eq!(
+            S2CWriteManyPacketResponse {
+                _type: PacketType::WriteManyResponse,
+                request_id: 42,
+                count: 2,
+                results: vec![
+                    WriteManyResult {
+                        success: true,
+                        errno: 0,
+                        bytes_written: 2,
+                    },
+                    WriteManyResult {
+                        success: false,
+                        errno: 14,
+                        bytes_written: 0,
+                    },
+                ],
+            },
+            packet
+        );
+    }
+
     #[test]
     fn test_target_pid_packet() {
-        let data = C2STargetPidPacket::out_bytes(1234567890);
-        let packet = C2STargetPidPacket::parse(&data);
+        let data = C2STargetPidPacket::out_bytes(42, 1234567890);
+        let packet = C2STargetPidPacket::parse(&data).unwrap();
 
         assert_eq!(
             C2STargetPidPacket {
                 _type: PacketType::TargetPID,
+                request_id: 42,
                 target_pid: 1234567890,
             },
             packet
@@ -313,12 +3443,15 @@ mod tests {
     fn test_read_memory_packet_response() {
         let test_payload = vec![255, 100, 50, 25, 10];
 
-        let response_data = S2CReadMemoryPacketResponse::out_bytes(test_payload);
-        let parsed_response = S2CReadMemoryPacketResponse::parse(&response_data);
+        let response_data = S2CReadMemoryPacketResponse::out_bytes(42, test_payload);
+        let parsed_response = S2CReadMemoryPacketResponse::parse(&response_data).unwrap();
 
         assert_eq!(
             S2CReadMemoryPacketResponse {
-                _type: PacketType::Read,
+                _type: PacketType::ReadResponse,
+                request_id: 42,
+                compression: Compression::None,
+                uncompressed_size: 5,
                 count: 5,
                 data: vec![255, 100, 50, 25, 10],
             },
@@ -326,21 +3459,180 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_compressed_read_memory_packet_response() {
+        let memory: Vec<u8> = (0..4096).map(|i| (i / 64) as u8).collect();
+
+        let response_data = S2CReadMemoryPacketResponse::out_bytes_compressed(42, memory.clone(), Compression::Lz4);
+        let parsed_response = S2CReadMemoryPacketResponse::parse(&response_data).unwrap();
+
+        assert_eq!(Compression::Lz4, parsed_response.compression);
+        assert_eq!(4096, parsed_response.uncompressed_size);
+        assert!(parsed_response.data.len() < memory.len());
+        assert_eq!(memory, parsed_response.payload().unwrap());
+    }
+
     #[test]
     fn test_write_memory_packet_response() {
         const BYTES_WRITTEN: u64 = 100;
-        let response_data = S2CWriteMemoryPacketResponse::out_bytes(BYTES_WRITTEN);
-        let parsed_response = S2CWriteMemoryPacketResponse::parse(&response_data);
+        let response_data = S2CWriteMemoryPacketResponse::out_bytes(42, BYTES_WRITTEN);
+        let parsed_response = S2CWriteMemoryPacketResponse::parse(&response_data).unwrap();
 
         assert_eq!(
             S2CWriteMemoryPacketResponse {
-                _type: PacketType::Write,
+                _type: PacketType::WriteResponse,
+                request_id: 42,
                 bytes_written: 100,
             },
             parsed_response
         );
     }
 
+    #[test]
+    fn test_error_packet() {
+        let data = S2CErrorPacket::out_bytes(42, ErrorCode::BadAddress, 14, PacketType::Read as u8, "Bad address".to_string());
+        let packet = S2CErrorPacket::parse(&data).unwrap();
+
+        assert_eq!(
+            S2CErrorPacket {
+                _type: PacketType::Error,
+                request_id: 42,
+                code: ErrorCode::BadAddress,
+                errno: 14,
+                packet_type: PacketType::Read as u8,
+                message: EncodedString::new("Bad address".to_string()),
+            },
+            packet
+        );
+    }
+
+    #[test]
+    fn test_hello_packet() {
+        let data = C2SHelloPacket::out_bytes(42, Compression::Lz4);
+        let packet = C2SHelloPacket::parse(&data).unwrap();
+
+        assert_eq!(
+            C2SHelloPacket {
+                _type: PacketType::Hello,
+                request_id: 42,
+                protocol_version: PROTOCOL_VERSION,
+                compression: Compression::Lz4,
+            },
+            packet
+        );
+        assert_eq!(Some(PROTOCOL_VERSION), C2SHelloPacket::peek_protocol_version(&data));
+        assert_eq!(Some(1), C2SHelloPacket::peek_protocol_version(&[PacketType::Hello as u8, 0, 0, 0, 42, 0, 1]));
+    }
+
+    #[test]
+    fn test_server_info_packet() {
+        let data = S2CServerInfoPacket::out_bytes(
+            42,
+            "0.1.0".to_string(),
+            false,
+            vec!["process_vm".to_string()],
+            4096,
+            capabilities::READ | capabilities::WRITE,
+            Compression::Lz4,
+            65536,
+        );
+        let packet = S2CServerInfoPacket::parse(&data).unwrap();
+
+        assert_eq!(
+            S2CServerInfoPacket {
+                _type: PacketType::ServerInfo,
+                request_id: 42,
+                protocol_version: PROTOCOL_VERSION,
+                service_version: EncodedString::new("0.1.0".to_string()),
+                fake_read_write: false,
+                backend_count: 1,
+                backends: vec![EncodedString::new("process_vm".to_string())],
+                max_read_size: 4096,
+                capabilities: capabilities::READ | capabilities::WRITE,
+                compression: Compression::Lz4,
+                compression_threshold: 65536,
+            },
+            packet
+        );
+        assert!(packet.has_capability(capabilities::WRITE));
+        assert!(!packet.has_capability(capabilities::PROCESS_LIST));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Err(ProtocolError::Empty), C2SReadMemoryPacket::parse(&[]));
+        assert_eq!(Err(ProtocolError::UnknownPacketType(200)), C2SReadMemoryPacket::parse(&[200, 0, 0]));
+
+        let data = C2STargetPidPacket::out_bytes(42, 1337);
+        assert_eq!(
+            Err(ProtocolError::UnexpectedPacketType { expected: PacketType::Read, found: PacketType::TargetPID as u8 }),
+            C2SReadMemoryPacket::parse(&data)
+        );
+    }
+
+    #[test]
+    fn test_borrowed_views() {
+        let regions = vec![Region {
+            start: 0x1000,
+            end: 0x2000,
+            size: 0x1000,
+            permissions: 5,
+            offset: 0,
+            device: EncodedString::new("8:1".to_string()),
+            inode: 42,
+            pathname: EncodedString::new("/usr/lib/libc.so.6".to_string()),
+        }];
+        let data = S2CTargetPidRegionsPacket::out_bytes(42, regions);
+        let view = S2CTargetPidRegionsView::parse(&data).unwrap();
+        assert_eq!(42, view.request_id);
+        assert_eq!(Ok("/usr/lib/libc.so.6"), view.regions[0].pathname.as_str());
+        assert_eq!(S2CTargetPidRegionsPacket::parse(&data).unwrap().regions[0].device, view.regions[0].device.to_owned());
+
+        let mut buffer = vec![0xFF; 64];
+        S2CReadMemoryPacketResponse::out_bytes_into(42, &[1, 2, 3], Compression::None, &mut buffer);
+        assert_eq!(S2CReadMemoryPacketResponse::out_bytes(42, vec![1, 2, 3]), buffer);
+        let view = S2CReadMemoryResponseView::parse(&buffer).unwrap();
+        assert!(matches!(view.payload(), Ok(Cow::Borrowed(&[1, 2, 3]))));
+
+        S2CReadChunkPacket::out_bytes_into(42, 4096, &[0; 1024], Compression::Lz4, &mut buffer);
+        let view = S2CReadChunkView::parse(&buffer).unwrap();
+        assert_eq!(4096, view.offset);
+        assert_eq!(vec![0; 1024], view.payload().unwrap().into_owned());
+    }
+
+    #[test]
+    fn test_packet_enums() {
+        let data = C2SReadMemoryPacket::out_bytes(42, 0x1000, 16);
+        let packet = C2SPacket::decode(&data).unwrap();
+        assert_eq!(PacketType::Read, packet.packet_type());
+        assert_eq!(42, packet.request_id());
+        assert_eq!(data, packet.encode());
+
+        // A request is not a response, even though they share a name.
+        assert_eq!(
+            Err(ProtocolError::UnknownPacketType(PacketType::Read as u8)),
+            S2CPacket::decode(&data)
+        );
+
+        let data = S2CReadMemoryPacketResponse::out_bytes(42, vec![1, 2, 3]);
+        match S2CPacket::decode(&data).unwrap() {
+            S2CPacket::ReadResponse(packet) => assert_eq!(vec![1, 2, 3], packet.payload().unwrap()),
+            packet => panic!("decoded {:?}", packet),
+        }
+        assert_eq!(Err(ProtocolError::Empty), C2SPacket::decode(&[]));
+    }
+
+    #[test]
+    fn test_peek_request_id() {
+        let data = C2SReadMemoryPacket::out_bytes(0xDEADBEEF, 1337, 100);
+        assert_eq!(Some(0xDEADBEEF), peek_request_id(&data));
+
+        let data = S2CWriteMemoryPacketResponse::out_bytes(7, 100);
+        assert_eq!(Some(7), peek_request_id(&data));
+
+        assert_eq!(None, peek_request_id(&[PacketType::Read as u8, 0, 0]));
+    }
+
     #[test]
     fn test_target_pid_regions() {
             
@@ -366,12 +3658,13 @@ mod tests {
                 pathname: EncodedString::new("/home/username/Projects/memflow-web-service/target/debug/memflow-web-service".to_string()),
             },
         ];
-        let data = S2CTargetPidRegionsPacket::out_bytes(test_regions);
-        let parsed_response = S2CTargetPidRegionsPacket::parse(&data);
+        let data = S2CTargetPidRegionsPacket::out_bytes(42, test_regions);
+        let parsed_response = S2CTargetPidRegionsPacket::parse(&data).unwrap();
 
         assert_eq!(
             S2CTargetPidRegionsPacket {
-                _type: PacketType::TargetPID,
+                _type: PacketType::Regions,
+                request_id: 42,
                 count: 2,
                 regions: vec![
                     Region {
@@ -410,16 +3703,17 @@ mod tests {
             },
             ProcessEntry {
                 name: EncodedString::new("memflow-web-service-2".to_string()),
-                pid: 0987654321,
+                pid: 987654321,
             },
         ];
 
-        let data = S2CSendProcessesPacket::out_bytes(test_processes);
-        let packet = S2CSendProcessesPacket::parse(&data);
+        let data = S2CSendProcessesPacket::out_bytes(42, test_processes);
+        let packet = S2CSendProcessesPacket::parse(&data).unwrap();
 
         assert_eq!(
             S2CSendProcessesPacket {
-                _type: PacketType::SendProcesses,
+                _type: PacketType::Processes,
+                request_id: 42,
                 count: 2,
                 processes: vec![
                     ProcessEntry {
@@ -428,7 +3722,7 @@ mod tests {
                     },
                     ProcessEntry {
                         name: EncodedString::new("memflow-web-service-2".to_string()),
-                        pid: 0987654321,
+                        pid: 987654321,
                     },
                 ],
             },
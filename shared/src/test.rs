@@ -0,0 +1,150 @@
+//! Property tests making sure no frame, however broken, can panic the decoders.
+
+use crate::protocol::*;
+use proptest::prelude::*;
+
+/// Runs every decoder over `data`; none of them may panic.
+fn decode_all(data: &[u8]) {
+    let _ = C2SPacket::decode(data);
+    match S2CPacket::decode(data) {
+        Ok(S2CPacket::ReadResponse(packet)) => {
+            let _ = packet.payload();
+        }
+        Ok(S2CPacket::ReadChunk(packet)) => {
+            let _ = packet.payload();
+        }
+        _ => {}
+    }
+    let _ = S2CTargetPidRegionsView::parse(data);
+    let _ = S2CSendProcessesView::parse(data);
+    if let Ok(packet) = S2CReadMemoryResponseView::parse(data) {
+        let _ = packet.payload();
+    }
+    if let Ok(packet) = S2CReadChunkView::parse(data) {
+        let _ = packet.payload();
+    }
+}
+
+fn region() -> impl Strategy<Value = Region> {
+    // Paths are raw OS bytes and need not be UTF-8.
+    let pathname = proptest::collection::vec(any::<u8>(), 0..64);
+    (any::<[u64; 5]>(), any::<u8>(), ".{0,8}", pathname).prop_map(
+        |([start, end, size, offset, inode], permissions, device, pathname)| Region {
+            start,
+            end,
+            size,
+            permissions,
+            offset,
+            device: EncodedString::new(device),
+            inode,
+            pathname: EncodedString::from_bytes(pathname),
+        },
+    )
+}
+
+fn packet_type() -> impl Strategy<Value = u8> {
+    prop_oneof![0u8..32, 0x80u8..0xA0, any::<u8>()]
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_bytes_never_panic(data in proptest::collection::vec(any::<u8>(), 0..256)) {
+        decode_all(&data);
+    }
+
+    #[test]
+    fn arbitrary_bodies_never_panic(
+        packet_type in packet_type(),
+        body in proptest::collection::vec(any::<u8>(), 0..256),
+    ) {
+        let mut data = vec![packet_type];
+        data.extend(body);
+        decode_all(&data);
+    }
+
+    #[test]
+    fn views_agree_with_owned_decoders(
+        packet_type in prop_oneof![Just(PacketType::Regions), Just(PacketType::Processes), Just(PacketType::ReadResponse)],
+        body in proptest::collection::vec(any::<u8>(), 0..256),
+    ) {
+        let mut data = vec![packet_type as u8];
+        data.extend(body);
+        prop_assert_eq!(
+            S2CTargetPidRegionsPacket::parse(&data).is_ok(),
+            S2CTargetPidRegionsView::parse(&data).is_ok()
+        );
+        prop_assert_eq!(
+            S2CSendProcessesPacket::parse(&data).is_ok(),
+            S2CSendProcessesView::parse(&data).is_ok()
+        );
+        prop_assert_eq!(
+            S2CReadMemoryPacketResponse::parse(&data).map(|packet| packet.data),
+            S2CReadMemoryResponseView::parse(&data).map(|view| view.data.to_vec())
+        );
+    }
+
+    #[test]
+    fn huge_length_prefixes_are_rejected(request_id: u32, count in 1024u32.., tail in proptest::collection::vec(any::<u8>(), 0..64)) {
+        let mut data = vec![PacketType::Regions as u8];
+        data.extend(request_id.to_be_bytes());
+        data.extend(count.to_be_bytes());
+        data.extend(tail);
+        prop_assert!(S2CTargetPidRegionsPacket::parse(&data).is_err());
+    }
+
+    #[test]
+    fn truncated_frames_are_rejected(
+        request_id: u32,
+        address: u64,
+        bytes in proptest::collection::vec(any::<u8>(), 0..64),
+        cut in any::<prop::sample::Index>(),
+    ) {
+        let data = C2SWriteMemoryPacket::out_bytes(request_id, address, bytes);
+        let cut = cut.index(data.len());
+        prop_assert!(C2SWriteMemoryPacket::parse(&data[..cut]).is_err());
+    }
+
+    #[test]
+    fn trailing_garbage_is_rejected(
+        request_id: u32,
+        address: u64,
+        size: u32,
+        garbage in proptest::collection::vec(any::<u8>(), 1..16),
+    ) {
+        let mut data = C2SReadMemoryPacket::out_bytes(request_id, address, size);
+        data.extend(&garbage);
+        prop_assert_eq!(
+            Err(ProtocolError::TrailingBytes(garbage.len())),
+            C2SReadMemoryPacket::parse(&data)
+        );
+    }
+
+    #[test]
+    fn compressed_payloads_round_trip(request_id: u32, memory in proptest::collection::vec(0u8..4, 0..4096)) {
+        let data = S2CReadMemoryPacketResponse::out_bytes_compressed(request_id, memory.clone(), Compression::Lz4);
+        let packet = S2CReadMemoryPacketResponse::parse(&data).unwrap();
+        prop_assert_eq!(memory, packet.payload().unwrap());
+    }
+
+    #[test]
+    fn region_lists_round_trip(request_id: u32, regions in proptest::collection::vec(region(), 0..32)) {
+        let data = S2CTargetPidRegionsPacket::out_bytes(request_id, regions.clone());
+        let packet = S2CTargetPidRegionsPacket::parse(&data).unwrap();
+        prop_assert_eq!(request_id, packet.request_id);
+        prop_assert_eq!(&regions, &packet.regions);
+
+        let view = S2CTargetPidRegionsView::parse(&data).unwrap();
+        for (region, view) in regions.iter().zip(&view.regions) {
+            prop_assert_eq!(&region.pathname.string[..], view.pathname.as_bytes());
+        }
+    }
+
+    #[test]
+    fn write_packets_round_trip(request_id: u32, address: u64, bytes in proptest::collection::vec(any::<u8>(), 0..256)) {
+        let data = C2SWriteMemoryPacket::out_bytes(request_id, address, bytes.clone());
+        let packet = C2SWriteMemoryPacket::parse(&data).unwrap();
+        prop_assert_eq!(request_id, packet.request_id);
+        prop_assert_eq!(address, packet.address);
+        prop_assert_eq!(bytes, packet.bytes);
+    }
+}
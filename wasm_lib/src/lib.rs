@@ -1,3 +1,4 @@
+use shared::expression::Expression;
 use shared::protocol::*;
 use wasm_bindgen::prelude::*;
 
@@ -6,62 +7,903 @@ pub fn greet(name: &str) -> String {
     format!("Hello, {}!", name)
 }
 
+/// Mirror of `shared::protocol::ErrorCode` exported to JS.
 #[wasm_bindgen]
-pub fn parse_payload_to_string(msg: &[u8]) -> String {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ServiceErrorCode {
+    NoTarget = 0,
+    NoSuchProcess = 1,
+    PermissionDenied = 2,
+    BadAddress = 3,
+    PartialTransfer = 4,
+    MalformedPacket = 5,
+    UnknownPacketType = 6,
+    IncompatibleVersion = 7,
+    HandshakeRequired = 8,
+    RequestTooLarge = 9,
+    NoScan = 10,
+    InvalidArgument = 11,
+    Other = 255,
+}
+
+impl From<ErrorCode> for ServiceErrorCode {
+    fn from(code: ErrorCode) -> Self {
+        match code {
+            ErrorCode::NoTarget => Self::NoTarget,
+            ErrorCode::NoSuchProcess => Self::NoSuchProcess,
+            ErrorCode::PermissionDenied => Self::PermissionDenied,
+            ErrorCode::BadAddress => Self::BadAddress,
+            ErrorCode::PartialTransfer => Self::PartialTransfer,
+            ErrorCode::MalformedPacket => Self::MalformedPacket,
+            ErrorCode::UnknownPacketType => Self::UnknownPacketType,
+            ErrorCode::IncompatibleVersion => Self::IncompatibleVersion,
+            ErrorCode::HandshakeRequired => Self::HandshakeRequired,
+            ErrorCode::RequestTooLarge => Self::RequestTooLarge,
+            ErrorCode::NoScan => Self::NoScan,
+            ErrorCode::InvalidArgument => Self::InvalidArgument,
+            ErrorCode::Other => Self::Other,
+        }
+    }
+}
+
+/// Mirror of `shared::protocol::ValueType` exported to JS.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ServiceValueType {
+    I8 = 0,
+    I16 = 1,
+    I32 = 2,
+    I64 = 3,
+    U8 = 4,
+    U16 = 5,
+    U32 = 6,
+    U64 = 7,
+    F32 = 8,
+    F64 = 9,
+}
+
+impl From<ServiceValueType> for ValueType {
+    fn from(value_type: ServiceValueType) -> Self {
+        match value_type {
+            ServiceValueType::I8 => Self::I8,
+            ServiceValueType::I16 => Self::I16,
+            ServiceValueType::I32 => Self::I32,
+            ServiceValueType::I64 => Self::I64,
+            ServiceValueType::U8 => Self::U8,
+            ServiceValueType::U16 => Self::U16,
+            ServiceValueType::U32 => Self::U32,
+            ServiceValueType::U64 => Self::U64,
+            ServiceValueType::F32 => Self::F32,
+            ServiceValueType::F64 => Self::F64,
+        }
+    }
+}
+
+/// Mirror of `shared::protocol::ScanComparison` exported to JS.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ServiceScanComparison {
+    Equal = 0,
+    Changed = 1,
+    Unchanged = 2,
+    Increased = 3,
+    Decreased = 4,
+    Between = 5,
+    Unknown = 6,
+    IncreasedBy = 7,
+    DecreasedBy = 8,
+}
+
+impl From<ServiceScanComparison> for ScanComparison {
+    fn from(comparison: ServiceScanComparison) -> Self {
+        match comparison {
+            ServiceScanComparison::Equal => Self::Equal,
+            ServiceScanComparison::Changed => Self::Changed,
+            ServiceScanComparison::Unchanged => Self::Unchanged,
+            ServiceScanComparison::Increased => Self::Increased,
+            ServiceScanComparison::Decreased => Self::Decreased,
+            ServiceScanComparison::Between => Self::Between,
+            ServiceScanComparison::Unknown => Self::Unknown,
+            ServiceScanComparison::IncreasedBy => Self::IncreasedBy,
+            ServiceScanComparison::DecreasedBy => Self::DecreasedBy,
+        }
+    }
+}
+
+/// Paths and process names are raw bytes that need not be UTF-8. This renders
+/// them for display, replacing invalid sequences with U+FFFD.
+#[wasm_bindgen]
+pub fn bytes_to_display_string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// A decoded `S2CErrorPacket`, thrown on the JS side by `parse_payload_to_string`.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct ServiceError {
+    request_id: u32,
+    code: ServiceErrorCode,
+    errno: i32,
+    packet_type: u8,
+    message: String,
+}
+
+#[wasm_bindgen]
+impl ServiceError {
+    #[wasm_bindgen(getter)]
+    pub fn request_id(&self) -> u32 {
+        self.request_id
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn code(&self) -> ServiceErrorCode {
+        self.code
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn errno(&self) -> i32 {
+        self.errno
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn packet_type(&self) -> u8 {
+        self.packet_type
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        self.message.clone()
+    }
+}
+
+impl From<S2CErrorPacket> for ServiceError {
+    fn from(packet: S2CErrorPacket) -> Self {
+        Self {
+            request_id: packet.request_id,
+            code: packet.code.into(),
+            errno: packet.errno,
+            packet_type: packet.packet_type,
+            message: packet.message.to_string_lossy().into_owned(),
+        }
+    }
+}
+
+impl ServiceError {
+    fn malformed(msg: &[u8], error: ProtocolError) -> Self {
+        Self {
+            request_id: peek_request_id(msg).unwrap_or(0),
+            code: error.code().into(),
+            errno: 0,
+            packet_type: msg.first().copied().unwrap_or(0),
+            message: error.to_string(),
+        }
+    }
+
+    fn unexpected(msg: &[u8], expected: PacketType) -> Self {
+        Self::malformed(
+            msg,
+            ProtocolError::UnexpectedPacketType {
+                expected,
+                found: msg.first().copied().unwrap_or(0),
+            },
+        )
+    }
+}
+
+/// A decoded `S2CServerInfoPacket`.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct ServerInfo {
+    protocol_version: u16,
+    service_version: String,
+    fake_read_write: bool,
+    backends: Vec<String>,
+    max_read_size: u32,
+    capabilities: u64,
+    compressed: bool,
+    compression_threshold: u32,
+}
+
+#[wasm_bindgen]
+impl ServerInfo {
+    #[wasm_bindgen(getter)]
+    pub fn protocol_version(&self) -> u16 {
+        self.protocol_version
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn service_version(&self) -> String {
+        self.service_version.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn fake_read_write(&self) -> bool {
+        self.fake_read_write
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn backends(&self) -> Vec<String> {
+        self.backends.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn max_read_size(&self) -> u32 {
+        self.max_read_size
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn capabilities(&self) -> u64 {
+        self.capabilities
+    }
+
+    /// Whether large read payloads will arrive compressed; `read_response_data` undoes it.
+    #[wasm_bindgen(getter)]
+    pub fn compressed(&self) -> bool {
+        self.compressed
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn compression_threshold(&self) -> u32 {
+        self.compression_threshold
+    }
+
+    pub fn has_capability(&self, capability: u64) -> bool {
+        self.capabilities & capability == capability
+    }
+}
+
+/// Decodes any service response, turning error packets into `Err`.
+fn decode_response(msg: &[u8]) -> Result<S2CPacket, ServiceError> {
+    match S2CPacket::decode(msg).map_err(|e| ServiceError::malformed(msg, e))? {
+        S2CPacket::Error(packet) => Err(packet.into()),
+        packet => Ok(packet),
+    }
+}
+
+/// Decodes the answer to `hello_packet_data`.
+#[wasm_bindgen]
+pub fn parse_server_info(msg: &[u8]) -> Result<ServerInfo, ServiceError> {
+    let S2CPacket::ServerInfo(packet) = decode_response(msg)? else {
+        return Err(ServiceError::unexpected(msg, PacketType::ServerInfo));
+    };
+    Ok(ServerInfo {
+        protocol_version: packet.protocol_version,
+        service_version: packet.service_version.to_string_lossy().into_owned(),
+        fake_read_write: packet.fake_read_write,
+        backends: packet.backends.iter().map(|backend| backend.to_string_lossy().into_owned()).collect(),
+        max_read_size: packet.max_read_size,
+        capabilities: packet.capabilities,
+        compressed: packet.compression != Compression::None,
+        compression_threshold: packet.compression_threshold,
+    })
+}
+
+/// The memory carried by a read response, decompressed if needed.
+#[wasm_bindgen]
+pub fn read_response_data(msg: &[u8]) -> Result<Vec<u8>, ServiceError> {
+    if msg.first() != Some(&(PacketType::ReadResponse as u8)) {
+        decode_response(msg)?;
+        return Err(ServiceError::unexpected(msg, PacketType::ReadResponse));
+    }
+    // Borrow the payload so it is copied once, into the returned buffer.
+    S2CReadMemoryResponseView::parse(msg)
+        .and_then(|packet| packet.payload().map(|payload| payload.into_owned()))
+        .map_err(|e| ServiceError::malformed(msg, e))
+}
+
+/// A decoded `S2CReadChunkPacket`, or the `S2CReadStreamEndPacket` closing the stream.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct ReadChunk {
+    request_id: u32,
+    offset: u64,
+    data: Vec<u8>,
+    end: Option<StreamEnd>,
+    errno: i32,
+}
+
+#[wasm_bindgen]
+impl ReadChunk {
+    #[wasm_bindgen(getter)]
+    pub fn request_id(&self) -> u32 {
+        self.request_id
+    }
+
+    /// Offset of `data` relative to the streamed address. For the end of the
+    /// stream this is the number of bytes that were sent.
+    #[wasm_bindgen(getter)]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn is_end(&self) -> bool {
+        self.end.is_some()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn cancelled(&self) -> bool {
+        self.end == Some(StreamEnd::Cancelled)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn failed(&self) -> bool {
+        self.end == Some(StreamEnd::Failed)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn target_changed(&self) -> bool {
+        self.end == Some(StreamEnd::TargetChanged)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn errno(&self) -> i32 {
+        self.errno
+    }
+}
+
+/// Decodes one message of a read stream.
+#[wasm_bindgen]
+pub fn parse_read_chunk(msg: &[u8]) -> Result<ReadChunk, ServiceError> {
+    match decode_response(msg)? {
+        S2CPacket::ReadStreamEnd(packet) => Ok(ReadChunk {
+            request_id: packet.request_id,
+            offset: packet.bytes_sent,
+            data: Vec::new(),
+            end: Some(packet.reason),
+            errno: packet.errno,
+        }),
+        S2CPacket::ReadChunk(packet) => {
+            let request_id = packet.request_id;
+            let offset = packet.offset;
+            let data = packet.payload().map_err(|e| ServiceError::malformed(msg, e))?;
+            Ok(ReadChunk {
+                request_id,
+                offset,
+                data,
+                end: None,
+                errno: 0,
+            })
+        }
+        _ => Err(ServiceError::unexpected(msg, PacketType::ReadChunk)),
+    }
+}
+
+#[wasm_bindgen]
+pub fn parse_payload_to_string(msg: &[u8]) -> Result<String, ServiceError> {
     if msg.is_empty() {
-        return "Empty message".to_string();
+        return Ok("Empty message".to_string());
     }
-    match PacketType::from_u8(msg[0]) {
-        Some(PacketType::Read) => {
-            let packet = S2CReadMemoryPacketResponse::parse(&msg);
-            format!("Read: count: {}, data: {:?}", packet.count, packet.data)
+    Ok(match S2CPacket::decode(msg).map_err(|e| ServiceError::malformed(msg, e))? {
+        S2CPacket::ReadResponse(packet) => {
+            let request_id = packet.request_id;
+            let data = packet.payload().map_err(|e| ServiceError::malformed(msg, e))?;
+            format!("Read: request id: {}, count: {}, data: {:?}", request_id, data.len(), data)
+        }
+        S2CPacket::ReadTolerantResponse(packet) => {
+            format!(
+                "ReadTolerant: request id: {}, address: {}, count: {}, readable ranges: {:?}, data: {:?}",
+                packet.request_id,
+                packet.address,
+                packet.count,
+                packet.readable_ranges(),
+                packet.data
+            )
+        }
+        S2CPacket::ReadChunk(packet) => {
+            let request_id = packet.request_id;
+            let offset = packet.offset;
+            let data = packet.payload().map_err(|e| ServiceError::malformed(msg, e))?;
+            format!(
+                "ReadChunk: request id: {}, offset: {}, count: {}, data: {:?}",
+                request_id,
+                offset,
+                data.len(),
+                data
+            )
+        }
+        S2CPacket::ReadStreamEnd(packet) => {
+            format!(
+                "ReadStreamEnd: request id: {}, reason: {:?}, errno: {}, bytes sent: {}",
+                packet.request_id, packet.reason, packet.errno, packet.bytes_sent
+            )
+        }
+        S2CPacket::ReadManyResponse(packet) => {
+            let results = packet.results.iter().fold(String::new(), |acc, result| {
+                if result.success {
+                    acc + &format!("Ok: count: {}, data: {:?}\n", result.count, result.data)
+                } else {
+                    acc + &format!("Failed: errno: {}\n", result.errno)
+                }
+            });
+            format!(
+                "ReadMany: request id: {}, count: {}, results: {}\n",
+                packet.request_id, packet.count, results
+            )
         }
-        Some(PacketType::Write) => {
-            let packet = S2CWriteMemoryPacketResponse::parse(&msg);
-            format!("Write: bytes written: {}", packet.bytes_written)
+        S2CPacket::WriteResponse(packet) => {
+            format!("Write: request id: {}, bytes written: {}", packet.request_id, packet.bytes_written)
         }
-        Some(PacketType::TargetPID) => {
-            let packet = S2CTargetPidRegionsPacket::parse(&msg);
+        S2CPacket::WriteManyResponse(packet) => {
+            let results = packet.results.iter().fold(String::new(), |acc, result| {
+                if result.success {
+                    acc + &format!("Ok: bytes written: {}\n", result.bytes_written)
+                } else {
+                    acc + &format!("Failed: errno: {}\n", result.errno)
+                }
+            });
+            format!(
+                "WriteMany: request id: {}, count: {}, results: {}\n",
+                packet.request_id, packet.count, results
+            )
+        }
+        S2CPacket::Regions(packet) => {
             let regions_string = packet.regions.iter().fold(String::new(), |acc, region| {
-                acc + &format!("Start: {}, End: {}, Size: {}, Permissions: {}, Offset: {}, Device: {}, Inode: {}, Pathname: {}\n", region.start, region.end, region.size, region.permissions, region.offset, region.device.to_string(), region.inode, region.pathname.to_string())
+                acc + &format!("Start: {}, End: {}, Size: {}, Permissions: {}, Offset: {}, Device: {}, Inode: {}, Pathname: {}\n", region.start, region.end, region.size, region.permissions, region.offset, region.device.to_string_lossy(), region.inode, region.pathname.to_string_lossy())
             });
             format!(
-                "TargetPID: count: {}, regions: {}\n",
-                packet.count, regions_string
+                "TargetPID: request id: {}, count: {}, regions: {}\n",
+                packet.request_id, packet.count, regions_string
             )
         }
-        Some(PacketType::SendProcesses) => {
-            let packet = S2CSendProcessesPacket::parse(&msg);
+        S2CPacket::Processes(packet) => {
             let processes = packet.processes.iter().fold(String::new(), |acc, process| {
-                acc + &format!("Pid: {}, Name: {}\n", process.pid, process.name.to_string())
+                acc + &format!("Pid: {}, Name: {}\n", process.pid, process.name.to_string_lossy())
             });
             format!(
-                "SendProcesses: count: {} processes: {}\n",
-                packet.count, processes
+                "SendProcesses: request id: {}, count: {} processes: {}\n",
+                packet.request_id, packet.count, processes
             )
         }
-        None => {
-            format!("None")
+        S2CPacket::ServerInfo(packet) => {
+            format!(
+                "ServerInfo: request id: {}, protocol version: {}, service version: {}, fake read/write: {}, backends: {:?}, max read size: {}, capabilities: {:#x}",
+                packet.request_id,
+                packet.protocol_version,
+                packet.service_version.to_string_lossy(),
+                packet.fake_read_write,
+                packet.backends.iter().map(EncodedString::to_string_lossy).collect::<Vec<_>>(),
+                packet.max_read_size,
+                packet.capabilities
+            )
         }
-    }
+        S2CPacket::ScanStatus(packet) => {
+            format!(
+                "ScanStatus: request id: {}, results: {}, truncated: {}",
+                packet.request_id, packet.result_count, packet.truncated
+            )
+        }
+        S2CPacket::ScanResultsResponse(packet) => {
+            let results = packet.results.iter().fold(String::new(), |acc, result| {
+                acc + &format!("Address: {}, value: {:?}\n", result.address, result.value)
+            });
+            format!(
+                "ScanResults: request id: {}, total: {}, offset: {}, count: {}, results: {}\n",
+                packet.request_id, packet.total, packet.offset, packet.count, results
+            )
+        }
+        S2CPacket::PatternScanResponse(packet) => {
+            let matches = packet.matches.iter().fold(String::new(), |acc, found| {
+                acc + &format!("Address: {}, resolved: {}\n", found.address, found.resolved)
+            });
+            format!(
+                "PatternScan: request id: {}, truncated: {}, count: {}, matches: {}\n",
+                packet.request_id, packet.truncated, packet.count, matches
+            )
+        }
+        S2CPacket::StringSearchResponse(packet) => {
+            let hits = packet.hits.iter().fold(String::new(), |acc, hit| {
+                acc + &format!(
+                    "Address: {}, length: {}, encoding: {:?}, context: {}\n",
+                    hit.address,
+                    hit.length,
+                    hit.encoding,
+                    bytes_to_display_string(&hit.context)
+                )
+            });
+            format!(
+                "StringSearch: request id: {}, truncated: {}, count: {}, hits: {}\n",
+                packet.request_id, packet.truncated, packet.count, hits
+            )
+        }
+        S2CPacket::RegexSearchResponse(packet) => {
+            let matches = packet.matches.iter().fold(String::new(), |acc, found| {
+                acc + &format!(
+                    "Address: {}, count: {}, bytes: {}\n",
+                    found.address,
+                    found.count,
+                    bytes_to_display_string(&found.bytes)
+                )
+            });
+            format!(
+                "RegexSearch: request id: {}, truncated: {}, count: {}, matches: {}\n",
+                packet.request_id, packet.truncated, packet.count, matches
+            )
+        }
+        S2CPacket::PointerScanResultsResponse(packet) => {
+            let paths = packet.paths.iter().fold(String::new(), |acc, path| {
+                let offsets = path.offsets.iter().fold(String::new(), |acc, offset| acc + &format!(" -> +{:#x}", offset));
+                acc + &format!("{}+{:#x}{}\n", path.module.to_string_lossy(), path.module_offset, offsets)
+            });
+            format!(
+                "PointerScanResults: request id: {}, total: {}, offset: {}, count: {}, paths: {}\n",
+                packet.request_id, packet.total, packet.offset, packet.count, paths
+            )
+        }
+        S2CPacket::ResolvePointerChainResponse(packet) => {
+            let addresses = packet.addresses.iter().fold(String::new(), |acc, address| acc + &format!("{:#x} ", address));
+            if packet.success {
+                format!(
+                    "ResolvePointerChain: request id: {}, addresses: {}, data: {}\n",
+                    packet.request_id,
+                    addresses,
+                    bytes_to_display_string(&packet.data)
+                )
+            } else {
+                format!(
+                    "ResolvePointerChain: request id: {}, addresses: {}, failed at step {} with errno {}\n",
+                    packet.request_id, addresses, packet.failed_step, packet.errno
+                )
+            }
+        }
+        S2CPacket::ResolveExpressionResponse(packet) => format!(
+            "ResolveExpression: request id: {}, address: {:#x}, data: {}\n",
+            packet.request_id,
+            packet.address,
+            bytes_to_display_string(&packet.data)
+        ),
+        S2CPacket::FreezeResponse(packet) => format!(
+            "Freeze: request id: {}, address: {:#x}\n",
+            packet.request_id, packet.address
+        ),
+        S2CPacket::FreezeEnd(packet) => format!(
+            "FreezeEnd: request id: {}, reason: {:?}, errno: {}\n",
+            packet.request_id, packet.reason, packet.errno
+        ),
+        S2CPacket::WatchUpdate(packet) => {
+            let values = packet.values.iter().fold(String::new(), |acc, value| {
+                if value.result.success {
+                    acc + &format!("{}: {:#x}: {:?}\n", value.index, value.address, value.result.data)
+                } else {
+                    acc + &format!("{}: {:#x}: failed: errno: {}\n", value.index, value.address, value.result.errno)
+                }
+            });
+            format!(
+                "WatchUpdate: request id: {}, timestamp: {}us, values: {}\n",
+                packet.request_id, packet.timestamp_us, values
+            )
+        }
+        S2CPacket::WatchEnd(packet) => format!("WatchEnd: request id: {}\n", packet.request_id),
+        S2CPacket::SnapshotStatus(packet) => format!(
+            "SnapshotStatus: request id: {}, regions: {}, size: {}, on disk: {}\n",
+            packet.request_id, packet.region_count, packet.size, packet.on_disk
+        ),
+        S2CPacket::DiffSnapshotsResponse(packet) => {
+            let ranges = packet.ranges.iter().fold(String::new(), |acc, range| {
+                acc + &format!("{:#x}: {:?} -> {:?}\n", range.address, range.old, range.new)
+            });
+            format!(
+                "DiffSnapshots: request id: {}, truncated: {}, count: {}, ranges: {}\n",
+                packet.request_id, packet.truncated, packet.count, ranges
+            )
+        }
+        S2CPacket::SelectBackendResponse(packet) => format!(
+            "SelectBackend: request id: {}, backend: {}\n",
+            packet.request_id,
+            packet.backend.to_string_lossy()
+        ),
+        S2CPacket::Error(packet) => return Err(packet.into()),
+    })
+}
+
+/// Returns the request id echoed by a response, or `None` for a frame too short to carry one.
+#[wasm_bindgen]
+pub fn response_request_id(msg: &[u8]) -> Option<u32> {
+    peek_request_id(msg)
+}
+
+/// Must be the first packet sent on a new connection. With `accept_lz4` the
+/// service may compress large read payloads.
+#[wasm_bindgen]
+pub fn hello_packet_data(request_id: u32, accept_lz4: bool) -> Vec<u8> {
+    let compression = if accept_lz4 { Compression::Lz4 } else { Compression::None };
+    C2SHelloPacket::out_bytes(request_id, compression)
+}
+
+#[wasm_bindgen]
+pub fn target_pid_packet_data(request_id: u32, pid: i32) -> Vec<u8> {
+    C2STargetPidPacket::out_bytes(request_id, pid)
+}
+
+#[wasm_bindgen]
+pub fn read_memory_packet_data(request_id: u32, address: u64, size: u32) -> Vec<u8> {
+    C2SReadMemoryPacket::out_bytes(request_id, address, size)
+}
+
+/// Unreadable pages are zero-filled in the response instead of failing the request.
+#[wasm_bindgen]
+pub fn read_tolerant_packet_data(request_id: u32, address: u64, size: u32) -> Vec<u8> {
+    C2SReadTolerantPacket::out_bytes(request_id, address, size)
+}
+
+/// Streams `size` bytes as chunks of at most `chunk_size` bytes, see `parse_read_chunk`.
+#[wasm_bindgen]
+pub fn read_stream_packet_data(request_id: u32, address: u64, size: u64, chunk_size: u32) -> Vec<u8> {
+    C2SReadStreamPacket::out_bytes(request_id, address, size, chunk_size)
+}
+
+#[wasm_bindgen]
+pub fn cancel_stream_packet_data(request_id: u32, stream_request_id: u32) -> Vec<u8> {
+    C2SCancelStreamPacket::out_bytes(request_id, stream_request_id)
+}
+
+/// Starts a new scan of the target for values of `value_type` passing
+/// `comparison`, or snapshots it with `Unknown`. `value` and `upper` are the
+/// operands in the target's byte order, `upper` is only used by `Between`. An `alignment` of 0 means the
+/// size of the value.
+#[wasm_bindgen]
+pub fn start_scan_packet_data(
+    request_id: u32,
+    value_type: ServiceValueType,
+    little_endian: bool,
+    alignment: u32,
+    comparison: ServiceScanComparison,
+    value: &[u8],
+    upper: &[u8],
+) -> Vec<u8> {
+    let endianness = if little_endian { Endianness::Little } else { Endianness::Big };
+    C2SStartScanPacket::out_bytes(
+        request_id,
+        value_type.into(),
+        endianness,
+        alignment,
+        comparison.into(),
+        value.to_vec(),
+        upper.to_vec(),
+    )
+}
+
+#[wasm_bindgen]
+pub fn next_scan_packet_data(request_id: u32, comparison: ServiceScanComparison, value: &[u8], upper: &[u8]) -> Vec<u8> {
+    C2SNextScanPacket::out_bytes(request_id, comparison.into(), value.to_vec(), upper.to_vec())
+}
+
+#[wasm_bindgen]
+pub fn scan_results_packet_data(request_id: u32, offset: u64, limit: u32) -> Vec<u8> {
+    C2SScanResultsPacket::out_bytes(request_id, offset, limit)
+}
+
+#[wasm_bindgen]
+pub fn clear_scan_packet_data(request_id: u32) -> Vec<u8> {
+    C2SClearScanPacket::out_bytes(request_id)
+}
+
+/// Searches the regions having all `permissions` bits whose pathname contains
+/// `pathname` for an IDA-style `pattern` like `48 8B 05 ?? ?? ?? ??`. With
+/// `resolve` each match also carries the target of the RIP-relative
+/// displacement at `displacement_offset`.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn pattern_scan_packet_data(
+    request_id: u32,
+    permissions: u8,
+    pathname: &[u8],
+    pattern: String,
+    max_results: u32,
+    resolve: bool,
+    displacement_offset: u32,
+    instruction_length: u32,
+) -> Vec<u8> {
+    C2SPatternScanPacket::out_bytes(
+        request_id,
+        RegionFilter::new(permissions, pathname.to_vec()),
+        pattern,
+        max_results,
+        resolve,
+        displacement_offset,
+        instruction_length,
+    )
+}
+
+/// Searches the regions having all `permissions` bits whose pathname contains
+/// `pathname` for `text` as UTF-8 and/or UTF-16LE, returning up to `context`
+/// bytes around each hit.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn string_search_packet_data(
+    request_id: u32,
+    permissions: u8,
+    pathname: &[u8],
+    text: String,
+    utf8: bool,
+    utf16: bool,
+    ignore_case: bool,
+    context: u32,
+    max_results: u32,
+) -> Vec<u8> {
+    C2SStringSearchPacket::out_bytes(
+        request_id,
+        RegionFilter::new(permissions, pathname.to_vec()),
+        text,
+        utf8,
+        utf16,
+        ignore_case,
+        context,
+        max_results,
+    )
+}
+
+/// Searches the regions having all `permissions` bits whose pathname contains
+/// `pathname` for a `regex::bytes` pattern, cutting matches short after
+/// `max_match_length` bytes.
+#[wasm_bindgen]
+pub fn regex_search_packet_data(
+    request_id: u32,
+    permissions: u8,
+    pathname: &[u8],
+    pattern: String,
+    max_results: u32,
+    max_match_length: u32,
+) -> Vec<u8> {
+    C2SRegexSearchPacket::out_bytes(
+        request_id,
+        RegionFilter::new(permissions, pathname.to_vec()),
+        pattern,
+        max_results,
+        max_match_length,
+    )
+}
+
+/// Finds pointer paths from static memory to `target` and saves them under
+/// `name`, or with `rescan` keeps only the saved ones still leading to it.
+#[wasm_bindgen]
+pub fn pointer_scan_packet_data(
+    request_id: u32,
+    name: String,
+    target: u64,
+    max_depth: u8,
+    max_offset: u32,
+    rescan: bool,
+) -> Vec<u8> {
+    C2SPointerScanPacket::out_bytes(request_id, name, target, max_depth, max_offset, rescan)
+}
+
+#[wasm_bindgen]
+pub fn pointer_scan_results_packet_data(request_id: u32, name: String, offset: u64, limit: u32) -> Vec<u8> {
+    C2SPointerScanResultsPacket::out_bytes(request_id, name, offset, limit)
+}
+
+/// An empty `module` makes `base` an absolute address.
+#[wasm_bindgen]
+pub fn resolve_pointer_chain_packet_data(
+    request_id: u32,
+    module: String,
+    base: u64,
+    offsets: &[i64],
+    read_size: u32,
+) -> Vec<u8> {
+    C2SResolvePointerChainPacket::out_bytes(request_id, module.into_bytes(), base, offsets.to_vec(), read_size)
+}
+
+/// See `shared::expression` for the syntax of `expression`.
+#[wasm_bindgen]
+pub fn resolve_expression_packet_data(request_id: u32, expression: String, read_size: u32) -> Vec<u8> {
+    C2SResolveExpressionPacket::out_bytes(request_id, expression, read_size)
+}
+
+/// Keeps `bytes` written at `expression` every `interval_ms` milliseconds,
+/// 0 picks the service's default. Unfreeze with this request's id.
+#[wasm_bindgen]
+pub fn freeze_packet_data(request_id: u32, expression: String, interval_ms: u32, bytes: &[u8]) -> Vec<u8> {
+    C2SFreezePacket::out_bytes(request_id, expression, interval_ms, bytes.to_vec())
+}
+
+#[wasm_bindgen]
+pub fn unfreeze_packet_data(request_id: u32, freeze_request_id: u32) -> Vec<u8> {
+    C2SUnfreezePacket::out_bytes(request_id, freeze_request_id)
+}
+
+/// `expressions` and `sizes` describe the targets pairwise and must have the
+/// same length. Interval 0 picks the service's default.
+#[wasm_bindgen]
+pub fn watch_packet_data(
+    request_id: u32,
+    interval_ms: u32,
+    every_tick: bool,
+    expressions: Vec<String>,
+    sizes: &[u32],
+) -> Vec<u8> {
+    let targets = expressions
+        .into_iter()
+        .zip(sizes)
+        .map(|(expression, &size)| WatchTarget::new(expression, size))
+        .collect();
+    C2SWatchPacket::out_bytes(request_id, interval_ms, every_tick, targets)
+}
+
+#[wasm_bindgen]
+pub fn unwatch_packet_data(request_id: u32, watch_request_id: u32) -> Vec<u8> {
+    C2SUnwatchPacket::out_bytes(request_id, watch_request_id)
+}
+
+/// Snapshots the readable regions with all of the `permissions` bits whose
+/// pathname contains `pathname`, replacing any snapshot called `name`.
+#[wasm_bindgen]
+pub fn take_snapshot_packet_data(
+    request_id: u32,
+    name: String,
+    permissions: u8,
+    pathname: &[u8],
+    to_disk: bool,
+) -> Vec<u8> {
+    let filter = RegionFilter::new(permissions, pathname.to_vec());
+    C2STakeSnapshotPacket::out_bytes(request_id, name.into_bytes(), filter, to_disk)
+}
+
+/// An empty `new` compares `old` with the target's current memory.
+#[wasm_bindgen]
+pub fn diff_snapshots_packet_data(request_id: u32, old: String, new: String, max_results: u32) -> Vec<u8> {
+    C2SDiffSnapshotsPacket::out_bytes(request_id, old.into_bytes(), new.into_bytes(), max_results)
+}
+
+#[wasm_bindgen]
+pub fn delete_snapshot_packet_data(request_id: u32, name: String) -> Vec<u8> {
+    C2SDeleteSnapshotPacket::out_bytes(request_id, name.into_bytes())
+}
+
+/// `backend` is one of the names listed in the server info.
+#[wasm_bindgen]
+pub fn select_backend_packet_data(request_id: u32, backend: String) -> Vec<u8> {
+    C2SSelectBackendPacket::out_bytes(request_id, backend)
+}
+
+/// Checks an address expression as typed into the UI, returning its canonical
+/// form so it can be saved. Throws with the position of a syntax error.
+#[wasm_bindgen]
+pub fn format_address_expression(expression: &str) -> Result<String, JsError> {
+    Expression::parse(expression)
+        .map(|expression| expression.to_string())
+        .map_err(|error| JsError::new(&error.to_string()))
 }
 
+/// `addresses` and `sizes` describe the ranges pairwise and must have the same length.
 #[wasm_bindgen]
-pub fn target_pid_packet_data(pid: i32) -> Vec<u8> {
-    C2STargetPidPacket::out_bytes(pid)
+pub fn read_many_packet_data(request_id: u32, addresses: &[u64], sizes: &[u32]) -> Vec<u8> {
+    let ranges = addresses
+        .iter()
+        .zip(sizes)
+        .map(|(&address, &size)| ReadRange { address, size })
+        .collect();
+    C2SReadManyPacket::out_bytes(request_id, ranges)
 }
 
 #[wasm_bindgen]
-pub fn read_memory_packet_data(address: u64, size: u32) -> Vec<u8> {
-    C2SReadMemoryPacket::out_bytes(address, size)
+pub fn write_memory_packet_data(request_id: u32, address: u64, bytes: &[u8]) -> Vec<u8> {
+    C2SWriteMemoryPacket::out_bytes(request_id, address, bytes.to_vec())
 }
 
+/// Builds a batched write from parallel arrays: entry `i` writes
+/// `bytes[offsets[i]..offsets[i + 1]]` (or up to the end for the last one) to `addresses[i]`.
 #[wasm_bindgen]
-pub fn write_memory_packet_data(address: u64, bytes: &[u8]) -> Vec<u8> {
-    C2SWriteMemoryPacket::out_bytes(address, bytes.to_vec())
+pub fn write_many_packet_data(request_id: u32, addresses: &[u64], offsets: &[u32], bytes: &[u8]) -> Vec<u8> {
+    let entries = addresses
+        .iter()
+        .enumerate()
+        .map(|(i, &address)| {
+            let start = offsets.get(i).map_or(bytes.len(), |&offset| offset as usize).min(bytes.len());
+            let end = offsets.get(i + 1).map_or(bytes.len(), |&offset| offset as usize).clamp(start, bytes.len());
+            WriteEntry::new(address, bytes[start..end].to_vec())
+        })
+        .collect();
+    C2SWriteManyPacket::out_bytes(request_id, entries)
 }
 
 #[wasm_bindgen]
-pub fn get_processes_packet_data() -> Vec<u8> {
-    C2SGetProcessesPacket::out_bytes()
+pub fn get_processes_packet_data(request_id: u32) -> Vec<u8> {
+    C2SGetProcessesPacket::out_bytes(request_id)
 }
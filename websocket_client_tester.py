@@ -4,7 +4,11 @@ import websockets
 
 async def connect():
     async with websockets.connect("ws://127.0.0.1:8080") as websocket:
-        read_msg = b'\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01'
+        # type (Hello), request id, protocol version, compression (None)
+        hello_msg = b'\x05' + (0).to_bytes(4, 'big') + (3).to_bytes(2, 'big') + b'\x00'
+        await websocket.send(hello_msg)
+        # type (Read), request id, address, size
+        read_msg = b'\x00' + (1).to_bytes(4, 'big') + (0).to_bytes(8, 'big') + (1).to_bytes(4, 'big')
         await websocket.send(read_msg)
         while True:
             response = await websocket.recv()